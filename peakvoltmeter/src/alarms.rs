@@ -0,0 +1,79 @@
+use crate::{config::AlarmConfig, measurements::ChannelMeasurements};
+use serde::Serialize;
+
+/// Limit of a measurement that has been crossed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Alarm {
+    RmsLow,
+    RmsHigh,
+    PeakSqrtLow,
+    PeakSqrtHigh,
+    FrequencyLow,
+    FrequencyHigh,
+}
+
+impl Alarm {
+    // in the order of their bits
+    pub const ALL: [Alarm; 6] = [
+        Alarm::RmsLow,
+        Alarm::RmsHigh,
+        Alarm::PeakSqrtLow,
+        Alarm::PeakSqrtHigh,
+        Alarm::FrequencyLow,
+        Alarm::FrequencyHigh,
+    ];
+
+    pub fn bit(&self) -> u16 {
+        1 << Alarm::ALL
+            .iter()
+            .position(|alarm| alarm == self)
+            .expect("every alarm is listed")
+    }
+}
+
+/// Alarms of a channel, one bit per alarm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AlarmState(u16);
+
+impl AlarmState {
+    /// Compares the measurements of a channel with the limits, values that are not available yet
+    /// raise no alarm.
+    pub fn evaluate(limits: &AlarmConfig, measurements: &ChannelMeasurements) -> Self {
+        let below = |value: Option<f64>, limit: Option<f64>| matches!((value, limit), (Some(value), Some(limit)) if value < limit);
+        let above = |value: Option<f64>, limit: Option<f64>| matches!((value, limit), (Some(value), Some(limit)) if value > limit);
+
+        // in the order of `Alarm::ALL`
+        let crossed = [
+            below(measurements.rms, limits.rms_min),
+            above(measurements.rms, limits.rms_max),
+            below(measurements.peak_sqrt, limits.peak_sqrt_min),
+            above(measurements.peak_sqrt, limits.peak_sqrt_max),
+            below(measurements.frequency, limits.frequency_min),
+            above(measurements.frequency, limits.frequency_max),
+        ];
+
+        Self(
+            Alarm::ALL
+                .iter()
+                .zip(crossed)
+                .filter(|(_, crossed)| *crossed)
+                .fold(0, |bits, (alarm, _)| bits | alarm.bit()),
+        )
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.0 != 0
+    }
+
+    pub fn bits(&self) -> u16 {
+        self.0
+    }
+
+    pub fn alarms(&self) -> Vec<Alarm> {
+        Alarm::ALL
+            .into_iter()
+            .filter(|alarm| self.0 & alarm.bit() != 0)
+            .collect()
+    }
+}
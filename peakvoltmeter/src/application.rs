@@ -1,22 +1,43 @@
 use crate::{
-    frequency_widget::FrequencyWidget,
-    harmonics::Harmonics,
+    channel::{channel_color, channel_name, ChannelLayout, MAX_CHANNELS},
+    config::{
+        Config, DisplayConfig, IngestConfig, CALIBRATION_FACTOR_RANGE, CHART_SIZE_RANGE,
+        CYCLES_RANGE, FFT_SIZE_RANGE, FIXED_WIDTH_DEFAULT, FIXED_WIDTH_RANGE, PERIODS_RANGE,
+        PRECISION_RANGE, RATED_VOLTAGE_RANGE, REFRESH_PERIOD_RANGE, RMS_WINDOW_RANGE,
+        SAMPLE_RATE_RANGE, ZOOM_FACTOR_RANGE,
+    },
+    cycle_measurement::MeasurementMode,
+    export::Exporter,
+    frequency_widget::{FrequencyEstimator, FrequencyWidget},
+    harmonic_analysis::HarmonicTable,
+    harmonics::{FftWindow, Harmonics, SpectrumScale},
+    ingest::{
+        Endianness, GapHandling, LinkStatus, PacketFormat, SampleEncoding, StreamFraming, Transport,
+    },
     peak_sqrt_widget::PeakSqrtChart,
+    playback::{Playback, PlaybackBar},
+    profiles::ProfileSelector,
+    recorder::Recorder,
     rms_trend::RmsTrend,
     rms_widget::RmsWidget,
-    settings::{
-        CalibrationFactor, ChartSize, FftSize, RefreshPeriod, RmsWindow, SettingsPacket,
-        TimeChartPeriods,
-    },
+    settings::SettingsPacket,
+    three_phase::{PhasorDiagram, PHASES},
     time::Time,
     time_chart::TimeChart,
+    Buffers, MAX_WARNINGS,
 };
 use core::fmt;
-use egui::{Align, Layout, RichText, Style, Visuals};
+use egui::{Align, Color32, Layout, RichText, Style, Visuals};
+use serde::{Deserialize, Serialize};
 use std::{
     fmt::{Display, Formatter},
+    net::SocketAddr,
     ops::RangeInclusive,
-    sync::{mpsc::Sender, Arc, RwLock},
+    path::PathBuf,
+    sync::{
+        mpsc::{Receiver, Sender},
+        Arc, RwLock,
+    },
 };
 
 pub fn calculate_precision(range: &RangeInclusive<f64>) -> usize {
@@ -41,28 +62,19 @@ pub fn calculate_precision(range: &RangeInclusive<f64>) -> usize {
 
 pub type Precision = usize;
 
-const SAMPLE_RATE_DEFAULT: usize = 3125;
-const CALIBRATION_FACTOR_DEFAULT: CalibrationFactor = 0.00319929;
-const DEFAULT_UNIT: VoltageUnit = VoltageUnit::Volt;
-const DEFAULT_PRECISION: Precision = 2;
-const PERIODS_DEFAULT: TimeChartPeriods = 3;
 const CHART_X_BOUND_DEFAULT: usize = 187;
-const FFT_SIZE_DEFAULT: FftSize = 2048;
-const HARMONICS_REFRESH_PERIOD: RefreshPeriod = 0.2;
-const WINDOW_DEFAULT: RmsWindow = 0.5;
-const ZOOM_FACTOR_DEFAULT: f32 = 1.0;
-const CHART_SIZE_DEFAULT: ChartSize = 180;
-const RMS_REFRESH_PERIOD_DEFAULT: RefreshPeriod = 0.5;
 
 pub const CHART_X_BOUND_MARGIN: usize = 1;
 
 #[derive(PartialEq)]
 enum Panel {
     Charts,
+    Harmonics,
     Settings,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum VoltageUnit {
     Volt,
     KiloVolt,
@@ -78,14 +90,29 @@ impl Display for VoltageUnit {
 }
 
 impl VoltageUnit {
-    pub fn apply_unit_with_precision(&self, value: f64, precision: usize) -> String {
+    pub fn symbol(&self) -> &'static str {
         match self {
-            VoltageUnit::Volt => format!("{:.precision$} V", value, precision = precision),
-            VoltageUnit::KiloVolt => {
-                format!("{:.precision$} kV", value / 1000.0, precision = precision)
-            }
+            VoltageUnit::Volt => "V",
+            VoltageUnit::KiloVolt => "kV",
+        }
+    }
+
+    /// Converts a value in volts into the unit.
+    pub fn scale(&self, value: f64) -> f64 {
+        match self {
+            VoltageUnit::Volt => value,
+            VoltageUnit::KiloVolt => value / 1000.0,
         }
     }
+
+    pub fn apply_unit_with_precision(&self, value: f64, precision: usize) -> String {
+        format!(
+            "{:.precision$} {}",
+            self.scale(value),
+            self.symbol(),
+            precision = precision
+        )
+    }
 }
 
 pub struct Application {
@@ -96,101 +123,224 @@ pub struct Application {
     peak_sqrt_chart: PeakSqrtChart,
     rms_widget: RmsWidget,
     frequency_widget: FrequencyWidget,
+    harmonic_table: HarmonicTable,
+    // only with enough channels for three phases
+    phasor_diagram: Option<PhasorDiagram>,
+    link_status: LinkStatus,
+    recorder: Recorder,
+    exporter: Exporter,
+    // only while playing back a capture
+    playback_bar: Option<PlaybackBar>,
+    profile_selector: ProfileSelector,
 
     panel: Panel,
 
     settings_sender: Sender<SettingsPacket>,
+    // settings changed through the remote interfaces, already sent to the pipeline
+    remote_changes: Receiver<SettingsPacket>,
+
+    // all persisted settings, saved whenever they differ from `saved_config`
+    config: Config,
+    saved_config: Config,
+    config_path: Option<PathBuf>,
+    config_error: Option<String>,
+    // invalid values replaced by defaults while loading the config, remote interfaces that failed
+    // to start
+    warnings: Vec<String>,
+    // warnings of the background threads, moved into `warnings`
+    runtime_warnings: Arc<RwLock<Vec<String>>>,
 
-    // signal settings
-    sample_rate: usize,
-    calibration_factor: CalibrationFactor,
-    unit: VoltageUnit,
-    precision: Precision,
+    // channel count of the pipeline
+    channels: usize,
 
-    // general settings
-    zoom_factor: f32,
-    chart_size: ChartSize,
+    // network settings, including the command line overrides which are not persisted
+    ingest: IngestConfig,
+    // source the pipeline receives from, only the fields changed from it are persisted
+    applied_ingest: IngestConfig,
+    listen_address: String,
+    connect_address: String,
+    source_error: Option<String>,
 
     // time chart settings
-    periods: TimeChartPeriods,
     chart_x_bound: usize,
-
-    // harmonics and frequency settings
-    fft_size: FftSize,
-    harmonics_refresh_period: RefreshPeriod,
-
-    // rms trend and peak sqrt settings
-    window: RmsWindow,
-    rms_refresh_period: RefreshPeriod,
 }
 
 impl Application {
     pub fn new(
-        time_chart_data: Arc<RwLock<Vec<[f64; 2]>>>,
-        harmonics_data: Arc<RwLock<Vec<[f64; 2]>>>,
-        rms_trend_data: Arc<RwLock<Vec<[f64; 2]>>>,
-        peak_sqrt_data: Arc<RwLock<Vec<[f64; 2]>>>,
-        frequency_widget_data: Arc<RwLock<Vec<[f64; 2]>>>,
+        buffers: Buffers,
+        mut config: Config,
+        config_path: Option<PathBuf>,
+        warnings: Vec<String>,
+        ingest: IngestConfig,
         settings_sender: Sender<SettingsPacket>,
+        remote_changes: Receiver<SettingsPacket>,
     ) -> Self {
-        // Set default settings
-        settings_sender
-            .send(SettingsPacket::SampleRate(SAMPLE_RATE_DEFAULT as f32))
-            .unwrap();
-        settings_sender
-            .send(SettingsPacket::CalibrationFactor(
-                CALIBRATION_FACTOR_DEFAULT,
-            ))
-            .unwrap();
-        settings_sender
-            .send(SettingsPacket::ChartSize(CHART_SIZE_DEFAULT))
-            .unwrap();
-        settings_sender
-            .send(SettingsPacket::TimeChartPeriods(PERIODS_DEFAULT))
-            .unwrap();
-        settings_sender
-            .send(SettingsPacket::FftSize(FFT_SIZE_DEFAULT))
-            .unwrap();
-        settings_sender
-            .send(SettingsPacket::HarmonicsRefreshPeriod(
-                HARMONICS_REFRESH_PERIOD,
-            ))
-            .unwrap();
-        settings_sender
-            .send(SettingsPacket::Window(WINDOW_DEFAULT))
-            .unwrap();
-        settings_sender
-            .send(SettingsPacket::RmsRefreshPeriod(RMS_REFRESH_PERIOD_DEFAULT))
-            .unwrap();
+        let channels = buffers.channels.len();
+
+        // the pipeline starts with the stored settings
+        for packet in ingest
+            .settings_packets()
+            .into_iter()
+            .chain(config.measurement.settings_packets(channels))
+            .chain(buffers.playback.iter().flat_map(Playback::settings_packets))
+        {
+            settings_sender.send(packet).unwrap();
+        }
+
+        // every channel of the pipeline has its calibration factor
+        config.measurement.calibration_factors = (0..channels)
+            .map(|channel| config.measurement.calibration_factor(channel))
+            .collect();
 
         Self {
-            time_chart: TimeChart::new(time_chart_data),
-            harmonics: Harmonics::new(harmonics_data),
-            rms_trend: RmsTrend::new(rms_trend_data.clone()),
-            peak_sqrt_chart: PeakSqrtChart::new(peak_sqrt_data),
-            rms_widget: RmsWidget::new(rms_trend_data),
-            frequency_widget: FrequencyWidget::new(frequency_widget_data),
+            time_chart: TimeChart::new(buffers.per_channel(|channel| &channel.time_chart)),
+            harmonics: Harmonics::new(
+                buffers.per_channel(|channel| &channel.harmonics),
+                buffers.per_channel(|channel| &channel.harmonic_analysis),
+            ),
+            rms_trend: RmsTrend::new(buffers.per_channel(|channel| &channel.rms_trend)),
+            peak_sqrt_chart: PeakSqrtChart::new(buffers.per_channel(|channel| &channel.peak_sqrt)),
+            rms_widget: RmsWidget::new(buffers.per_channel(|channel| &channel.rms_trend)),
+            frequency_widget: FrequencyWidget::new(
+                buffers.per_channel(|channel| &channel.frequency_widget),
+            ),
+            harmonic_table: HarmonicTable::new(
+                buffers.per_channel(|channel| &channel.harmonic_analysis),
+            ),
+            phasor_diagram: (channels >= PHASES)
+                .then(|| PhasorDiagram::new(buffers.three_phase.clone())),
+            link_status: LinkStatus::new(buffers.link_statistics, buffers.connection_status),
+            exporter: Exporter::new(buffers.channels.clone()),
+            recorder: buffers.recorder,
+            playback_bar: buffers.playback.map(PlaybackBar::new),
+            profile_selector: ProfileSelector::new(),
             time: Time::new(),
             panel: Panel::Charts,
             settings_sender,
-            sample_rate: SAMPLE_RATE_DEFAULT,
-            calibration_factor: CALIBRATION_FACTOR_DEFAULT,
-            unit: DEFAULT_UNIT,
-            precision: DEFAULT_PRECISION,
-            zoom_factor: ZOOM_FACTOR_DEFAULT,
-            chart_size: CHART_SIZE_DEFAULT,
-            periods: PERIODS_DEFAULT,
+            remote_changes,
+            saved_config: config.clone(),
+            config,
+            config_path,
+            config_error: None,
+            warnings,
+            runtime_warnings: buffers.warnings,
+            channels,
+            ingest,
+            applied_ingest: ingest,
+            listen_address: ingest.listen_address.to_string(),
+            connect_address: ingest.connect_address.to_string(),
+            source_error: None,
             chart_x_bound: CHART_X_BOUND_DEFAULT,
-            fft_size: FFT_SIZE_DEFAULT,
-            harmonics_refresh_period: HARMONICS_REFRESH_PERIOD,
-            window: WINDOW_DEFAULT,
-            rms_refresh_period: RMS_REFRESH_PERIOD_DEFAULT,
+        }
+    }
+
+    /// Pushes all measurement settings into the pipeline at once, after a profile was selected.
+    fn apply_measurement(&mut self) {
+        let measurement = &mut self.config.measurement;
+
+        measurement.calibration_factors = (0..self.channels)
+            .map(|channel| measurement.calibration_factor(channel))
+            .collect();
+
+        self.settings_sender
+            .send(SettingsPacket::Batch(
+                measurement.settings_packets(self.channels),
+            ))
+            .unwrap();
+
+        self.apply_playback();
+    }
+
+    /// The sample rate and calibration factors of a capture played back take precedence over the
+    /// measurement settings, which are kept for later.
+    fn apply_playback(&self) {
+        if let Some(playback_bar) = &self.playback_bar {
+            self.settings_sender
+                .send(SettingsPacket::Batch(
+                    playback_bar.playback().settings_packets(),
+                ))
+                .unwrap();
+        }
+    }
+
+    fn save_config(&mut self) {
+        // a failed save is not retried until the settings change again
+        self.saved_config = self.config.clone();
+
+        self.config_error = self
+            .config_path
+            .as_ref()
+            .and_then(|path| self.config.save(path).err())
+            .map(|e| format!("Failed to save config: {}", e));
+    }
+
+    fn apply_source(&mut self) {
+        let addresses = self
+            .listen_address
+            .trim()
+            .parse::<SocketAddr>()
+            .map_err(|e| format!("Invalid listen address: {}", e))
+            .and_then(|listen_address| {
+                self.connect_address
+                    .trim()
+                    .parse::<SocketAddr>()
+                    .map(|connect_address| (listen_address, connect_address))
+                    .map_err(|e| format!("Invalid connect address: {}", e))
+            });
+
+        match addresses {
+            Ok((listen_address, connect_address)) => {
+                self.source_error = None;
+
+                self.ingest.listen_address = listen_address;
+                self.ingest.connect_address = connect_address;
+
+                self.settings_sender
+                    .send(SettingsPacket::Source(self.ingest.source()))
+                    .unwrap();
+
+                // the command line overrides stay out of the config unless they were edited
+                let (ingest, applied) = (self.ingest, self.applied_ingest);
+                let config = &mut self.config.ingest;
+
+                if ingest.transport != applied.transport {
+                    config.transport = ingest.transport;
+                }
+                if ingest.listen_address != applied.listen_address {
+                    config.listen_address = ingest.listen_address;
+                }
+                if ingest.connect_address != applied.connect_address {
+                    config.connect_address = ingest.connect_address;
+                }
+                if ingest.stream_framing != applied.stream_framing {
+                    config.stream_framing = ingest.stream_framing;
+                }
+
+                self.applied_ingest = ingest;
+            }
+            Err(e) => {
+                self.source_error = Some(e);
+            }
         }
     }
 
     fn charts(&mut self, ctx: &egui::Context) {
         let available_size = ctx.available_rect().size();
 
+        let DisplayConfig {
+            unit,
+            precision,
+            channel_layout,
+            ..
+        } = self.config.display;
+        let chart_size = self.config.measurement.chart_size;
+        let sample_rate = self
+            .playback_bar
+            .as_ref()
+            .map_or(self.config.measurement.sample_rate as f32, |playback_bar| {
+                playback_bar.playback().sample_rate()
+            });
+
         egui::SidePanel::right("side_panel")
             .resizable(false)
             .exact_width(available_size.x / 5.0)
@@ -200,104 +350,410 @@ impl Application {
                 ui.separator();
 
                 self.peak_sqrt_chart
-                    .ui(ui, self.chart_size, self.unit, self.precision);
+                    .ui(ui, channel_layout, chart_size, unit, precision);
 
                 self.rms_widget
-                    .ui(ui, self.chart_size, self.unit, self.precision);
+                    .ui(ui, channel_layout, chart_size, unit, precision);
 
                 self.frequency_widget
-                    .ui(ui, self.chart_size, self.precision);
+                    .ui(ui, channel_layout, chart_size, precision);
+
+                if let Some(phasor_diagram) = &mut self.phasor_diagram {
+                    phasor_diagram.ui(ui, unit, precision);
+                }
             });
 
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
                 self.time_chart.ui(
                     ui,
+                    channel_layout,
                     self.chart_x_bound,
-                    self.sample_rate as f32,
-                    self.unit,
-                    self.precision,
+                    sample_rate,
+                    unit,
+                    precision,
                 );
 
                 ui.separator();
 
-                self.harmonics
-                    .ui(ui, self.sample_rate as f32, self.precision);
+                self.harmonics.ui(
+                    ui,
+                    channel_layout,
+                    sample_rate,
+                    self.config.display.spectrum_scale,
+                    precision,
+                );
 
                 ui.separator();
 
                 self.rms_trend
-                    .ui(ui, self.chart_size, self.unit, self.precision);
+                    .ui(ui, channel_layout, chart_size, unit, precision);
             });
 
             ui.ctx().request_repaint();
         });
     }
 
+    fn harmonic_table(&mut self, ctx: &egui::Context) {
+        let DisplayConfig {
+            unit, precision, ..
+        } = self.config.display;
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.harmonic_table.ui(ui, unit, precision);
+
+            ui.ctx().request_repaint();
+        });
+    }
+
     fn settings(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.spacing_mut().item_spacing.y = 10.0;
 
             ui.label(RichText::new("Signal Settings").size(20.0).strong());
 
+            // taken from the capture while playing back
+            let playing_back = self.playback_bar.is_some();
+
             ui.horizontal(|ui| {
                 ui.label("Signal Sample Rate:");
                 if ui
-                    .add(egui::Slider::new(&mut self.sample_rate, 1..=10_000).text("Hz"))
+                    .add_enabled(
+                        !playing_back,
+                        egui::Slider::new(
+                            &mut self.config.measurement.sample_rate,
+                            SAMPLE_RATE_RANGE,
+                        )
+                        .text("Hz"),
+                    )
                     .changed()
                 {
                     self.settings_sender
-                        .send(SettingsPacket::SampleRate(self.sample_rate as f32))
+                        .send(SettingsPacket::SampleRate(
+                            self.config.measurement.sample_rate as f32,
+                        ))
                         .unwrap();
                 }
             });
 
-            ui.horizontal(|ui| {
-                ui.label("Calibration Factor:");
-                if ui
-                    .add(egui::Slider::new(&mut self.calibration_factor, 0.0..=1.0))
-                    .changed()
-                {
-                    self.settings_sender
-                        .send(SettingsPacket::CalibrationFactor(self.calibration_factor))
-                        .unwrap();
-                }
-            });
+            for (channel, calibration_factor) in self
+                .config
+                .measurement
+                .calibration_factors
+                .iter_mut()
+                .enumerate()
+            {
+                ui.horizontal(|ui| {
+                    ui.label("Calibration Factor");
+                    ui.label(RichText::new(channel_name(channel)).color(channel_color(channel)));
+                    if ui
+                        .add_enabled(
+                            !playing_back,
+                            egui::Slider::new(calibration_factor, CALIBRATION_FACTOR_RANGE),
+                        )
+                        .changed()
+                    {
+                        self.settings_sender
+                            .send(SettingsPacket::CalibrationFactor(
+                                channel,
+                                *calibration_factor,
+                            ))
+                            .unwrap();
+                    }
+                });
+            }
 
             egui::ComboBox::from_label("Volatage Unit")
-                .selected_text(format!("{}", self.unit))
+                .selected_text(format!("{}", self.config.display.unit))
                 .show_ui(ui, |ui| {
-                    ui.selectable_value(&mut self.unit, VoltageUnit::Volt, "Volt");
-                    ui.selectable_value(&mut self.unit, VoltageUnit::KiloVolt, "Kilovolt");
+                    ui.selectable_value(&mut self.config.display.unit, VoltageUnit::Volt, "Volt");
+                    ui.selectable_value(
+                        &mut self.config.display.unit,
+                        VoltageUnit::KiloVolt,
+                        "Kilovolt",
+                    );
                 });
 
             ui.horizontal(|ui| {
                 ui.label("Chart Formatter Precision:");
-                ui.add(egui::Slider::new(&mut self.precision, 0..=10))
+                ui.add(egui::Slider::new(
+                    &mut self.config.display.precision,
+                    PRECISION_RANGE,
+                ))
             });
 
             ui.separator();
 
+            ui.label(RichText::new("Network Settings").size(20.0).strong());
+
+            egui::ComboBox::from_label("Transport")
+                .selected_text(format!("{}", self.ingest.transport))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.ingest.transport, Transport::Udp, "UDP");
+                    ui.selectable_value(
+                        &mut self.ingest.transport,
+                        Transport::TcpServer,
+                        "TCP Server",
+                    );
+                    ui.selectable_value(
+                        &mut self.ingest.transport,
+                        Transport::TcpClient,
+                        "TCP Client",
+                    );
+                })
+                .response
+                .on_hover_text(
+                    "As TCP server the sender connects to the listen address, as TCP client the \
+                     meter connects to the sender",
+                );
+
+            let mut apply = false;
+
+            ui.horizontal(|ui| {
+                let response = if self.ingest.transport == Transport::TcpClient {
+                    ui.label("Connect Address:");
+                    ui.text_edit_singleline(&mut self.connect_address)
+                } else {
+                    ui.label("Listen Address:");
+                    ui.text_edit_singleline(&mut self.listen_address)
+                };
+
+                apply = ui.button("Apply").clicked()
+                    || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+
+                ui.label(format!("{}", self.link_status.status.read().unwrap()));
+            });
+
+            if self.ingest.transport != Transport::Udp {
+                let fixed_width = match self.ingest.stream_framing {
+                    StreamFraming::FixedWidth(width) => width,
+                    StreamFraming::LengthPrefixed => FIXED_WIDTH_DEFAULT,
+                };
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_label("Stream Framing")
+                        .selected_text(match self.ingest.stream_framing {
+                            StreamFraming::LengthPrefixed => "Length Prefixed",
+                            StreamFraming::FixedWidth(_) => "Fixed Width",
+                        })
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.ingest.stream_framing,
+                                StreamFraming::LengthPrefixed,
+                                "Length Prefixed",
+                            );
+                            ui.selectable_value(
+                                &mut self.ingest.stream_framing,
+                                StreamFraming::FixedWidth(fixed_width),
+                                "Fixed Width",
+                            );
+                        })
+                        .response
+                        .on_hover_text(
+                            "Length prefixed packets start with their length as little endian \
+                             u32, fixed width packets all have the same size",
+                        );
+
+                    if let StreamFraming::FixedWidth(width) = &mut self.ingest.stream_framing {
+                        ui.add(
+                            egui::DragValue::new(width)
+                                .range(FIXED_WIDTH_RANGE)
+                                .suffix(" bytes"),
+                        );
+                    }
+                });
+            }
+
+            // transport and framing changes only take effect together with the addresses
+            if apply {
+                self.apply_source();
+            }
+
+            if let Some(error) = &self.source_error {
+                ui.colored_label(Color32::RED, error);
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Channels:");
+                ui.add(egui::DragValue::new(&mut self.config.channels).range(1..=MAX_CHANNELS));
+
+                if self.config.channels != self.channels {
+                    ui.label("(applied after a restart)");
+                }
+            })
+            .response
+            .on_hover_text("Number of interleaved channels in every packet");
+
+            if let Some(error) = &self.config_error {
+                ui.colored_label(Color32::RED, error);
+            }
+
+            let previous_packet_format = self.ingest.packet_format;
+
+            egui::ComboBox::from_label("Packet Format")
+                .selected_text(format!("{}", self.ingest.packet_format))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.ingest.packet_format,
+                        PacketFormat::Framed,
+                        "Framed",
+                    );
+                    ui.selectable_value(&mut self.ingest.packet_format, PacketFormat::Raw, "Raw");
+                })
+                .response
+                .on_hover_text(
+                    "Framed packets carry a header describing their samples, raw datagrams are \
+                     decoded using the sample layout below",
+                );
+
+            if self.ingest.packet_format != previous_packet_format {
+                self.settings_sender
+                    .send(SettingsPacket::PacketFormat(self.ingest.packet_format))
+                    .unwrap();
+
+                self.config.ingest.packet_format = self.ingest.packet_format;
+            }
+
+            if self.ingest.packet_format == PacketFormat::Raw {
+                let previous_sample_layout = self.ingest.sample_layout;
+
+                egui::ComboBox::from_label("Sample Encoding")
+                    .selected_text(format!("{}", self.ingest.sample_layout.codec.encoding))
+                    .show_ui(ui, |ui| {
+                        for encoding in SampleEncoding::ALL {
+                            ui.selectable_value(
+                                &mut self.ingest.sample_layout.codec.encoding,
+                                encoding,
+                                format!("{}", encoding),
+                            );
+                        }
+                    });
+
+                egui::ComboBox::from_label("Byte Order")
+                    .selected_text(format!("{}", self.ingest.sample_layout.codec.endianness))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut self.ingest.sample_layout.codec.endianness,
+                            Endianness::Little,
+                            "Little Endian",
+                        );
+                        ui.selectable_value(
+                            &mut self.ingest.sample_layout.codec.endianness,
+                            Endianness::Big,
+                            "Big Endian",
+                        );
+                    });
+
+                ui.horizontal(|ui| {
+                    ui.label("Offset:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.ingest.sample_layout.offset)
+                            .range(0..=1024)
+                            .suffix(" bytes"),
+                    );
+
+                    ui.label("Stride:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.ingest.sample_layout.stride)
+                            .range(self.ingest.sample_layout.codec.size()..=1024)
+                            .suffix(" bytes"),
+                    );
+                });
+
+                self.ingest.sample_layout.stride = self
+                    .ingest
+                    .sample_layout
+                    .stride
+                    .max(self.ingest.sample_layout.codec.size());
+
+                if self.ingest.sample_layout != previous_sample_layout {
+                    self.settings_sender
+                        .send(SettingsPacket::SampleLayout(self.ingest.sample_layout))
+                        .unwrap();
+
+                    self.config.ingest.sample_layout = self.ingest.sample_layout;
+                }
+            }
+
+            let previous_gap_handling = self.ingest.gap_handling;
+
+            egui::ComboBox::from_label("Gap Handling")
+                .selected_text(format!("{}", self.ingest.gap_handling))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(
+                        &mut self.ingest.gap_handling,
+                        GapHandling::ZeroFill,
+                        "Zero Fill",
+                    );
+                    ui.selectable_value(
+                        &mut self.ingest.gap_handling,
+                        GapHandling::HoldLast,
+                        "Hold Last",
+                    );
+                    ui.selectable_value(
+                        &mut self.ingest.gap_handling,
+                        GapHandling::MarkInvalid,
+                        "Mark Invalid",
+                    );
+                })
+                .response
+                .on_hover_text("Samples inserted in place of lost packets");
+
+            if self.ingest.gap_handling != previous_gap_handling {
+                self.settings_sender
+                    .send(SettingsPacket::GapHandling(self.ingest.gap_handling))
+                    .unwrap();
+
+                self.config.ingest.gap_handling = self.ingest.gap_handling;
+            }
+
+            ui.separator();
+
             ui.label(RichText::new("General Settings").size(20.0).strong());
 
             ui.horizontal(|ui| {
                 ui.label("Zoom Factor:");
                 ui.add(
-                    egui::DragValue::new(&mut self.zoom_factor)
-                        .range(0.1..=3.0)
+                    egui::DragValue::new(&mut self.config.display.zoom_factor)
+                        .range(ZOOM_FACTOR_RANGE)
                         .speed(0.1)
                         .update_while_editing(false),
                 );
             });
 
+            if self.channels > 1 {
+                egui::ComboBox::from_label("Channel Layout")
+                    .selected_text(format!("{}", self.config.display.channel_layout))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut self.config.display.channel_layout,
+                            ChannelLayout::Overlaid,
+                            "Overlaid",
+                        );
+                        ui.selectable_value(
+                            &mut self.config.display.channel_layout,
+                            ChannelLayout::SideBySide,
+                            "Side by Side",
+                        );
+                    });
+            }
+
             ui.horizontal(|ui| {
                 ui.label("Chart Size:");
                 if ui
-                    .add(egui::Slider::new(&mut self.chart_size, 10..=300).text("seconds"))
+                    .add(
+                        egui::Slider::new(
+                            &mut self.config.measurement.chart_size,
+                            CHART_SIZE_RANGE,
+                        )
+                        .text("seconds"),
+                    )
                     .changed()
                 {
                     self.settings_sender
-                        .send(SettingsPacket::ChartSize(self.chart_size))
+                        .send(SettingsPacket::ChartSize(
+                            self.config.measurement.chart_size,
+                        ))
                         .unwrap();
                 }
             });
@@ -309,11 +765,16 @@ impl Application {
             ui.horizontal(|ui| {
                 ui.label("Periods:");
                 if ui
-                    .add(egui::Slider::new(&mut self.periods, 1..=10))
+                    .add(egui::Slider::new(
+                        &mut self.config.measurement.time_chart_periods,
+                        PERIODS_RANGE,
+                    ))
                     .changed()
                 {
                     self.settings_sender
-                        .send(SettingsPacket::TimeChartPeriods(self.periods))
+                        .send(SettingsPacket::TimeChartPeriods(
+                            self.config.measurement.time_chart_periods,
+                        ))
                         .unwrap();
                 }
             });
@@ -329,27 +790,104 @@ impl Application {
             ui.horizontal(|ui| {
                 ui.label("FFT Size:");
                 if ui
-                    .add(egui::Slider::new(&mut self.fft_size, 128..=8192).text("samples"))
+                    .add(
+                        egui::Slider::new(&mut self.config.measurement.fft_size, FFT_SIZE_RANGE)
+                            .text("samples"),
+                    )
                     .changed()
                 {
                     self.settings_sender
-                        .send(SettingsPacket::FftSize(self.fft_size))
+                        .send(SettingsPacket::FftSize(self.config.measurement.fft_size))
                         .unwrap();
                 }
             });
 
+            egui::ComboBox::from_label("FFT Window")
+                .selected_text(format!("{}", self.config.measurement.fft_window))
+                .show_ui(ui, |ui| {
+                    for window in FftWindow::ALL {
+                        if ui
+                            .selectable_value(
+                                &mut self.config.measurement.fft_window,
+                                window,
+                                window.to_string(),
+                            )
+                            .changed()
+                        {
+                            self.settings_sender
+                                .send(SettingsPacket::FftWindow(window))
+                                .unwrap();
+                        }
+                    }
+                });
+
+            egui::ComboBox::from_label("Frequency Estimator")
+                .selected_text(format!("{}", self.config.measurement.frequency_estimator))
+                .show_ui(ui, |ui| {
+                    for estimator in FrequencyEstimator::ALL {
+                        if ui
+                            .selectable_value(
+                                &mut self.config.measurement.frequency_estimator,
+                                estimator,
+                                estimator.to_string(),
+                            )
+                            .changed()
+                        {
+                            self.settings_sender
+                                .send(SettingsPacket::FrequencyEstimator(estimator))
+                                .unwrap();
+                        }
+                    }
+                });
+
             ui.horizontal(|ui| {
                 ui.label("Refresh Period:");
                 if ui
                     .add(
-                        egui::Slider::new(&mut self.harmonics_refresh_period, 0.01..=10.0)
-                            .text("seconds"),
+                        egui::Slider::new(
+                            &mut self.config.measurement.harmonics_refresh_period,
+                            REFRESH_PERIOD_RANGE,
+                        )
+                        .text("seconds"),
                     )
                     .changed()
                 {
                     self.settings_sender
                         .send(SettingsPacket::HarmonicsRefreshPeriod(
-                            self.harmonics_refresh_period,
+                            self.config.measurement.harmonics_refresh_period,
+                        ))
+                        .unwrap();
+                }
+            });
+
+            egui::ComboBox::from_label("Spectrum Scale")
+                .selected_text(format!("{}", self.config.display.spectrum_scale))
+                .show_ui(ui, |ui| {
+                    for scale in SpectrumScale::ALL {
+                        ui.selectable_value(
+                            &mut self.config.display.spectrum_scale,
+                            scale,
+                            scale.to_string(),
+                        );
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                ui.label("Rated Voltage:");
+                if ui
+                    .add(
+                        egui::Slider::new(
+                            &mut self.config.measurement.rated_voltage,
+                            RATED_VOLTAGE_RANGE,
+                        )
+                        .logarithmic(true)
+                        .text("V"),
+                    )
+                    .changed()
+                {
+                    self.settings_sender
+                        .send(SettingsPacket::RatedVoltage(
+                            self.config.measurement.rated_voltage,
                         ))
                         .unwrap();
                 }
@@ -366,11 +904,51 @@ impl Application {
             ui.horizontal(|ui| {
                 ui.label("Window Size:");
                 if ui
-                    .add(egui::Slider::new(&mut self.window, 0.01..=12.0).text("seconds"))
+                    .add(
+                        egui::Slider::new(
+                            &mut self.config.measurement.rms_window,
+                            RMS_WINDOW_RANGE,
+                        )
+                        .text("seconds"),
+                    )
                     .changed()
                 {
                     self.settings_sender
-                        .send(SettingsPacket::Window(self.window))
+                        .send(SettingsPacket::Window(self.config.measurement.rms_window))
+                        .unwrap();
+                }
+            });
+
+            egui::ComboBox::from_label("Measurement Mode")
+                .selected_text(format!("{}", self.config.measurement.measurement_mode))
+                .show_ui(ui, |ui| {
+                    for mode in MeasurementMode::ALL {
+                        if ui
+                            .selectable_value(
+                                &mut self.config.measurement.measurement_mode,
+                                mode,
+                                mode.to_string(),
+                            )
+                            .changed()
+                        {
+                            self.settings_sender
+                                .send(SettingsPacket::MeasurementMode(mode))
+                                .unwrap();
+                        }
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                ui.label("Cycles:");
+                if ui
+                    .add(egui::Slider::new(
+                        &mut self.config.measurement.cycles,
+                        CYCLES_RANGE,
+                    ))
+                    .changed()
+                {
+                    self.settings_sender
+                        .send(SettingsPacket::Cycles(self.config.measurement.cycles))
                         .unwrap();
                 }
             });
@@ -379,13 +957,18 @@ impl Application {
                 ui.label("Refresh Period:");
                 if ui
                     .add(
-                        egui::Slider::new(&mut self.rms_refresh_period, 0.01..=10.0)
-                            .text("seconds"),
+                        egui::Slider::new(
+                            &mut self.config.measurement.rms_refresh_period,
+                            REFRESH_PERIOD_RANGE,
+                        )
+                        .text("seconds"),
                     )
                     .changed()
                 {
                     self.settings_sender
-                        .send(SettingsPacket::RmsRefreshPeriod(self.rms_refresh_period))
+                        .send(SettingsPacket::RmsRefreshPeriod(
+                            self.config.measurement.rms_refresh_period,
+                        ))
                         .unwrap();
                 }
             });
@@ -400,28 +983,98 @@ impl eframe::App for Application {
             ..Style::default()
         };
         ctx.set_style(style);
-        ctx.set_zoom_factor(self.zoom_factor);
+        ctx.set_zoom_factor(self.config.display.zoom_factor);
 
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.add_space(3.0);
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut self.panel, Panel::Charts, "Charts");
+                ui.selectable_value(&mut self.panel, Panel::Harmonics, "Harmonics");
                 ui.selectable_value(&mut self.panel, Panel::Settings, "Settings");
 
+                ui.separator();
+
+                ui.label("Profile:");
+                if self.profile_selector.ui(ui, &mut self.config) {
+                    self.apply_measurement();
+                }
+
                 ui.add_space(ui.available_width());
 
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                     if ui.button("Reset Time Chart Bounds").clicked() {
-                        self.chart_x_bound = self.time_chart.data.read().unwrap().len();
+                        self.chart_x_bound = self.time_chart.data[0].read().unwrap().len();
                     }
+
+                    ui.separator();
+
+                    self.link_status.ui(ui);
+
+                    ui.separator();
+
+                    self.recorder.ui(ui);
+
+                    ui.separator();
+
+                    self.exporter
+                        .ui(ui, &mut self.config.export, self.config.display.unit);
                 });
             });
             ui.add_space(3.0);
         });
 
+        self.warnings
+            .append(&mut self.runtime_warnings.write().unwrap());
+        let excess = self.warnings.len().saturating_sub(MAX_WARNINGS);
+        self.warnings.drain(..excess);
+
+        if !self.warnings.is_empty() {
+            egui::TopBottomPanel::top("warnings").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.vertical(|ui| {
+                        for warning in &self.warnings {
+                            ui.colored_label(Color32::ORANGE, warning);
+                        }
+                    });
+
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        if ui.button("Dismiss").clicked() {
+                            self.warnings.clear();
+                        }
+                    });
+                });
+            });
+        }
+
+        if let Some(playback_bar) = &mut self.playback_bar {
+            egui::TopBottomPanel::bottom("playback").show(ctx, |ui| {
+                ui.add_space(3.0);
+                playback_bar.ui(ui, &mut self.config.export);
+                ui.add_space(3.0);
+            });
+        }
+
         match self.panel {
             Panel::Charts => self.charts(ctx),
+            Panel::Harmonics => self.harmonic_table(ctx),
             Panel::Settings => self.settings(ctx),
         };
+
+        // remote changes are kept like the ones made in the settings panel
+        for packet in self.remote_changes.try_iter() {
+            self.config.measurement.apply(&packet);
+        }
+
+        // changes of the settings are kept in the active profile
+        if let Some(index) = self.config.active_profile() {
+            if self.config.profiles[index].measurement != self.config.measurement {
+                self.config.profiles[index].measurement = self.config.measurement.clone();
+            }
+        }
+
+        // saving waits until a slider is released, instead of writing the file on every frame
+        if self.config != self.saved_config && !ctx.input(|i| i.pointer.any_down()) {
+            self.save_config();
+        }
     }
 }
@@ -0,0 +1,313 @@
+use crate::{
+    channel::channel_name,
+    config::MeasurementConfig,
+    settings::{CalibrationFactor, SampleRate},
+};
+use chrono::{DateTime, Local, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+pub const CAPTURE_MAGIC: [u8; 4] = *b"PVMC";
+pub const CAPTURE_VERSION: u16 = 1;
+pub const CAPTURE_EXTENSION: &str = "pvc";
+
+const CHUNK_MAGIC: [u8; 4] = *b"DATA";
+// frames of all channels per chunk, the last chunk of a capture may be shorter
+const CHUNK_FRAMES: usize = 1024;
+const CHUNK_HEADER_SIZE: u64 = 16;
+const FILE_HEADER_SIZE: usize = 12;
+
+// the calibrated samples are in volts, the raw samples keep the scale of the sender
+const CALIBRATED_UNIT: &str = "V";
+
+/// Description of a channel of a capture.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureChannel {
+    pub name: String,
+    // unit of the calibrated samples
+    pub unit: String,
+    // calibrated sample = raw sample * calibration factor
+    pub calibration_factor: CalibrationFactor,
+}
+
+impl CaptureChannel {
+    pub fn new(channel: usize, calibration_factor: CalibrationFactor) -> Self {
+        Self {
+            name: channel_name(channel),
+            unit: CALIBRATED_UNIT.to_string(),
+            calibration_factor,
+        }
+    }
+}
+
+/// JSON header at the start of a capture, describing all of its samples.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureHeader {
+    pub sample_rate: SampleRate,
+    // RFC 3339, time of the first sample
+    pub start_time: String,
+    pub channels: Vec<CaptureChannel>,
+}
+
+impl CaptureHeader {
+    pub fn new(
+        measurement: &MeasurementConfig,
+        channels: usize,
+        start_time: DateTime<Local>,
+    ) -> Self {
+        Self {
+            sample_rate: measurement.sample_rate as SampleRate,
+            start_time: start_time.to_rfc3339_opts(SecondsFormat::Micros, false),
+            channels: (0..channels)
+                .map(|channel| {
+                    CaptureChannel::new(channel, measurement.calibration_factor(channel))
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the samples of the measurement settings are described by the header.
+    pub fn matches(&self, measurement: &MeasurementConfig) -> bool {
+        self.sample_rate == measurement.sample_rate as SampleRate
+            && self
+                .channels
+                .iter()
+                .enumerate()
+                .all(|(channel, description)| {
+                    description.calibration_factor == measurement.calibration_factor(channel)
+                })
+    }
+}
+
+/// Writes the samples of all channels into a capture file, chunk by chunk. A chunk is written
+/// at once, so the file is readable up to its last chunk at any time.
+pub struct CaptureWriter {
+    file: File,
+    header: CaptureHeader,
+    // raw and calibrated sample of every channel, per frame
+    chunk: Vec<f32>,
+    chunk_start: u64,
+    size: u64,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &Path, header: CaptureHeader) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+
+        let json = serde_json::to_vec(&header).expect("headers are always serializable");
+
+        let mut bytes = Vec::with_capacity(FILE_HEADER_SIZE + json.len());
+        bytes.extend_from_slice(&CAPTURE_MAGIC);
+        bytes.extend_from_slice(&CAPTURE_VERSION.to_le_bytes());
+        // reserved
+        bytes.extend_from_slice(&[0; 2]);
+        bytes.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&json);
+
+        file.write_all(&bytes)?;
+
+        Ok(Self {
+            file,
+            chunk: Vec::with_capacity(CHUNK_FRAMES * header.channels.len() * 2),
+            header,
+            chunk_start: 0,
+            size: bytes.len() as u64,
+        })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Bytes written to the file so far.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Adds the samples of one frame, returns whether they completed a chunk.
+    pub fn push(&mut self, raw: &[f32], calibrated: &[f32]) -> io::Result<bool> {
+        for (raw, calibrated) in raw.iter().zip(calibrated) {
+            self.chunk.push(*raw);
+            self.chunk.push(*calibrated);
+        }
+
+        if self.chunk.len() < CHUNK_FRAMES * self.header.channels.len() * 2 {
+            return Ok(false);
+        }
+
+        self.write_chunk()?;
+
+        Ok(true)
+    }
+
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+
+        let frames = self.chunk.len() / (self.header.channels.len() * 2);
+
+        let mut bytes = Vec::with_capacity(CHUNK_HEADER_SIZE as usize + self.chunk.len() * 4);
+        bytes.extend_from_slice(&CHUNK_MAGIC);
+        bytes.extend_from_slice(&(frames as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.chunk_start.to_le_bytes());
+        bytes.extend(self.chunk.iter().flat_map(|sample| sample.to_le_bytes()));
+
+        self.file.write_all(&bytes)?;
+
+        self.chunk.clear();
+        self.chunk_start += frames as u64;
+        self.size += bytes.len() as u64;
+
+        Ok(())
+    }
+
+    /// Writes the samples of the last, incomplete chunk.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_chunk()?;
+        self.file.sync_all()
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+struct Chunk {
+    // of the first sample
+    offset: u64,
+    start: u64,
+    frames: u64,
+}
+
+/// Reads the raw samples of a capture file. The chunks are indexed when the file is opened, a
+/// chunk cut off at the end of the file is left out.
+pub struct CaptureReader {
+    file: File,
+    header: CaptureHeader,
+    chunks: Vec<Chunk>,
+}
+
+impl CaptureReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let length = file.metadata()?.len();
+
+        let mut prefix = [0; FILE_HEADER_SIZE];
+        file.read_exact(&mut prefix)?;
+
+        if prefix[0..4] != CAPTURE_MAGIC {
+            return Err(invalid_data("not a capture file"));
+        }
+
+        let version = u16::from_le_bytes([prefix[4], prefix[5]]);
+
+        if version != CAPTURE_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported capture version {}",
+                version
+            )));
+        }
+
+        let header_length = u32::from_le_bytes([prefix[8], prefix[9], prefix[10], prefix[11]]);
+
+        let mut json = vec![0; header_length as usize];
+        file.read_exact(&mut json)?;
+
+        let header: CaptureHeader =
+            serde_json::from_slice(&json).map_err(|e| invalid_data(e.to_string()))?;
+
+        if header.channels.is_empty() {
+            return Err(invalid_data("capture without channels"));
+        }
+
+        let frame_size = header.channels.len() as u64 * 2 * 4;
+
+        let mut chunks = Vec::new();
+        let mut offset = (FILE_HEADER_SIZE + json.len()) as u64;
+        let mut start = 0;
+
+        while offset + CHUNK_HEADER_SIZE <= length {
+            let mut chunk_header = [0; CHUNK_HEADER_SIZE as usize];
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut chunk_header)?;
+
+            if chunk_header[0..4] != CHUNK_MAGIC {
+                return Err(invalid_data(format!("invalid chunk at byte {}", offset)));
+            }
+
+            let frames = u32::from_le_bytes([
+                chunk_header[4],
+                chunk_header[5],
+                chunk_header[6],
+                chunk_header[7],
+            ]) as u64;
+
+            offset += CHUNK_HEADER_SIZE;
+
+            if offset + frames * frame_size > length {
+                break;
+            }
+
+            chunks.push(Chunk {
+                offset,
+                start,
+                frames,
+            });
+
+            offset += frames * frame_size;
+            start += frames;
+        }
+
+        Ok(Self {
+            file,
+            header,
+            chunks,
+        })
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Number of frames of all chunks.
+    pub fn frames(&self) -> u64 {
+        self.chunks
+            .last()
+            .map_or(0, |chunk| chunk.start + chunk.frames)
+    }
+
+    /// Raw samples of the frames from `frame` up to `count` frames or the end of its chunk,
+    /// interleaved like the samples of a packet.
+    pub fn read(&mut self, frame: u64, count: u64) -> io::Result<Vec<f32>> {
+        let index = self
+            .chunks
+            .partition_point(|chunk| chunk.start + chunk.frames <= frame);
+
+        let Some(chunk) = self.chunks.get(index) else {
+            return Ok(Vec::new());
+        };
+
+        let channels = self.header.channels.len();
+        let frame_size = channels as u64 * 2 * 4;
+
+        let skipped = frame - chunk.start;
+        let frames = count.min(chunk.frames - skipped);
+
+        let mut bytes = vec![0; (frames * frame_size) as usize];
+        self.file
+            .seek(SeekFrom::Start(chunk.offset + skipped * frame_size))?;
+        self.file.read_exact(&mut bytes)?;
+
+        // every frame holds the raw and the calibrated sample of every channel, the calibration
+        // is applied by the pipeline again
+        Ok(bytes
+            .chunks_exact(8)
+            .map(|pair| f32::from_le_bytes([pair[0], pair[1], pair[2], pair[3]]))
+            .collect())
+    }
+}
@@ -0,0 +1,64 @@
+use crate::settings::Channel;
+use egui::{Color32, Ui};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    ops::Range,
+};
+
+pub const MAX_CHANNELS: usize = 8;
+
+const CHANNEL_COLORS: [Color32; MAX_CHANNELS] = [
+    Color32::LIGHT_BLUE,
+    Color32::YELLOW,
+    Color32::LIGHT_RED,
+    Color32::LIGHT_GREEN,
+    Color32::ORANGE,
+    Color32::from_rgb(200, 140, 255),
+    Color32::from_rgb(120, 230, 230),
+    Color32::GRAY,
+];
+
+pub fn channel_color(channel: Channel) -> Color32 {
+    CHANNEL_COLORS[channel % MAX_CHANNELS]
+}
+
+pub fn channel_name(channel: Channel) -> String {
+    format!("CH{}", channel + 1)
+}
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelLayout {
+    Overlaid,
+    SideBySide,
+}
+
+impl Display for ChannelLayout {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelLayout::Overlaid => write!(f, "Overlaid"),
+            ChannelLayout::SideBySide => write!(f, "Side by Side"),
+        }
+    }
+}
+
+/// Shows all channels in a single view, or every channel in a column of its own.
+/// `add_contents` is called once per view with the channels it has to show.
+pub fn channel_views(
+    ui: &mut Ui,
+    layout: ChannelLayout,
+    channels: usize,
+    mut add_contents: impl FnMut(&mut Ui, Range<Channel>),
+) {
+    match layout {
+        ChannelLayout::SideBySide if channels > 1 => {
+            ui.columns(channels, |columns| {
+                for (channel, ui) in columns.iter_mut().enumerate() {
+                    add_contents(ui, channel..channel + 1);
+                }
+            });
+        }
+        _ => add_contents(ui, 0..channels),
+    }
+}
@@ -0,0 +1,1153 @@
+use crate::{
+    application::{Precision, VoltageUnit},
+    channel::{ChannelLayout, MAX_CHANNELS},
+    cycle_measurement::MeasurementMode,
+    export::ComtradeFormat,
+    frequency_widget::FrequencyEstimator,
+    harmonics::{FftWindow, SpectrumScale},
+    headless::{Output, INTERVAL_MIN},
+    ingest::{
+        Endianness, GapHandling, PacketFormat, SampleCodec, SampleEncoding, SampleLayout, Source,
+        StreamFraming, Transport,
+    },
+    settings::{
+        CalibrationFactor, Channel, ChartSize, Cycles, FftSize, RatedVoltage, RefreshPeriod,
+        RmsWindow, SettingsPacket, TimeChartPeriods,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{Display, Formatter},
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+const CONFIG_DIRECTORY: &str = "peakvoltmeter";
+const CONFIG_FILE: &str = "config.toml";
+
+const TRANSPORT_DEFAULT: Transport = Transport::Udp;
+const LISTEN_ADDRESS_DEFAULT: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080);
+const CONNECT_ADDRESS_DEFAULT: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080);
+const STREAM_FRAMING_DEFAULT: StreamFraming = StreamFraming::LengthPrefixed;
+const PACKET_FORMAT_DEFAULT: PacketFormat = PacketFormat::Framed;
+const SAMPLE_LAYOUT_DEFAULT: SampleLayout = SampleLayout {
+    codec: SampleCodec {
+        encoding: SampleEncoding::I32,
+        endianness: Endianness::Little,
+    },
+    offset: 0,
+    stride: 4,
+};
+const GAP_HANDLING_DEFAULT: GapHandling = GapHandling::HoldLast;
+const CHANNELS_DEFAULT: usize = 1;
+
+const SAMPLE_RATE_DEFAULT: usize = 3125;
+const CALIBRATION_FACTOR_DEFAULT: CalibrationFactor = 0.00319929;
+const PERIODS_DEFAULT: TimeChartPeriods = 3;
+const FFT_SIZE_DEFAULT: FftSize = 2048;
+const FFT_WINDOW_DEFAULT: FftWindow = FftWindow::Hann;
+const HARMONICS_REFRESH_PERIOD: RefreshPeriod = 0.2;
+const RATED_VOLTAGE_DEFAULT: RatedVoltage = 230.0;
+const FREQUENCY_ESTIMATOR_DEFAULT: FrequencyEstimator = FrequencyEstimator::Jacobsen;
+const WINDOW_DEFAULT: RmsWindow = 0.5;
+const MEASUREMENT_MODE_DEFAULT: MeasurementMode = MeasurementMode::Window;
+// 200 ms at 50 Hz, like the aggregation of IEC 61000-4-30
+const CYCLES_DEFAULT: Cycles = 10;
+const CHART_SIZE_DEFAULT: ChartSize = 180;
+const RMS_REFRESH_PERIOD_DEFAULT: RefreshPeriod = 0.5;
+
+const UNIT_DEFAULT: VoltageUnit = VoltageUnit::Volt;
+const PRECISION_DEFAULT: Precision = 2;
+const ZOOM_FACTOR_DEFAULT: f32 = 1.0;
+const CHANNEL_LAYOUT_DEFAULT: ChannelLayout = ChannelLayout::Overlaid;
+const SPECTRUM_SCALE_DEFAULT: SpectrumScale = SpectrumScale::Dbv;
+
+const HEADLESS_INTERVAL_DEFAULT: f32 = 1.0;
+
+const PROFILE_NAME_DEFAULT: &str = "Profile";
+
+// the usual port of instruments accepting SCPI over raw sockets
+const SCPI_LISTEN_ADDRESS_DEFAULT: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 5025);
+const HTTP_LISTEN_ADDRESS_DEFAULT: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8000);
+const WEBSOCKET_LISTEN_ADDRESS_DEFAULT: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8001);
+const METRICS_LISTEN_ADDRESS_DEFAULT: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 9101);
+// the registered Modbus TCP port, binding it may need privileges
+const MODBUS_LISTEN_ADDRESS_DEFAULT: SocketAddr =
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 502);
+
+const MQTT_BROKER_DEFAULT: &str = "localhost:1883";
+const MQTT_CLIENT_ID_DEFAULT: &str = "peakvoltmeter";
+// `{channel}` is replaced by the channel, counted from 0
+const MQTT_RMS_TOPIC_DEFAULT: &str = "peakvoltmeter/{channel}/rms";
+const MQTT_PEAK_SQRT_TOPIC_DEFAULT: &str = "peakvoltmeter/{channel}/peak-sqrt";
+const MQTT_FREQUENCY_TOPIC_DEFAULT: &str = "peakvoltmeter/{channel}/frequency";
+const MQTT_ALARM_TOPIC_DEFAULT: &str = "peakvoltmeter/{channel}/alarm";
+const MQTT_STATUS_TOPIC_DEFAULT: &str = "peakvoltmeter/status";
+const MQTT_QOS_DEFAULT: u8 = 1;
+const MQTT_BUFFER_SIZE_DEFAULT: usize = 1000;
+
+// directory of the captures, inside the user data directory
+const RECORDER_DIRECTORY: &str = "captures";
+const RECORDER_MAX_FILE_SIZE_DEFAULT: u64 = 100;
+const RECORDER_MAX_FILES_DEFAULT: usize = 0;
+
+// directory of the exported chart data, inside the user data directory
+const EXPORT_DIRECTORY: &str = "exports";
+const STATION_NAME_DEFAULT: &str = "peakvoltmeter";
+const LINE_FREQUENCY_DEFAULT: f64 = 50.0;
+const EVENT_WINDOW_DEFAULT: f64 = 1.0;
+
+// size of a framed packet with the simulator's default of 64 i32 samples
+pub const FIXED_WIDTH_DEFAULT: usize = 268;
+
+// valid values, also the ranges of the settings in the user interface
+pub const FIXED_WIDTH_RANGE: RangeInclusive<usize> = 1..=65_507;
+pub const SAMPLE_RATE_RANGE: RangeInclusive<usize> = 1..=10_000;
+pub const CALIBRATION_FACTOR_RANGE: RangeInclusive<CalibrationFactor> = 0.0..=1.0;
+pub const PRECISION_RANGE: RangeInclusive<Precision> = 0..=10;
+pub const ZOOM_FACTOR_RANGE: RangeInclusive<f32> = 0.1..=3.0;
+pub const CHART_SIZE_RANGE: RangeInclusive<ChartSize> = 10..=300;
+pub const PERIODS_RANGE: RangeInclusive<TimeChartPeriods> = 1..=10;
+pub const FFT_SIZE_RANGE: RangeInclusive<FftSize> = 128..=8192;
+pub const REFRESH_PERIOD_RANGE: RangeInclusive<RefreshPeriod> = 0.01..=10.0;
+pub const RMS_WINDOW_RANGE: RangeInclusive<RmsWindow> = 0.01..=12.0;
+pub const CYCLES_RANGE: RangeInclusive<Cycles> = 1..=100;
+pub const RATED_VOLTAGE_RANGE: RangeInclusive<RatedVoltage> = 1.0..=1_000_000.0;
+
+const MQTT_QOS_RANGE: RangeInclusive<u8> = 0..=2;
+const MQTT_BUFFER_SIZE_RANGE: RangeInclusive<usize> = 1..=100_000;
+const RECORDER_MAX_FILE_SIZE_RANGE: RangeInclusive<u64> = 1..=100_000;
+const LINE_FREQUENCY_RANGE: RangeInclusive<f64> = 1.0..=1000.0;
+pub const EVENT_WINDOW_RANGE: RangeInclusive<f64> = 0.01..=60.0;
+
+pub type ConfigResult<T> = Result<T, ConfigError>;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(e: std::io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(e: toml::ser::Error) -> Self {
+        ConfigError::Serialize(e)
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse(e) => write!(f, "{}", e),
+            ConfigError::Serialize(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Replaces a value outside of `range` with `default`, and records a warning about it.
+fn check_range<T: PartialOrd + Display + Copy>(
+    warnings: &mut Vec<String>,
+    name: &str,
+    value: &mut T,
+    range: RangeInclusive<T>,
+    default: T,
+) {
+    if !range.contains(value) {
+        warnings.push(format!(
+            "`{}` = {} is outside of {} to {}, using {} instead",
+            name,
+            value,
+            range.start(),
+            range.end(),
+            default
+        ));
+
+        *value = default;
+    }
+}
+
+fn check_in_range<T: PartialOrd + Display>(
+    name: &str,
+    value: T,
+    range: RangeInclusive<T>,
+) -> Result<(), String> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} {} is outside of {} to {}",
+            name,
+            value,
+            range.start(),
+            range.end()
+        ))
+    }
+}
+
+/// Where samples are received from.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IngestConfig {
+    pub transport: Transport,
+    // used by the udp and tcp server transports
+    pub listen_address: SocketAddr,
+    // used by the tcp client transport
+    pub connect_address: SocketAddr,
+    // used by both tcp transports
+    pub stream_framing: StreamFraming,
+
+    pub packet_format: PacketFormat,
+    // used by raw packets
+    pub sample_layout: SampleLayout,
+    pub gap_handling: GapHandling,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            transport: TRANSPORT_DEFAULT,
+            listen_address: LISTEN_ADDRESS_DEFAULT,
+            connect_address: CONNECT_ADDRESS_DEFAULT,
+            stream_framing: STREAM_FRAMING_DEFAULT,
+            packet_format: PACKET_FORMAT_DEFAULT,
+            sample_layout: SAMPLE_LAYOUT_DEFAULT,
+            gap_handling: GAP_HANDLING_DEFAULT,
+        }
+    }
+}
+
+impl IngestConfig {
+    pub fn source(&self) -> Source {
+        match self.transport {
+            Transport::Udp => Source::Udp(self.listen_address),
+            Transport::TcpServer => Source::TcpServer(self.listen_address, self.stream_framing),
+            Transport::TcpClient => Source::TcpClient(self.connect_address, self.stream_framing),
+        }
+    }
+
+    pub fn settings_packets(&self) -> Vec<SettingsPacket> {
+        vec![
+            SettingsPacket::Source(self.source()),
+            SettingsPacket::PacketFormat(self.packet_format),
+            SettingsPacket::SampleLayout(self.sample_layout),
+            SettingsPacket::GapHandling(self.gap_handling),
+        ]
+    }
+
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        if let StreamFraming::FixedWidth(width) = &mut self.stream_framing {
+            check_range(
+                warnings,
+                "stream_framing.fixed-width",
+                width,
+                FIXED_WIDTH_RANGE,
+                FIXED_WIDTH_DEFAULT,
+            );
+        }
+
+        let sample_size = self.sample_layout.codec.size();
+
+        if self.sample_layout.stride < sample_size {
+            warnings.push(format!(
+                "`sample_layout.stride` = {} is smaller than a sample, using {} instead",
+                self.sample_layout.stride, sample_size
+            ));
+
+            self.sample_layout.stride = sample_size;
+        }
+    }
+}
+
+/// Settings of the measurement pipeline.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeasurementConfig {
+    pub sample_rate: usize,
+    // one per channel, channels without an entry use the default
+    pub calibration_factors: Vec<CalibrationFactor>,
+    pub time_chart_periods: TimeChartPeriods,
+    pub fft_size: FftSize,
+    pub fft_window: FftWindow,
+    pub harmonics_refresh_period: RefreshPeriod,
+    // reference of the total demand distortion
+    pub rated_voltage: RatedVoltage,
+    pub frequency_estimator: FrequencyEstimator,
+    pub rms_window: RmsWindow,
+    pub measurement_mode: MeasurementMode,
+    pub cycles: Cycles,
+    pub chart_size: ChartSize,
+    pub rms_refresh_period: RefreshPeriod,
+}
+
+impl Default for MeasurementConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: SAMPLE_RATE_DEFAULT,
+            calibration_factors: vec![CALIBRATION_FACTOR_DEFAULT],
+            time_chart_periods: PERIODS_DEFAULT,
+            fft_size: FFT_SIZE_DEFAULT,
+            fft_window: FFT_WINDOW_DEFAULT,
+            harmonics_refresh_period: HARMONICS_REFRESH_PERIOD,
+            rated_voltage: RATED_VOLTAGE_DEFAULT,
+            frequency_estimator: FREQUENCY_ESTIMATOR_DEFAULT,
+            rms_window: WINDOW_DEFAULT,
+            measurement_mode: MEASUREMENT_MODE_DEFAULT,
+            cycles: CYCLES_DEFAULT,
+            chart_size: CHART_SIZE_DEFAULT,
+            rms_refresh_period: RMS_REFRESH_PERIOD_DEFAULT,
+        }
+    }
+}
+
+impl MeasurementConfig {
+    pub fn calibration_factor(&self, channel: Channel) -> CalibrationFactor {
+        self.calibration_factors
+            .get(channel)
+            .copied()
+            .unwrap_or(CALIBRATION_FACTOR_DEFAULT)
+    }
+
+    pub fn settings_packets(&self, channels: usize) -> Vec<SettingsPacket> {
+        let mut packets = vec![SettingsPacket::SampleRate(self.sample_rate as f32)];
+
+        packets.extend((0..channels).map(|channel| {
+            SettingsPacket::CalibrationFactor(channel, self.calibration_factor(channel))
+        }));
+
+        packets.extend([
+            SettingsPacket::ChartSize(self.chart_size),
+            SettingsPacket::TimeChartPeriods(self.time_chart_periods),
+            SettingsPacket::FftSize(self.fft_size),
+            SettingsPacket::FftWindow(self.fft_window),
+            SettingsPacket::HarmonicsRefreshPeriod(self.harmonics_refresh_period),
+            SettingsPacket::RatedVoltage(self.rated_voltage),
+            SettingsPacket::FrequencyEstimator(self.frequency_estimator),
+            SettingsPacket::Window(self.rms_window),
+            SettingsPacket::MeasurementMode(self.measurement_mode),
+            SettingsPacket::Cycles(self.cycles),
+            SettingsPacket::RmsRefreshPeriod(self.rms_refresh_period),
+        ]);
+
+        packets
+    }
+
+    /// Checks a setting received from a remote interface, only measurement settings within the
+    /// ranges of the user interface are accepted.
+    pub fn check(packet: &SettingsPacket, channels: usize) -> Result<(), String> {
+        match *packet {
+            SettingsPacket::SampleRate(sample_rate) => check_in_range(
+                "sample rate",
+                sample_rate,
+                *SAMPLE_RATE_RANGE.start() as f32..=*SAMPLE_RATE_RANGE.end() as f32,
+            ),
+            SettingsPacket::CalibrationFactor(channel, calibration_factor) => {
+                check_in_range("channel", channel + 1, 1..=channels)?;
+                check_in_range(
+                    "calibration factor",
+                    calibration_factor,
+                    CALIBRATION_FACTOR_RANGE,
+                )
+            }
+            SettingsPacket::TimeChartPeriods(periods) => {
+                check_in_range("periods", periods, PERIODS_RANGE)
+            }
+            SettingsPacket::FftSize(fft_size) => {
+                check_in_range("FFT size", fft_size, FFT_SIZE_RANGE)
+            }
+            SettingsPacket::FftWindow(_)
+            | SettingsPacket::FrequencyEstimator(_)
+            | SettingsPacket::MeasurementMode(_) => Ok(()),
+            SettingsPacket::HarmonicsRefreshPeriod(refresh_period)
+            | SettingsPacket::RmsRefreshPeriod(refresh_period) => {
+                check_in_range("refresh period", refresh_period, REFRESH_PERIOD_RANGE)
+            }
+            SettingsPacket::RatedVoltage(rated_voltage) => {
+                check_in_range("rated voltage", rated_voltage, RATED_VOLTAGE_RANGE)
+            }
+            SettingsPacket::Window(window) => check_in_range("window", window, RMS_WINDOW_RANGE),
+            SettingsPacket::Cycles(cycles) => check_in_range("cycles", cycles, CYCLES_RANGE),
+            SettingsPacket::ChartSize(chart_size) => {
+                check_in_range("chart size", chart_size, CHART_SIZE_RANGE)
+            }
+            SettingsPacket::Batch(ref packets) => packets
+                .iter()
+                .try_for_each(|packet| Self::check(packet, channels)),
+            _ => Err("not a measurement setting".to_string()),
+        }
+    }
+
+    /// Takes over a measurement setting that has been sent to the pipeline.
+    pub fn apply(&mut self, packet: &SettingsPacket) {
+        match *packet {
+            SettingsPacket::SampleRate(sample_rate) => self.sample_rate = sample_rate as usize,
+            SettingsPacket::CalibrationFactor(channel, calibration_factor) => {
+                if channel >= self.calibration_factors.len() {
+                    self.calibration_factors
+                        .resize(channel + 1, CALIBRATION_FACTOR_DEFAULT);
+                }
+
+                self.calibration_factors[channel] = calibration_factor;
+            }
+            SettingsPacket::TimeChartPeriods(periods) => self.time_chart_periods = periods,
+            SettingsPacket::FftSize(fft_size) => self.fft_size = fft_size,
+            SettingsPacket::FftWindow(fft_window) => self.fft_window = fft_window,
+            SettingsPacket::HarmonicsRefreshPeriod(refresh_period) => {
+                self.harmonics_refresh_period = refresh_period
+            }
+            SettingsPacket::RatedVoltage(rated_voltage) => self.rated_voltage = rated_voltage,
+            SettingsPacket::FrequencyEstimator(frequency_estimator) => {
+                self.frequency_estimator = frequency_estimator
+            }
+            SettingsPacket::Window(window) => self.rms_window = window,
+            SettingsPacket::MeasurementMode(measurement_mode) => {
+                self.measurement_mode = measurement_mode
+            }
+            SettingsPacket::Cycles(cycles) => self.cycles = cycles,
+            SettingsPacket::ChartSize(chart_size) => self.chart_size = chart_size,
+            SettingsPacket::RmsRefreshPeriod(refresh_period) => {
+                self.rms_refresh_period = refresh_period
+            }
+            SettingsPacket::Batch(ref packets) => {
+                for packet in packets {
+                    self.apply(packet);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn validate(&mut self, prefix: &str, warnings: &mut Vec<String>) {
+        check_range(
+            warnings,
+            &format!("{}.sample_rate", prefix),
+            &mut self.sample_rate,
+            SAMPLE_RATE_RANGE,
+            SAMPLE_RATE_DEFAULT,
+        );
+
+        for (channel, calibration_factor) in self.calibration_factors.iter_mut().enumerate() {
+            check_range(
+                warnings,
+                &format!("{}.calibration_factors[{}]", prefix, channel),
+                calibration_factor,
+                CALIBRATION_FACTOR_RANGE,
+                CALIBRATION_FACTOR_DEFAULT,
+            );
+        }
+
+        check_range(
+            warnings,
+            &format!("{}.time_chart_periods", prefix),
+            &mut self.time_chart_periods,
+            PERIODS_RANGE,
+            PERIODS_DEFAULT,
+        );
+        check_range(
+            warnings,
+            &format!("{}.fft_size", prefix),
+            &mut self.fft_size,
+            FFT_SIZE_RANGE,
+            FFT_SIZE_DEFAULT,
+        );
+        check_range(
+            warnings,
+            &format!("{}.harmonics_refresh_period", prefix),
+            &mut self.harmonics_refresh_period,
+            REFRESH_PERIOD_RANGE,
+            HARMONICS_REFRESH_PERIOD,
+        );
+        check_range(
+            warnings,
+            &format!("{}.rated_voltage", prefix),
+            &mut self.rated_voltage,
+            RATED_VOLTAGE_RANGE,
+            RATED_VOLTAGE_DEFAULT,
+        );
+        check_range(
+            warnings,
+            &format!("{}.rms_window", prefix),
+            &mut self.rms_window,
+            RMS_WINDOW_RANGE,
+            WINDOW_DEFAULT,
+        );
+        check_range(
+            warnings,
+            &format!("{}.cycles", prefix),
+            &mut self.cycles,
+            CYCLES_RANGE,
+            CYCLES_DEFAULT,
+        );
+        check_range(
+            warnings,
+            &format!("{}.chart_size", prefix),
+            &mut self.chart_size,
+            CHART_SIZE_RANGE,
+            CHART_SIZE_DEFAULT,
+        );
+        check_range(
+            warnings,
+            &format!("{}.rms_refresh_period", prefix),
+            &mut self.rms_refresh_period,
+            REFRESH_PERIOD_RANGE,
+            RMS_REFRESH_PERIOD_DEFAULT,
+        );
+    }
+}
+
+/// How measurements are shown in the user interface.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayConfig {
+    pub unit: VoltageUnit,
+    pub precision: Precision,
+    pub zoom_factor: f32,
+    pub channel_layout: ChannelLayout,
+    pub spectrum_scale: SpectrumScale,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            unit: UNIT_DEFAULT,
+            precision: PRECISION_DEFAULT,
+            zoom_factor: ZOOM_FACTOR_DEFAULT,
+            channel_layout: CHANNEL_LAYOUT_DEFAULT,
+            spectrum_scale: SPECTRUM_SCALE_DEFAULT,
+        }
+    }
+}
+
+impl DisplayConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        check_range(
+            warnings,
+            "display.precision",
+            &mut self.precision,
+            PRECISION_RANGE,
+            PRECISION_DEFAULT,
+        );
+        check_range(
+            warnings,
+            "display.zoom_factor",
+            &mut self.zoom_factor,
+            ZOOM_FACTOR_RANGE,
+            ZOOM_FACTOR_DEFAULT,
+        );
+    }
+}
+
+/// Where and how often measurements are written in headless mode.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeadlessConfig {
+    pub output: Output,
+    // seconds between two measurements
+    pub interval: f32,
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        Self {
+            output: Output::Stdout,
+            interval: HEADLESS_INTERVAL_DEFAULT,
+        }
+    }
+}
+
+impl HeadlessConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        if self.interval.is_nan() || self.interval < INTERVAL_MIN {
+            warnings.push(format!(
+                "`headless.interval` = {} is shorter than {}, using {} instead",
+                self.interval, INTERVAL_MIN, HEADLESS_INTERVAL_DEFAULT
+            ));
+
+            self.interval = HEADLESS_INTERVAL_DEFAULT;
+        }
+    }
+}
+
+/// SCPI server for remote control over TCP.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScpiConfig {
+    pub enabled: bool,
+    pub listen_address: SocketAddr,
+}
+
+impl Default for ScpiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: SCPI_LISTEN_ADDRESS_DEFAULT,
+        }
+    }
+}
+
+/// HTTP server of the JSON API.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub enabled: bool,
+    pub listen_address: SocketAddr,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: HTTP_LISTEN_ADDRESS_DEFAULT,
+        }
+    }
+}
+
+/// WebSocket server streaming the chart data.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebSocketConfig {
+    pub enabled: bool,
+    pub listen_address: SocketAddr,
+}
+
+impl Default for WebSocketConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: WEBSOCKET_LISTEN_ADDRESS_DEFAULT,
+        }
+    }
+}
+
+/// Prometheus metrics endpoint.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    pub listen_address: SocketAddr,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: METRICS_LISTEN_ADDRESS_DEFAULT,
+        }
+    }
+}
+
+/// Modbus TCP server.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModbusConfig {
+    pub enabled: bool,
+    pub listen_address: SocketAddr,
+}
+
+impl Default for ModbusConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen_address: MODBUS_LISTEN_ADDRESS_DEFAULT,
+        }
+    }
+}
+
+/// Limits of the measurements of every channel, a value beyond a limit raises an alarm. Limits
+/// that are not set are not checked.
+#[derive(PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlarmConfig {
+    // volts
+    pub rms_min: Option<f64>,
+    pub rms_max: Option<f64>,
+    pub peak_sqrt_min: Option<f64>,
+    pub peak_sqrt_max: Option<f64>,
+    // hertz
+    pub frequency_min: Option<f64>,
+    pub frequency_max: Option<f64>,
+}
+
+impl AlarmConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        for (name, min, max) in [
+            ("rms", &mut self.rms_min, &mut self.rms_max),
+            (
+                "peak_sqrt",
+                &mut self.peak_sqrt_min,
+                &mut self.peak_sqrt_max,
+            ),
+            (
+                "frequency",
+                &mut self.frequency_min,
+                &mut self.frequency_max,
+            ),
+        ] {
+            if let (Some(minimum), Some(maximum)) = (*min, *max) {
+                if minimum > maximum {
+                    warnings.push(format!(
+                        "`alarms.{0}_min` = {1} is larger than `alarms.{0}_max` = {2}, both limits are ignored",
+                        name, minimum, maximum
+                    ));
+
+                    *min = None;
+                    *max = None;
+                }
+            }
+        }
+    }
+}
+
+/// MQTT publisher of the measurements and alarms.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    // `host:port`
+    pub broker: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+
+    // `{channel}` is replaced by the channel, counted from 0
+    pub rms_topic: String,
+    pub peak_sqrt_topic: String,
+    pub frequency_topic: String,
+    pub alarm_topic: String,
+    // `online` while connected, `offline` as last will
+    pub status_topic: String,
+
+    pub qos: u8,
+    // messages kept while the broker is not reachable
+    pub buffer_size: usize,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            broker: MQTT_BROKER_DEFAULT.to_string(),
+            client_id: MQTT_CLIENT_ID_DEFAULT.to_string(),
+            username: None,
+            password: None,
+            rms_topic: MQTT_RMS_TOPIC_DEFAULT.to_string(),
+            peak_sqrt_topic: MQTT_PEAK_SQRT_TOPIC_DEFAULT.to_string(),
+            frequency_topic: MQTT_FREQUENCY_TOPIC_DEFAULT.to_string(),
+            alarm_topic: MQTT_ALARM_TOPIC_DEFAULT.to_string(),
+            status_topic: MQTT_STATUS_TOPIC_DEFAULT.to_string(),
+            qos: MQTT_QOS_DEFAULT,
+            buffer_size: MQTT_BUFFER_SIZE_DEFAULT,
+        }
+    }
+}
+
+impl MqttConfig {
+    /// Host and port of the broker.
+    pub fn broker_address(&self) -> Option<(String, u16)> {
+        let (host, port) = self.broker.trim().rsplit_once(':')?;
+
+        (!host.is_empty())
+            .then_some(())
+            .and(port.parse().ok())
+            .map(|port| (host.to_string(), port))
+    }
+
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        if self.broker_address().is_none() {
+            warnings.push(format!(
+                "`mqtt.broker` = \"{}\" is not a `host:port` address, using \"{}\" instead",
+                self.broker, MQTT_BROKER_DEFAULT
+            ));
+
+            self.broker = MQTT_BROKER_DEFAULT.to_string();
+        }
+
+        check_range(
+            warnings,
+            "mqtt.qos",
+            &mut self.qos,
+            MQTT_QOS_RANGE,
+            MQTT_QOS_DEFAULT,
+        );
+        check_range(
+            warnings,
+            "mqtt.buffer_size",
+            &mut self.buffer_size,
+            MQTT_BUFFER_SIZE_RANGE,
+            MQTT_BUFFER_SIZE_DEFAULT,
+        );
+    }
+}
+
+/// Captures of the samples written to disk.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecorderConfig {
+    pub directory: PathBuf,
+    // MiB, a full file is continued in a new one
+    pub max_file_size: u64,
+    // the oldest captures are deleted beyond this count, 0 keeps all of them
+    pub max_files: usize,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            directory: dirs::data_dir()
+                .map(|directory| directory.join(CONFIG_DIRECTORY).join(RECORDER_DIRECTORY))
+                .unwrap_or_else(|| PathBuf::from(RECORDER_DIRECTORY)),
+            max_file_size: RECORDER_MAX_FILE_SIZE_DEFAULT,
+            max_files: RECORDER_MAX_FILES_DEFAULT,
+        }
+    }
+}
+
+impl RecorderConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        check_range(
+            warnings,
+            "recorder.max_file_size",
+            &mut self.max_file_size,
+            RECORDER_MAX_FILE_SIZE_RANGE,
+            RECORDER_MAX_FILE_SIZE_DEFAULT,
+        );
+    }
+}
+
+/// Chart data exported from the user interface.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    pub directory: PathBuf,
+    pub comtrade_format: ComtradeFormat,
+    // written into the COMTRADE configuration
+    pub station_name: String,
+    // nominal frequency of the measured system in Hz
+    pub line_frequency: f64,
+    // seconds around the playback position exported as an event
+    pub event_window: f64,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            directory: dirs::data_dir()
+                .map(|directory| directory.join(CONFIG_DIRECTORY).join(EXPORT_DIRECTORY))
+                .unwrap_or_else(|| PathBuf::from(EXPORT_DIRECTORY)),
+            comtrade_format: ComtradeFormat::Binary,
+            station_name: STATION_NAME_DEFAULT.to_string(),
+            line_frequency: LINE_FREQUENCY_DEFAULT,
+            event_window: EVENT_WINDOW_DEFAULT,
+        }
+    }
+}
+
+impl ExportConfig {
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        check_range(
+            warnings,
+            "export.line_frequency",
+            &mut self.line_frequency,
+            LINE_FREQUENCY_RANGE,
+            LINE_FREQUENCY_DEFAULT,
+        );
+        check_range(
+            warnings,
+            "export.event_window",
+            &mut self.event_window,
+            EVENT_WINDOW_RANGE,
+            EVENT_WINDOW_DEFAULT,
+        );
+    }
+}
+
+/// A named set of measurement settings, selected as a whole.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    #[serde(flatten)]
+    pub measurement: MeasurementConfig,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            name: PROFILE_NAME_DEFAULT.to_string(),
+            measurement: MeasurementConfig::default(),
+        }
+    }
+}
+
+/// Settings that are persisted between launches.
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub ingest: IngestConfig,
+    // the pipeline is built for a fixed number of channels, changes apply after a restart
+    pub channels: usize,
+
+    // current settings, a copy of the active profile while one is selected
+    pub measurement: MeasurementConfig,
+    pub display: DisplayConfig,
+    pub headless: HeadlessConfig,
+    pub scpi: ScpiConfig,
+    pub http: HttpConfig,
+    pub websocket: WebSocketConfig,
+    pub metrics: MetricsConfig,
+    pub mqtt: MqttConfig,
+    pub modbus: ModbusConfig,
+    pub alarms: AlarmConfig,
+    pub recorder: RecorderConfig,
+    pub export: ExportConfig,
+
+    // name of the active profile
+    pub profile: Option<String>,
+    pub profiles: Vec<Profile>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ingest: IngestConfig::default(),
+            channels: CHANNELS_DEFAULT,
+            measurement: MeasurementConfig::default(),
+            display: DisplayConfig::default(),
+            headless: HeadlessConfig::default(),
+            scpi: ScpiConfig::default(),
+            http: HttpConfig::default(),
+            websocket: WebSocketConfig::default(),
+            metrics: MetricsConfig::default(),
+            mqtt: MqttConfig::default(),
+            modbus: ModbusConfig::default(),
+            alarms: AlarmConfig::default(),
+            recorder: RecorderConfig::default(),
+            export: ExportConfig::default(),
+            profile: None,
+            profiles: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Location of the config file in the user config directory, if the platform has one.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|directory| directory.join(CONFIG_DIRECTORY).join(CONFIG_FILE))
+    }
+
+    /// Loads the config file, a missing file yields the default config. Invalid and out of range
+    /// values are replaced by their defaults, the returned warnings describe every replacement.
+    pub fn load(path: &Path) -> ConfigResult<(Self, Vec<String>)> {
+        if !path.exists() {
+            return Ok((Self::default(), Vec::new()));
+        }
+
+        let loaded: toml::Table = toml::from_str(&fs::read_to_string(path)?)?;
+
+        let mut config = toml::Value::try_from(Self::default())?;
+        let mut warnings = Vec::new();
+
+        merge_values(&mut config, &mut Vec::new(), loaded, &mut warnings);
+
+        let mut config: Self = config.try_into()?;
+        config.validate(&mut warnings);
+
+        Ok((config, warnings))
+    }
+
+    fn validate(&mut self, warnings: &mut Vec<String>) {
+        check_range(
+            warnings,
+            "channels",
+            &mut self.channels,
+            1..=MAX_CHANNELS,
+            CHANNELS_DEFAULT,
+        );
+
+        self.ingest.validate(warnings);
+        self.measurement.validate("measurement", warnings);
+        self.display.validate(warnings);
+        self.headless.validate(warnings);
+        self.mqtt.validate(warnings);
+        self.alarms.validate(warnings);
+        self.recorder.validate(warnings);
+        self.export.validate(warnings);
+
+        for index in 0..self.profiles.len() {
+            let name = self.profiles[index].name.trim().to_string();
+
+            let taken = self.profiles[..index]
+                .iter()
+                .any(|profile| profile.name == name);
+
+            if name.is_empty() || taken {
+                let unique_name = self.unique_profile_name(&name);
+
+                warnings.push(format!(
+                    "Profile name \"{}\" is empty or used twice, using \"{}\" instead",
+                    name, unique_name
+                ));
+
+                self.profiles[index].name = unique_name;
+            }
+
+            self.profiles[index]
+                .measurement
+                .validate(&format!("profiles[{}]", index), warnings);
+        }
+
+        if let Some(name) = &self.profile {
+            match self.profiles.iter().find(|profile| &profile.name == name) {
+                // the settings are taken from the active profile
+                Some(profile) => self.measurement = profile.measurement.clone(),
+                None => {
+                    warnings.push(format!("Profile \"{}\" does not exist", name));
+                    self.profile = None;
+                }
+            }
+        }
+    }
+
+    /// `name` if no profile is called like that, otherwise `name` with the lowest free number.
+    pub fn unique_profile_name(&self, name: &str) -> String {
+        let name = match name.trim() {
+            "" => PROFILE_NAME_DEFAULT,
+            name => name,
+        };
+
+        let taken = |name: &str| self.profiles.iter().any(|profile| profile.name == name);
+
+        if !taken(name) {
+            return name.to_string();
+        }
+
+        (2..)
+            .map(|number| format!("{} {}", name, number))
+            .find(|name| !taken(name))
+            .unwrap()
+    }
+
+    pub fn active_profile(&self) -> Option<usize> {
+        let name = self.profile.as_ref()?;
+
+        self.profiles
+            .iter()
+            .position(|profile| &profile.name == name)
+    }
+
+    pub fn save(&self, path: &Path) -> ConfigResult<()> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        fs::write(path, toml::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+}
+
+fn value_at<'a>(value: &'a mut toml::Value, path: &[String]) -> &'a mut toml::Value {
+    path.iter().fold(value, |value, key| match value {
+        toml::Value::Array(array) => &mut array[key.parse::<usize>().unwrap()],
+        value => &mut value[key.as_str()],
+    })
+}
+
+// written like the keys in the warnings of the validation, `profiles[0].name`
+fn path_name(path: &[String]) -> String {
+    path.iter().fold(String::new(), |name, key| {
+        if key.parse::<usize>().is_ok() {
+            format!("{}[{}]", name, key)
+        } else if name.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", name, key)
+        }
+    })
+}
+
+fn table_at<'a>(value: &'a mut toml::Value, path: &[String]) -> &'a mut toml::Table {
+    value_at(value, path).as_table_mut().unwrap()
+}
+
+/// Takes the values of `loaded` one at a time into `config`, which holds the defaults. A value
+/// that does not deserialize is skipped, so only the affected setting keeps its default.
+fn merge_values(
+    config: &mut toml::Value,
+    path: &mut Vec<String>,
+    loaded: toml::Table,
+    warnings: &mut Vec<String>,
+) {
+    for (key, value) in loaded {
+        let table = table_at(config, path);
+
+        match (value, table.get(&key)) {
+            // tables are merged key by key, so their other values are kept
+            (toml::Value::Table(loaded), Some(toml::Value::Table(_))) => {
+                path.push(key);
+                merge_values(config, path, loaded, warnings);
+                path.pop();
+            }
+            // every table of an array starts out with the defaults, like a missing table
+            (toml::Value::Array(loaded), Some(toml::Value::Array(_)))
+                if loaded.iter().all(toml::Value::is_table) =>
+            {
+                table.insert(key.clone(), toml::Value::Array(Vec::new()));
+                path.push(key);
+
+                for (index, loaded) in loaded.into_iter().enumerate() {
+                    if let (toml::Value::Array(array), toml::Value::Table(loaded)) =
+                        (value_at(config, path), loaded)
+                    {
+                        array.push(toml::Value::Table(toml::Table::new()));
+
+                        path.push(index.to_string());
+                        merge_values(config, path, loaded, warnings);
+                        path.pop();
+                    }
+                }
+
+                path.pop();
+            }
+            (value, _) => {
+                let previous = table.insert(key.clone(), value);
+
+                if let Err(e) = config.clone().try_into::<Config>() {
+                    let table = table_at(config, path);
+
+                    match previous {
+                        Some(previous) => table.insert(key.clone(), previous),
+                        None => table.remove(&key),
+                    };
+
+                    path.push(key);
+                    warnings.push(format!(
+                        "Invalid value for `{}`, using the default: {}",
+                        path_name(path),
+                        e.message()
+                    ));
+                    path.pop();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use crate::{
+    settings::{Cycles, SampleRate},
+    time_chart::ZeroCrossingDetector,
+};
+use conductor::prelude::*;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt::{Display, Formatter},
+};
+
+// the signal needs to fall below this share of the amplitude of the last cycle before the next
+// rising zero crossing counts, so noise and harmonics around zero do not start a cycle
+const HYSTERESIS: f64 = 0.1;
+// cycles taking longer are not counted, the signal is treated as lost until the next crossing
+const MAX_CYCLE_DURATION: f64 = 1.0;
+
+/// How the RMS, Vp / √2 and frequency charts measure the signal.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MeasurementMode {
+    // over the rms window, independent of the period of the signal
+    Window,
+    // over whole cycles between rising zero crossings
+    Cycles,
+}
+
+impl Display for MeasurementMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MeasurementMode::Window => write!(f, "Time Window"),
+            MeasurementMode::Cycles => write!(f, "Whole Cycles"),
+        }
+    }
+}
+
+impl MeasurementMode {
+    pub const ALL: [MeasurementMode; 2] = [MeasurementMode::Window, MeasurementMode::Cycles];
+}
+
+/// Values of the signal over the last whole cycles.
+#[derive(Clone, Copy, Debug)]
+pub struct CycleValues {
+    pub rms: f64,
+    pub peak: f64,
+    pub frequency: f64,
+}
+
+/// Sums of the samples of a single cycle, the samples at its crossings are weighted by the share
+/// of the sample period within the cycle.
+struct Cycle {
+    sum: f64,
+    sum_of_squares: f64,
+    // in samples, from crossing to crossing once the cycle is completed
+    duration: f64,
+    peak: f64,
+    // largest deviation from the mean the cycle started with
+    amplitude: f64,
+}
+
+impl Default for Cycle {
+    fn default() -> Self {
+        Self {
+            sum: 0.0,
+            sum_of_squares: 0.0,
+            duration: 0.0,
+            peak: f64::MIN,
+            amplitude: 0.0,
+        }
+    }
+}
+
+impl Cycle {
+    fn push(&mut self, sample: f64, deviation: f64, weight: f64) {
+        self.sum += sample * weight;
+        self.sum_of_squares += sample * sample * weight;
+        self.duration += weight;
+        self.peak = self.peak.max(sample);
+        self.amplitude = self.amplitude.max(deviation.abs());
+    }
+}
+
+/// Detects the rising zero crossings of the signal after removing the mean of the last cycles,
+/// and measures the last whole cycles between them.
+struct CycleDetector {
+    sample_rate: f64,
+    cycles: Cycles,
+
+    crossings: ZeroCrossingDetector,
+    // a crossing started the current cycle
+    started: bool,
+    current: Cycle,
+    completed: VecDeque<Cycle>,
+}
+
+impl CycleDetector {
+    fn new(sample_rate: SampleRate, cycles: Cycles) -> Self {
+        Self {
+            sample_rate: sample_rate as f64,
+            cycles,
+            crossings: ZeroCrossingDetector::default(),
+            started: false,
+            current: Cycle::default(),
+            completed: VecDeque::new(),
+        }
+    }
+
+    fn set_cycles(&mut self, cycles: Cycles) {
+        self.cycles = cycles;
+
+        while self.completed.len() > cycles {
+            self.completed.pop_front();
+        }
+    }
+
+    /// There are enough cycles to measure.
+    fn is_complete(&self) -> bool {
+        self.completed.len() == self.cycles
+    }
+
+    fn mean(&self) -> f64 {
+        let (sum, duration) = self
+            .completed
+            .iter()
+            .fold((0.0, 0.0), |(sum, duration), cycle| {
+                (sum + cycle.sum, duration + cycle.duration)
+            });
+
+        if duration > 0.0 {
+            sum / duration
+        } else {
+            0.0
+        }
+    }
+
+    fn values(&self) -> CycleValues {
+        let (sum_of_squares, duration) =
+            self.completed
+                .iter()
+                .fold((0.0, 0.0), |(sum_of_squares, duration), cycle| {
+                    (
+                        sum_of_squares + cycle.sum_of_squares,
+                        duration + cycle.duration,
+                    )
+                });
+
+        CycleValues {
+            rms: (sum_of_squares / duration).sqrt(),
+            peak: self
+                .completed
+                .iter()
+                .map(|cycle| cycle.peak)
+                .fold(f64::MIN, f64::max),
+            frequency: self.completed.len() as f64 / duration * self.sample_rate,
+        }
+    }
+
+    /// Takes the next sample, returns the values of the last cycles whenever a cycle completes.
+    fn push(&mut self, sample: f32) -> Option<CycleValues> {
+        let sample = sample as f64;
+        let value = sample - self.mean();
+
+        let hysteresis = self
+            .completed
+            .back()
+            .map_or(0.0, |cycle| cycle.amplitude * HYSTERESIS);
+
+        let mut values = None;
+        // share of the current sample in the current cycle
+        let mut weight = 1.0;
+
+        if let Some(fraction) = self.crossings.push(value, hysteresis) {
+            if self.started {
+                let mut cycle = std::mem::take(&mut self.current);
+                cycle.push(sample, value, fraction);
+
+                self.completed.push_back(cycle);
+                while self.completed.len() > self.cycles {
+                    self.completed.pop_front();
+                }
+
+                if self.is_complete() {
+                    values = Some(self.values());
+                }
+            }
+
+            self.started = true;
+            self.current = Cycle::default();
+            weight = 1.0 - fraction;
+        }
+
+        if self.started {
+            self.current.push(sample, value, weight);
+
+            if self.current.duration > MAX_CYCLE_DURATION * self.sample_rate {
+                self.started = false;
+                self.current = Cycle::default();
+                self.completed.clear();
+            }
+        }
+
+        values
+    }
+}
+
+struct CycleMeasurementRunner {
+    input: NodeRunnerInputPort<f32>,
+    output: NodeRunnerOutputPort<Option<CycleValues>>,
+
+    cycles: NodeRunnerInputPort<Cycles>,
+    sample_rate: NodeRunnerInputPort<SampleRate>,
+}
+
+impl NodeRunner for CycleMeasurementRunner {
+    fn run(self: Box<Self>) {
+        let mut cycles = self.cycles.recv();
+        let mut sample_rate = self.sample_rate.recv();
+
+        let mut detector = CycleDetector::new(sample_rate, cycles);
+
+        loop {
+            receive! {
+                (self.input): sample => {
+                    let was_complete = detector.is_complete();
+
+                    match detector.push(sample) {
+                        Some(values) => self.output.send(&Some(values)),
+                        // the signal is lost, the last values are not valid anymore
+                        None if was_complete && !detector.is_complete() => self.output.send(&None),
+                        None => {}
+                    }
+                },
+                (self.cycles): new_cycles => {
+                    cycles = new_cycles;
+
+                    detector.set_cycles(cycles);
+                    if !detector.is_complete() {
+                        self.output.send(&None);
+                    }
+                },
+                (self.sample_rate): new_sample_rate => {
+                    sample_rate = new_sample_rate;
+
+                    // previous cycles are invalidated so a new detector must be created
+                    detector = CycleDetector::new(sample_rate, cycles);
+                    self.output.send(&None);
+                },
+            };
+        }
+    }
+}
+
+/// Measures the RMS, the peak and the frequency over the last whole cycles of the signal, every
+/// time a cycle completes. Sends `None` while there are not enough cycles.
+pub struct CycleMeasurement {
+    pub input: NodeConfigInputPort<f32>,
+    pub output: NodeConfigOutputPort<Option<CycleValues>>,
+
+    pub cycles: NodeConfigInputPort<Cycles>,
+    pub sample_rate: NodeConfigInputPort<SampleRate>,
+}
+
+impl CycleMeasurement {
+    pub fn new() -> Self {
+        Self {
+            input: NodeConfigInputPort::new(),
+            output: NodeConfigOutputPort::new(),
+
+            cycles: NodeConfigInputPort::new(),
+            sample_rate: NodeConfigInputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for CycleMeasurement {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(CycleMeasurementRunner {
+            input: self.input.into(),
+            output: self.output.into(),
+
+            cycles: self.cycles.into(),
+            sample_rate: self.sample_rate.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::TAU;
+
+    const SAMPLE_RATE: SampleRate = 3125.0;
+    // 62.46 samples per cycle, so the crossings fall between the samples
+    const FREQUENCY: f64 = 50.03;
+    const AMPLITUDE: f64 = 325.27;
+
+    /// Pushes `seconds` of a sine, starting at `start` seconds, returns the last values measured.
+    fn push_sine(
+        detector: &mut CycleDetector,
+        start: f64,
+        seconds: f64,
+        offset: f64,
+    ) -> Option<CycleValues> {
+        let first = (start * SAMPLE_RATE as f64) as usize;
+        let last = ((start + seconds) * SAMPLE_RATE as f64) as usize;
+
+        (first..last)
+            .filter_map(|sample| {
+                let time = sample as f64 / SAMPLE_RATE as f64;
+                detector.push((offset + AMPLITUDE * (TAU * FREQUENCY * time + 0.4).sin()) as f32)
+            })
+            .last()
+    }
+
+    fn assert_values(values: CycleValues, offset: f64) {
+        let rms = (offset * offset + AMPLITUDE * AMPLITUDE / 2.0).sqrt();
+
+        assert!((values.rms - rms).abs() < 0.01, "{:?}", values);
+        // the peak falls between the samples, off by up to a twentieth of a radian
+        assert!(
+            values.peak <= offset + AMPLITUDE && values.peak > offset + AMPLITUDE - 0.5,
+            "{:?}",
+            values
+        );
+        assert!((values.frequency - FREQUENCY).abs() < 0.001, "{:?}", values);
+    }
+
+    #[test]
+    fn measures_whole_cycles() {
+        for offset in [0.0, 2.0, -25.0] {
+            let mut detector = CycleDetector::new(SAMPLE_RATE, 10);
+
+            let values = push_sine(&mut detector, 0.0, 2.0, offset).unwrap();
+
+            assert!(detector.is_complete());
+            assert_values(values, offset);
+        }
+    }
+
+    #[test]
+    fn loses_the_signal_without_crossings() {
+        let mut detector = CycleDetector::new(SAMPLE_RATE, 10);
+
+        assert!(push_sine(&mut detector, 0.0, 2.0, 2.0).is_some());
+
+        let silence = (MAX_CYCLE_DURATION * SAMPLE_RATE as f64) as usize + 1;
+        assert!((0..silence).all(|_| detector.push(2.0).is_none()));
+        assert!(!detector.is_complete());
+
+        // the values of the cycles before the loss do not count anymore
+        assert!(push_sine(&mut detector, 3.0, 0.1, 2.0).is_none());
+        assert_values(push_sine(&mut detector, 3.1, 1.0, 2.0).unwrap(), 2.0);
+    }
+
+    #[test]
+    fn follows_the_number_of_cycles() {
+        let mut detector = CycleDetector::new(SAMPLE_RATE, 10);
+
+        push_sine(&mut detector, 0.0, 1.0, 0.0);
+
+        detector.set_cycles(5);
+        assert!(detector.is_complete());
+        assert_values(push_sine(&mut detector, 1.0, 0.5, 0.0).unwrap(), 0.0);
+
+        // 20 cycles take 0.4 seconds, only 5 of them are there
+        detector.set_cycles(20);
+        assert!(!detector.is_complete());
+        assert!(push_sine(&mut detector, 1.5, 0.25, 0.0).is_none());
+        assert_values(push_sine(&mut detector, 1.75, 0.25, 0.0).unwrap(), 0.0);
+    }
+}
@@ -0,0 +1,433 @@
+use crate::{capture::CaptureChannel, settings::CalibrationFactor};
+use chrono::{DateTime, FixedOffset};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+// revision of IEEE C37.111 the files follow
+const REVISION: u16 = 2013;
+// identifies the recording device in the configuration
+const DEVICE_ID: &str = "peakvoltmeter";
+// every line of the configuration and of ASCII data ends with CR LF
+const LINE_END: &str = "\r\n";
+// largest integer up to which every f32 is exact, non-integral samples are scaled to it
+const SCALED_LIMIT: f64 = 8_388_607.0;
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ComtradeFormat {
+    // one line of text per sample
+    Ascii,
+    // little endian 32 bit integers, `BINARY32`
+    Binary,
+}
+
+impl Display for ComtradeFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ComtradeFormat::Ascii => write!(f, "ASCII"),
+            ComtradeFormat::Binary => write!(f, "Binary"),
+        }
+    }
+}
+
+impl ComtradeFormat {
+    fn file_type(&self) -> &'static str {
+        match self {
+            ComtradeFormat::Ascii => "ASCII",
+            ComtradeFormat::Binary => "BINARY32",
+        }
+    }
+}
+
+/// Waveforms of all channels, written as a COMTRADE configuration and data file.
+pub struct ComtradeRecord {
+    pub station_name: String,
+    pub line_frequency: f64,
+    pub sample_rate: f64,
+    // time of the first sample
+    pub start: DateTime<FixedOffset>,
+    pub trigger: DateTime<FixedOffset>,
+    pub channels: Vec<CaptureChannel>,
+    // raw samples, interleaved like the samples of a packet
+    pub samples: Vec<f32>,
+}
+
+/// Integer data of a channel, the value in the unit of the channel is `a` times the data.
+struct AnalogChannel {
+    calibration_factor: CalibrationFactor,
+    // value of one step of the data in raw units
+    step: f64,
+    data: Vec<i32>,
+}
+
+impl AnalogChannel {
+    /// Integral raw samples are kept as they are, others are scaled to integers without losing
+    /// precision. The calibration factor is applied through `a`.
+    fn new(samples: Vec<f64>, calibration_factor: CalibrationFactor) -> Self {
+        let max = samples
+            .iter()
+            .fold(0.0, |max: f64, sample| max.max(sample.abs()));
+
+        let integral = max <= i32::MAX as f64 && samples.iter().all(|sample| sample.fract() == 0.0);
+
+        let step = if integral || max == 0.0 {
+            1.0
+        } else {
+            max / SCALED_LIMIT
+        };
+
+        Self {
+            calibration_factor,
+            step,
+            data: samples
+                .iter()
+                .map(|sample| (sample / step).round() as i32)
+                .collect(),
+        }
+    }
+
+    // the calibration factor is written as it is configured whenever the data is unscaled
+    fn a(&self) -> String {
+        if self.step == 1.0 {
+            self.calibration_factor.to_string()
+        } else {
+            (self.calibration_factor as f64 * self.step).to_string()
+        }
+    }
+
+    fn min(&self) -> i32 {
+        self.data.iter().copied().min().unwrap_or(0)
+    }
+
+    fn max(&self) -> i32 {
+        self.data.iter().copied().max().unwrap_or(0)
+    }
+}
+
+// commas separate the fields, so they are left out of names
+fn field(text: &str) -> String {
+    text.replace(',', " ")
+}
+
+fn timestamp(time: &DateTime<FixedOffset>) -> String {
+    time.format("%d/%m/%Y,%H:%M:%S%.6f").to_string()
+}
+
+// difference of the time stamps to UTC, like `+1`, `-5h30` or `0`
+fn time_code(time: &DateTime<FixedOffset>) -> String {
+    let offset = time.offset().local_minus_utc();
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => "0".to_string(),
+        (hours, 0) => format!("{}{}", sign, hours),
+        (hours, minutes) => format!("{}{}h{:02}", sign, hours, minutes),
+    }
+}
+
+impl ComtradeRecord {
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels.len().max(1)
+    }
+
+    fn analog_channels(&self) -> Vec<AnalogChannel> {
+        let count = self.channels.len();
+
+        self.channels
+            .iter()
+            .enumerate()
+            .map(|(channel, description)| {
+                AnalogChannel::new(
+                    self.samples
+                        .iter()
+                        .skip(channel)
+                        .step_by(count)
+                        .map(|&sample| sample as f64)
+                        .collect(),
+                    description.calibration_factor,
+                )
+            })
+            .collect()
+    }
+
+    fn configuration(&self, analog: &[AnalogChannel], format: ComtradeFormat) -> String {
+        let count = self.channels.len();
+
+        let mut lines = vec![
+            format!("{},{},{}", field(&self.station_name), DEVICE_ID, REVISION),
+            format!("{},{}A,0D", count, count),
+        ];
+
+        lines.extend(self.channels.iter().zip(analog).enumerate().map(
+            |(index, (description, analog))| {
+                // the calibrated values are primary values
+                format!(
+                    "{},{},,,{},{},0,0,{},{},1,1,P",
+                    index + 1,
+                    field(&description.name),
+                    field(&description.unit),
+                    analog.a(),
+                    analog.min(),
+                    analog.max()
+                )
+            },
+        ));
+
+        lines.extend([
+            self.line_frequency.to_string(),
+            // one sample rate for all samples
+            "1".to_string(),
+            format!("{},{}", self.sample_rate, self.frames()),
+            timestamp(&self.start),
+            timestamp(&self.trigger),
+            format.file_type().to_string(),
+            // time stamps of the data in microseconds
+            "1".to_string(),
+            format!("{},{}", time_code(&self.start), time_code(&self.start)),
+            // the clock of the computer is not known to be synchronized
+            "F,0".to_string(),
+        ]);
+
+        lines
+            .iter()
+            .map(|line| format!("{}{}", line, LINE_END))
+            .collect()
+    }
+
+    fn write_data(
+        &self,
+        mut writer: impl Write,
+        analog: &[AnalogChannel],
+        format: ComtradeFormat,
+    ) -> io::Result<()> {
+        for frame in 0..self.frames() {
+            let number = frame as u32 + 1;
+            let time = (frame as f64 * 1e6 / self.sample_rate).round() as u32;
+
+            match format {
+                ComtradeFormat::Ascii => {
+                    let mut fields = vec![number.to_string(), time.to_string()];
+                    fields.extend(analog.iter().map(|channel| channel.data[frame].to_string()));
+
+                    write!(writer, "{}{}", fields.join(","), LINE_END)?;
+                }
+                ComtradeFormat::Binary => {
+                    writer.write_all(&number.to_le_bytes())?;
+                    writer.write_all(&time.to_le_bytes())?;
+
+                    for channel in analog {
+                        writer.write_all(&channel.data[frame].to_le_bytes())?;
+                    }
+                }
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Writes `<name>.cfg` and `<name>.dat` into the directory, returns the path of the
+    /// configuration.
+    pub fn write(
+        &self,
+        directory: &Path,
+        name: &str,
+        format: ComtradeFormat,
+    ) -> io::Result<PathBuf> {
+        let analog = self.analog_channels();
+
+        let configuration = directory.join(format!("{}.cfg", name));
+        let data = directory.join(format!("{}.dat", name));
+
+        self.write_data(BufWriter::new(File::create(data)?), &analog, format)?;
+
+        File::create(&configuration)?.write_all(self.configuration(&analog, format).as_bytes())?;
+
+        Ok(configuration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use std::fs;
+
+    const FRAMES: usize = 100;
+
+    fn record() -> ComtradeRecord {
+        let start = DateTime::parse_from_rfc3339("2024-03-01T12:30:15.250000+01:00").unwrap();
+
+        ComtradeRecord {
+            station_name: "Lab, bench 1".to_string(),
+            line_frequency: 50.0,
+            sample_rate: 3125.0,
+            start,
+            trigger: start + TimeDelta::milliseconds(20),
+            channels: vec![
+                CaptureChannel {
+                    name: "U1".to_string(),
+                    unit: "V".to_string(),
+                    calibration_factor: 0.5,
+                },
+                CaptureChannel {
+                    name: "U2".to_string(),
+                    unit: "V".to_string(),
+                    calibration_factor: 2.0,
+                },
+            ],
+            // integral samples on the first channel, fractional ones on the second
+            samples: (0..FRAMES)
+                .flat_map(|frame| {
+                    let phase = frame as f32 * 0.1;
+                    [(phase.sin() * 1000.0).round(), phase.cos() * 1.5]
+                })
+                .collect(),
+        }
+    }
+
+    /// Analog channels of a configuration and the data, scaled by `a`, read back.
+    struct Parsed {
+        a: Vec<f64>,
+        sample_rate: f64,
+        frames: usize,
+        start: String,
+        trigger: String,
+        file_type: String,
+        values: Vec<Vec<f64>>,
+    }
+
+    fn parse(configuration: &Path) -> Parsed {
+        let text = fs::read_to_string(configuration).unwrap();
+        let lines = text.split(LINE_END).collect::<Vec<_>>();
+
+        let counts = lines[1].split(',').collect::<Vec<_>>();
+        let count = counts[0].parse::<usize>().unwrap();
+        assert_eq!(counts[1], format!("{}A", count));
+
+        let a = lines[2..2 + count]
+            .iter()
+            .map(|line| line.split(',').nth(5).unwrap().parse().unwrap())
+            .collect::<Vec<f64>>();
+
+        // line frequency and the number of sample rates follow the channels
+        let rate = lines[4 + count].split(',').collect::<Vec<_>>();
+        let frames = rate[1].parse().unwrap();
+        let file_type = lines[7 + count].to_string();
+
+        let data = fs::read(configuration.with_extension("dat")).unwrap();
+
+        let frame_values = match file_type.as_str() {
+            "ASCII" => String::from_utf8(data)
+                .unwrap()
+                .split(LINE_END)
+                .filter(|line| !line.is_empty())
+                .map(|line| {
+                    line.split(',')
+                        .map(|field| field.parse::<i64>().unwrap())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>(),
+            _ => data
+                .chunks(4 * (count + 2))
+                .map(|frame| {
+                    frame
+                        .chunks(4)
+                        .map(|field| i32::from_le_bytes(field.try_into().unwrap()) as i64)
+                        .collect()
+                })
+                .collect(),
+        };
+
+        for (frame, fields) in frame_values.iter().enumerate() {
+            assert_eq!(fields[0], frame as i64 + 1);
+        }
+
+        Parsed {
+            a: a.clone(),
+            sample_rate: rate[0].parse().unwrap(),
+            frames,
+            start: lines[5 + count].to_string(),
+            trigger: lines[6 + count].to_string(),
+            file_type,
+            values: frame_values
+                .iter()
+                .map(|fields| {
+                    fields[2..]
+                        .iter()
+                        .zip(&a)
+                        .map(|(&data, a)| data as f64 * a)
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    fn round_trip(format: ComtradeFormat) {
+        let record = record();
+
+        let directory = std::env::temp_dir().join(format!(
+            "peakvoltmeter-comtrade-{}-{}",
+            std::process::id(),
+            format.file_type()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+
+        let configuration = record.write(&directory, "record", format).unwrap();
+        let parsed = parse(&configuration);
+
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(parsed.file_type, format.file_type());
+        assert_eq!(parsed.a.len(), record.channels.len());
+
+        // integral samples keep the calibration factor, scaled ones include their step
+        let max = record
+            .samples
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .fold(0.0, |max: f64, &sample| max.max(sample.abs() as f64));
+
+        assert_eq!(parsed.a[0], 0.5);
+        assert_eq!(parsed.a[1], 2.0 * (max / SCALED_LIMIT));
+
+        assert_eq!(parsed.sample_rate, record.sample_rate);
+        assert_eq!(parsed.frames, FRAMES);
+        assert_eq!(parsed.values.len(), FRAMES);
+        assert_eq!(parsed.start, "01/03/2024,12:30:15.250000");
+        assert_eq!(parsed.trigger, "01/03/2024,12:30:15.270000");
+
+        for (frame, values) in parsed.values.iter().enumerate() {
+            for (channel, (value, description)) in values.iter().zip(&record.channels).enumerate() {
+                let sample = record.samples[frame * record.channels.len() + channel] as f64;
+                let expected = sample * description.calibration_factor as f64;
+
+                // rounded to the nearest step of the data
+                assert!(
+                    (value - expected).abs() <= parsed.a[channel] / 2.0 + 1e-9,
+                    "frame {} channel {}: {} instead of {}",
+                    frame,
+                    channel,
+                    value,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_round_trip() {
+        round_trip(ComtradeFormat::Ascii);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        round_trip(ComtradeFormat::Binary);
+    }
+}
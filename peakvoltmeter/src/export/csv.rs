@@ -0,0 +1,170 @@
+use super::ExportChart;
+use crate::{
+    application::VoltageUnit, channel::channel_name, harmonic_analysis::HarmonicAnalysis,
+    measurements::HARMONICS,
+};
+use chrono::{DateTime, Local, SecondsFormat, TimeDelta};
+use std::io::{self, Write};
+
+/// Writes the series of all channels of a chart as CSV, one row per point. The first two columns
+/// hold the time relative to the latest point and the wall-clock time, with `now` taken as the
+/// time of the latest point.
+pub fn write_csv(
+    mut writer: impl Write,
+    chart: ExportChart,
+    data: &[Vec<[f64; 2]>],
+    unit: VoltageUnit,
+    now: DateTime<Local>,
+) -> io::Result<()> {
+    let mut header = vec!["Relative Time (s)".to_string(), "Absolute Time".to_string()];
+
+    if chart == ExportChart::Harmonics {
+        header.push("Frequency (Hz)".to_string());
+    }
+
+    header.extend((0..data.len()).map(|channel| {
+        format!(
+            "{} {} ({})",
+            channel_name(channel),
+            chart.quantity(),
+            chart.unit(unit)
+        )
+    }));
+
+    writeln!(writer, "{}", header.join(","))?;
+
+    let rows = data.iter().map(Vec::len).max().unwrap_or(0);
+
+    // the time chart starts at its trigger, the trends end at the latest value and a spectrum is
+    // taken at once
+    let latest = match chart {
+        ExportChart::TimeChart => data
+            .iter()
+            .filter_map(|series| series.last())
+            .map(|point| point[0])
+            .fold(0.0, f64::max),
+        _ => 0.0,
+    };
+
+    for row in 0..rows {
+        let Some(x) = data
+            .iter()
+            .find_map(|series| series.get(row))
+            .map(|point| point[0])
+        else {
+            continue;
+        };
+
+        let relative = match chart {
+            ExportChart::Harmonics => 0.0,
+            _ => x - latest,
+        };
+
+        let absolute = now + TimeDelta::microseconds((relative * 1e6).round() as i64);
+
+        let mut fields = vec![
+            relative.to_string(),
+            absolute.to_rfc3339_opts(SecondsFormat::Micros, false),
+        ];
+
+        if chart == ExportChart::Harmonics {
+            fields.push(x.to_string());
+        }
+
+        // a channel without a value at this row leaves its cell empty
+        fields.extend(data.iter().map(|series| {
+            series
+                .get(row)
+                .map(|point| chart.scale(point[1], unit).to_string())
+                .unwrap_or_default()
+        }));
+
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the harmonic analysis of all channels as CSV, one row per harmonic followed by the
+/// total harmonic and total demand distortion. The analysis is taken at once, at `now`.
+pub fn write_harmonic_table(
+    mut writer: impl Write,
+    data: &[Option<HarmonicAnalysis>],
+    unit: VoltageUnit,
+    now: DateTime<Local>,
+) -> io::Result<()> {
+    let mut header = vec![
+        "Relative Time (s)".to_string(),
+        "Absolute Time".to_string(),
+        "Order".to_string(),
+    ];
+
+    header.extend((0..data.len()).flat_map(|channel| {
+        [
+            format!("{} Frequency (Hz)", channel_name(channel)),
+            format!("{} RMS ({})", channel_name(channel), unit.symbol()),
+            format!("{} Level (%)", channel_name(channel)),
+        ]
+    }));
+
+    writeln!(writer, "{}", header.join(","))?;
+
+    let time = [
+        0.0.to_string(),
+        now.to_rfc3339_opts(SecondsFormat::Micros, false),
+    ];
+
+    for harmonic in 0..HARMONICS {
+        let mut fields = time.to_vec();
+        fields.push((harmonic + 1).to_string());
+
+        // a channel without the harmonic leaves its cells empty
+        fields.extend(data.iter().flat_map(|analysis| {
+            let values = analysis.as_ref().and_then(|analysis| {
+                let rms = *analysis.harmonics.get(harmonic)?;
+                let fundamental = analysis.harmonics[0];
+
+                Some([
+                    ((harmonic + 1) as f64 * analysis.fundamental).to_string(),
+                    unit.scale(rms).to_string(),
+                    if fundamental > 0.0 {
+                        (rms / fundamental * 100.0).to_string()
+                    } else {
+                        String::new()
+                    },
+                ])
+            });
+
+            values.unwrap_or_default()
+        }));
+
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+
+    // the distortion is given in the level column
+    let thd = data
+        .iter()
+        .map(|analysis| analysis.as_ref().map(|analysis| analysis.thd))
+        .collect::<Vec<_>>();
+    let tdd = data
+        .iter()
+        .map(|analysis| analysis.as_ref().map(|analysis| analysis.tdd))
+        .collect::<Vec<_>>();
+
+    for (name, distortion) in [("THD", thd), ("TDD", tdd)] {
+        let mut fields = time.to_vec();
+        fields.push(name.to_string());
+
+        fields.extend(distortion.into_iter().flat_map(|value| {
+            [
+                String::new(),
+                String::new(),
+                value.map(|value| value.to_string()).unwrap_or_default(),
+            ]
+        }));
+
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+
+    Ok(())
+}
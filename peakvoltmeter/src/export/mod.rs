@@ -0,0 +1,480 @@
+mod comtrade;
+mod csv;
+
+use crate::{
+    application::VoltageUnit,
+    capture::{CaptureChannel, CaptureHeader},
+    config::ExportConfig,
+    playback::Playback,
+    time_chart::TimeChartCapture,
+    ChannelBuffers,
+};
+use chrono::{DateTime, Local, TimeDelta};
+pub use comtrade::ComtradeFormat;
+use comtrade::ComtradeRecord;
+use csv::{write_csv, write_harmonic_table};
+use egui::Color32;
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+const STAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+const HARMONIC_TABLE_STEM: &str = "harmonic-table";
+
+// the charts of the channels take over a trigger one after the other, a capture of another
+// trigger is read again until they match
+const CAPTURE_ATTEMPTS: usize = 50;
+const CAPTURE_RETRY: Duration = Duration::from_millis(10);
+
+/// Charts whose plotted data can be exported.
+#[derive(PartialEq, Clone, Copy)]
+pub enum ExportChart {
+    TimeChart,
+    Harmonics,
+    RmsTrend,
+    PeakSqrt,
+    Frequency,
+}
+
+impl ExportChart {
+    pub const ALL: [ExportChart; 5] = [
+        ExportChart::TimeChart,
+        ExportChart::Harmonics,
+        ExportChart::RmsTrend,
+        ExportChart::PeakSqrt,
+        ExportChart::Frequency,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ExportChart::TimeChart => "Time Chart",
+            ExportChart::Harmonics => "Harmonics",
+            ExportChart::RmsTrend => "RMS Trend",
+            ExportChart::PeakSqrt => "Vp / √2",
+            ExportChart::Frequency => "Frequency",
+        }
+    }
+
+    fn file_stem(&self) -> &'static str {
+        match self {
+            ExportChart::TimeChart => "time-chart",
+            ExportChart::Harmonics => "harmonics",
+            ExportChart::RmsTrend => "rms-trend",
+            ExportChart::PeakSqrt => "peak-sqrt",
+            ExportChart::Frequency => "frequency",
+        }
+    }
+
+    fn quantity(&self) -> &'static str {
+        match self {
+            ExportChart::TimeChart => "Voltage",
+            ExportChart::Harmonics => "Signal Strength",
+            ExportChart::RmsTrend => "RMS",
+            ExportChart::PeakSqrt => "Peak / sqrt(2)",
+            ExportChart::Frequency => "Frequency",
+        }
+    }
+
+    fn unit(&self, unit: VoltageUnit) -> &'static str {
+        match self {
+            ExportChart::Harmonics => "dBV",
+            ExportChart::Frequency => "Hz",
+            _ => unit.symbol(),
+        }
+    }
+
+    fn buffer<'a>(&self, channel: &'a ChannelBuffers) -> &'a Arc<RwLock<Vec<[f64; 2]>>> {
+        match self {
+            ExportChart::TimeChart => &channel.time_chart,
+            ExportChart::Harmonics => &channel.harmonics,
+            ExportChart::RmsTrend => &channel.rms_trend,
+            ExportChart::PeakSqrt => &channel.peak_sqrt,
+            ExportChart::Frequency => &channel.frequency_widget,
+        }
+    }
+
+    /// Converts a plotted value into the unit of the export.
+    fn scale(&self, value: f64, unit: VoltageUnit) -> f64 {
+        match self {
+            ExportChart::Harmonics | ExportChart::Frequency => value,
+            _ => unit.scale(value),
+        }
+    }
+}
+
+fn seconds(seconds: f64) -> TimeDelta {
+    TimeDelta::microseconds((seconds * 1e6).round() as i64)
+}
+
+/// Shows the file or directory written by the last export, or why it failed.
+pub fn result_ui(ui: &mut egui::Ui, result: &Option<Result<PathBuf, String>>) {
+    match result {
+        Some(Ok(path)) => {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            ui.label(format!("Exported {}", name))
+                .on_hover_text(path.display().to_string());
+        }
+        Some(Err(e)) => {
+            ui.colored_label(Color32::RED, format!("Export failed: {}", e));
+        }
+        None => {}
+    }
+}
+
+/// Exports the samples of a capture around the playback position as a COMTRADE event, with the
+/// trigger at the playback position.
+pub fn export_event(playback: &Playback, config: &ExportConfig) -> io::Result<PathBuf> {
+    let position = playback.position();
+
+    let start = playback.to_frames(position - config.event_window / 2.0);
+    let trigger = playback.to_frames(position);
+    let end = playback.to_frames(position + config.event_window / 2.0);
+
+    let CaptureHeader {
+        sample_rate,
+        start_time,
+        channels,
+    } = playback.header().clone();
+
+    let capture_start = DateTime::parse_from_rfc3339(&start_time)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let record = ComtradeRecord {
+        station_name: config.station_name.clone(),
+        line_frequency: config.line_frequency,
+        sample_rate: sample_rate as f64,
+        start: capture_start + seconds(playback.to_seconds(start)),
+        trigger: capture_start + seconds(playback.to_seconds(trigger)),
+        channels,
+        samples: playback.read(start..end)?,
+    };
+
+    fs::create_dir_all(&config.directory)?;
+
+    record.write(
+        &config.directory,
+        &format!("event-{}", record.trigger.format(STAMP_FORMAT)),
+        config.comtrade_format,
+    )
+}
+
+/// Writes the data of the charts to CSV files and the time chart capture to COMTRADE files in the
+/// export directory, from the top panel.
+pub struct Exporter {
+    channels: Vec<ChannelBuffers>,
+
+    // file or directory of the last export
+    result: Option<Result<PathBuf, String>>,
+}
+
+impl Exporter {
+    pub fn new(channels: Vec<ChannelBuffers>) -> Self {
+        Self {
+            channels,
+            result: None,
+        }
+    }
+
+    /// Copies the data of every channel of the chart, so the pipeline is not held up by writing.
+    fn snapshot(&self, chart: ExportChart) -> Vec<Vec<[f64; 2]>> {
+        self.channels
+            .iter()
+            .map(|channel| chart.buffer(channel).read().unwrap().clone())
+            .collect()
+    }
+
+    fn write(
+        &self,
+        path: &Path,
+        chart: ExportChart,
+        unit: VoltageUnit,
+        now: DateTime<Local>,
+    ) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        write_csv(&mut writer, chart, &self.snapshot(chart), unit, now)?;
+
+        writer.flush()
+    }
+
+    fn write_harmonic_table(
+        &self,
+        path: &Path,
+        unit: VoltageUnit,
+        now: DateTime<Local>,
+    ) -> io::Result<()> {
+        let data = self
+            .channels
+            .iter()
+            .map(|channel| channel.harmonic_analysis.read().unwrap().clone())
+            .collect::<Vec<_>>();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        write_harmonic_table(&mut writer, &data, unit, now)?;
+
+        writer.flush()
+    }
+
+    /// Exports one chart into a file named after the chart and the time of the export.
+    pub fn export(
+        &self,
+        directory: &Path,
+        chart: ExportChart,
+        unit: VoltageUnit,
+    ) -> io::Result<PathBuf> {
+        fs::create_dir_all(directory)?;
+
+        let now = Local::now();
+
+        let path = directory.join(format!(
+            "{}-{}.csv",
+            chart.file_stem(),
+            now.format(STAMP_FORMAT)
+        ));
+
+        self.write(&path, chart, unit, now)?;
+
+        Ok(path)
+    }
+
+    /// Exports the harmonic analysis of all channels into a file named after the time of the
+    /// export.
+    pub fn export_harmonic_table(
+        &self,
+        directory: &Path,
+        unit: VoltageUnit,
+    ) -> io::Result<PathBuf> {
+        fs::create_dir_all(directory)?;
+
+        let now = Local::now();
+
+        let path = directory.join(format!(
+            "{}-{}.csv",
+            HARMONIC_TABLE_STEM,
+            now.format(STAMP_FORMAT)
+        ));
+
+        self.write_harmonic_table(&path, unit, now)?;
+
+        Ok(path)
+    }
+
+    /// Exports all charts and the harmonic table into a new directory, with the same time of export in every file.
+    pub fn export_all(&self, directory: &Path, unit: VoltageUnit) -> io::Result<PathBuf> {
+        let now = Local::now();
+
+        let directory = directory.join(format!("export-{}", now.format(STAMP_FORMAT)));
+
+        fs::create_dir_all(&directory)?;
+
+        for chart in ExportChart::ALL {
+            self.write(
+                &directory.join(format!("{}.csv", chart.file_stem())),
+                chart,
+                unit,
+                now,
+            )?;
+        }
+
+        self.write_harmonic_table(
+            &directory.join(format!("{}.csv", HARMONIC_TABLE_STEM)),
+            unit,
+            now,
+        )?;
+
+        Ok(directory)
+    }
+
+    /// Captures of all channels taken at the same trigger.
+    fn aligned_captures(&self) -> io::Result<Vec<TimeChartCapture>> {
+        for _ in 0..CAPTURE_ATTEMPTS {
+            let Some(captures) = self
+                .channels
+                .iter()
+                .map(|channel| channel.time_chart_capture.read().unwrap().clone())
+                .collect::<Option<Vec<_>>>()
+            else {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "the time chart has not captured anything yet",
+                ));
+            };
+
+            if captures
+                .iter()
+                .all(|capture| capture.end == captures[0].end)
+            {
+                return Ok(captures);
+            }
+
+            thread::sleep(CAPTURE_RETRY);
+        }
+
+        Err(io::Error::other(
+            "the time chart captures of the channels do not match",
+        ))
+    }
+
+    /// Exports the time chart capture of all channels as COMTRADE files. The captures of all
+    /// channels end at the same sample, the trigger, and are described with the sample rate and
+    /// calibration they were taken with.
+    pub fn export_comtrade(&self, config: &ExportConfig) -> io::Result<PathBuf> {
+        let captures = self.aligned_captures()?;
+
+        let frames = captures
+            .iter()
+            .map(|capture| capture.samples.len())
+            .min()
+            .unwrap_or(0);
+        let sample_rate = captures[0].sample_rate as f64;
+
+        let channels = captures
+            .iter()
+            .enumerate()
+            .map(|(channel, capture)| CaptureChannel::new(channel, capture.calibration_factor))
+            .collect::<Vec<_>>();
+
+        // the time chart holds calibrated values, the calibration is applied again through the
+        // scaling of the channels, the latest samples end at the trigger
+        let samples = (0..frames)
+            .flat_map(|frame| {
+                captures.iter().map(move |capture| {
+                    let sample = capture.samples[capture.samples.len() - frames + frame];
+
+                    if capture.calibration_factor == 0.0 {
+                        0.0
+                    } else {
+                        sample / capture.calibration_factor
+                    }
+                })
+            })
+            .collect();
+
+        let time = captures[0].time.fixed_offset();
+        let start = time - seconds(frames.saturating_sub(1) as f64 / sample_rate);
+
+        let record = ComtradeRecord {
+            station_name: config.station_name.clone(),
+            line_frequency: config.line_frequency,
+            sample_rate,
+            start,
+            trigger: start,
+            channels,
+            samples,
+        };
+
+        fs::create_dir_all(&config.directory)?;
+
+        record.write(
+            &config.directory,
+            &format!("time-chart-{}", time.format(STAMP_FORMAT)),
+            config.comtrade_format,
+        )
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &mut ExportConfig, unit: VoltageUnit) {
+        ui.menu_button("Export", |ui| {
+            for chart in ExportChart::ALL {
+                if ui.button(chart.title()).clicked() {
+                    self.result = Some(
+                        self.export(&config.directory, chart, unit)
+                            .map_err(|e| e.to_string()),
+                    );
+                    ui.close_menu();
+                }
+            }
+
+            if ui.button("Harmonic Table").clicked() {
+                self.result = Some(
+                    self.export_harmonic_table(&config.directory, unit)
+                        .map_err(|e| e.to_string()),
+                );
+                ui.close_menu();
+            }
+
+            ui.separator();
+
+            if ui.button("All Charts").clicked() {
+                self.result = Some(
+                    self.export_all(&config.directory, unit)
+                        .map_err(|e| e.to_string()),
+                );
+                ui.close_menu();
+            }
+
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("COMTRADE:");
+                for format in [ComtradeFormat::Ascii, ComtradeFormat::Binary] {
+                    ui.radio_value(&mut config.comtrade_format, format, format.to_string());
+                }
+            });
+
+            if ui.button("Time Chart Waveforms").clicked() {
+                self.result = Some(self.export_comtrade(config).map_err(|e| e.to_string()));
+                ui.close_menu();
+            }
+        });
+
+        result_ui(ui, &self.result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn exports_the_captures_of_one_trigger() {
+        let channels = vec![ChannelBuffers::default(), ChannelBuffers::default()];
+        let exporter = Exporter::new(channels.clone());
+
+        let time = Local.with_ymd_and_hms(2024, 3, 1, 12, 30, 15).unwrap();
+        let capture = |end, calibration_factor| {
+            Some(TimeChartCapture {
+                end,
+                time,
+                sample_rate: 3125.0,
+                calibration_factor,
+                samples: vec![1.0; 125],
+            })
+        };
+
+        let directory =
+            std::env::temp_dir().join(format!("peakvoltmeter-time-chart-{}", std::process::id()));
+        let config = ExportConfig {
+            directory: directory.clone(),
+            comtrade_format: ComtradeFormat::Ascii,
+            ..Default::default()
+        };
+
+        assert!(exporter.export_comtrade(&config).is_err());
+
+        // the second channel has not taken over the latest trigger
+        *channels[0].time_chart_capture.write().unwrap() = capture(1000, 0.5);
+        *channels[1].time_chart_capture.write().unwrap() = capture(875, 2.0);
+        assert!(exporter.export_comtrade(&config).is_err());
+
+        *channels[1].time_chart_capture.write().unwrap() = capture(1000, 2.0);
+        let path = exporter.export_comtrade(&config).unwrap();
+
+        assert_eq!(
+            path.file_name().unwrap().to_string_lossy(),
+            format!("time-chart-{}.cfg", time.format(STAMP_FORMAT))
+        );
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,16 +1,21 @@
-use crate::settings::{ChartSize, FftSize, RefreshPeriod, SampleRate};
+use crate::{
+    cycle_measurement::{CycleValues, MeasurementMode},
+    settings::{ChartSize, RefreshPeriod},
+    streams::Publisher,
+};
 
 use conductor::prelude::*;
 use std::sync::{Arc, RwLock};
 
 struct ChartRunner {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 
-    fft_input: NodeRunnerInputPort<Vec<f64>>,
+    frequency: NodeRunnerInputPort<f64>,
+    cycle_values: NodeRunnerInputPort<Option<CycleValues>>,
 
+    mode: NodeRunnerInputPort<MeasurementMode>,
     chart_size: NodeRunnerInputPort<ChartSize>,
-    sample_rate: NodeRunnerInputPort<SampleRate>,
-    fft_size: NodeRunnerInputPort<FftSize>,
     refresh_period: NodeRunnerInputPort<RefreshPeriod>,
 }
 
@@ -24,29 +29,33 @@ impl NodeRunner for ChartRunner {
             (chart_size as f32 / refresh_period) as usize + 1
         }
 
+        let mut mode = self.mode.recv();
         let mut chart_size = self.chart_size.recv();
-        let mut sample_rate = self.sample_rate.recv();
-        let mut fft_size = self.fft_size.recv();
         let mut refresh_period = self.refresh_period.recv();
 
         let mut frequency_data =
             CircularBuffer::new(calculate_buffer_size(chart_size, refresh_period));
+        // latest values of the whole cycles, taken over at the pace of the spectra
+        let mut cycle_values = None;
 
         loop {
             receive! {
-                (self.fft_input): buffer => {
-                    let Some((max_index, _)) = buffer
-                        .iter()
-                        .enumerate()
-                        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
-                    else {
-                        continue;
+                (self.frequency): estimated_frequency => {
+                    let frequency = match mode {
+                        MeasurementMode::Window => estimated_frequency,
+                        MeasurementMode::Cycles => {
+                            let Some(CycleValues { frequency, .. }) = cycle_values else {
+                                continue;
+                            };
+
+                            frequency
+                        }
                     };
 
-                    let frequency = max_index as f64 * (sample_rate as f64 / fft_size as f64);
-
                     frequency_data.push(frequency);
 
+                    self.publisher.publish(0.0, || vec![frequency]);
+
                     *self.data.write().unwrap() = frequency_data
                         .clone()
                         .into_iter()
@@ -54,22 +63,19 @@ impl NodeRunner for ChartRunner {
                         .map(|(i, v)| [index_to_time(i, frequency_data.len(), refresh_period), v])
                         .collect();
                 },
-                (self.chart_size): new_chart_size => {
-                    chart_size = new_chart_size;
-
-                    frequency_data.resize(calculate_buffer_size(chart_size, refresh_period));
+                (self.cycle_values): new_cycle_values => {
+                    cycle_values = new_cycle_values;
                 },
-                (self.sample_rate): new_sample_rate => {
-                    sample_rate = new_sample_rate;
+                (self.mode): new_mode => {
+                    mode = new_mode;
 
                     // previous data is invalidated so new buffer must be created
                     frequency_data = CircularBuffer::new(calculate_buffer_size(chart_size, refresh_period));
                 },
-                (self.fft_size): new_fft_size => {
-                    fft_size = new_fft_size;
+                (self.chart_size): new_chart_size => {
+                    chart_size = new_chart_size;
 
-                    // previous data is invalidated so new buffer must be created
-                    frequency_data = CircularBuffer::new(calculate_buffer_size(chart_size, refresh_period));
+                    frequency_data.resize(calculate_buffer_size(chart_size, refresh_period));
                 },
                 (self.refresh_period): new_refresh_period => {
                     refresh_period = new_refresh_period;
@@ -84,25 +90,27 @@ impl NodeRunner for ChartRunner {
 
 pub struct Chart {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 
-    pub fft_input: NodeConfigInputPort<Vec<f64>>,
+    pub frequency: NodeConfigInputPort<f64>,
+    pub cycle_values: NodeConfigInputPort<Option<CycleValues>>,
 
+    pub mode: NodeConfigInputPort<MeasurementMode>,
     pub chart_size: NodeConfigInputPort<ChartSize>,
-    pub sample_rate: NodeConfigInputPort<SampleRate>,
-    pub fft_size: NodeConfigInputPort<FftSize>,
     pub refresh_period: NodeConfigInputPort<RefreshPeriod>,
 }
 
 impl Chart {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>, publisher: Publisher) -> Self {
         Self {
             data,
+            publisher,
 
-            fft_input: NodeConfigInputPort::new(),
+            frequency: NodeConfigInputPort::new(),
+            cycle_values: NodeConfigInputPort::new(),
 
+            mode: NodeConfigInputPort::new(),
             chart_size: NodeConfigInputPort::new(),
-            sample_rate: NodeConfigInputPort::new(),
-            fft_size: NodeConfigInputPort::new(),
             refresh_period: NodeConfigInputPort::new(),
         }
     }
@@ -112,12 +120,13 @@ impl NodeConfig for Chart {
     fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
         Box::new(ChartRunner {
             data: self.data,
+            publisher: self.publisher,
 
-            fft_input: self.fft_input.into(),
+            frequency: self.frequency.into(),
+            cycle_values: self.cycle_values.into(),
 
+            mode: self.mode.into(),
             chart_size: self.chart_size.into(),
-            sample_rate: self.sample_rate.into(),
-            fft_size: self.fft_size.into(),
             refresh_period: self.refresh_period.into(),
         })
     }
@@ -0,0 +1,576 @@
+use crate::{harmonics::FftWindow, settings::SampleRate, time_chart::ZeroCrossingDetector};
+use conductor::prelude::*;
+use core::fmt;
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::{
+    f64::consts::TAU,
+    fmt::{Display, Formatter},
+};
+
+// the dc component and slow drifts are removed over this time, in seconds
+const DC_TIME_CONSTANT: f64 = 1.0;
+// the envelope of the signal decays over this time, in seconds
+const ENVELOPE_TIME_CONSTANT: f64 = 1.0;
+// the low pass filter is only tuned again when the fundamental moves further, relative
+const RETUNE_THRESHOLD: f64 = 0.05;
+// zero crossings need the signal to fall below this share of its envelope first
+const HYSTERESIS: f64 = 0.1;
+// natural frequency in hertz and damping of the phase locked loop
+const PLL_BANDWIDTH: f64 = 1.0;
+const PLL_DAMPING: f64 = 0.707;
+// gain of the phase detector for a signal normalized to an amplitude of one
+const PLL_DETECTOR_GAIN: f64 = 0.5;
+// steps of the search for the offset of a sine between the lines, each halves the error
+const SEARCH_STEPS: usize = 40;
+// estimates of the sine corrected by the leakage of its image, each one more accurate
+const IMAGE_STEPS: usize = 2;
+
+/// Method of the frequency widget to measure the frequency of the fundamental.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FrequencyEstimator {
+    // parabola through the magnitudes of the peak line and its neighbours
+    Quadratic,
+    // parabola through the logarithm of the magnitudes
+    Gaussian,
+    // ratio of the complex values of the peak line and its neighbours
+    Jacobsen,
+    // period of the rising zero crossings of the filtered signal
+    ZeroCrossing,
+    // frequency of an oscillator locked to the filtered signal
+    Pll,
+}
+
+impl Display for FrequencyEstimator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FrequencyEstimator::Quadratic => write!(f, "Quadratic Interpolation"),
+            FrequencyEstimator::Gaussian => write!(f, "Gaussian Interpolation"),
+            FrequencyEstimator::Jacobsen => write!(f, "Jacobsen Interpolation"),
+            FrequencyEstimator::ZeroCrossing => write!(f, "Zero Crossings"),
+            FrequencyEstimator::Pll => write!(f, "Phase Locked Loop"),
+        }
+    }
+}
+
+impl FrequencyEstimator {
+    pub const ALL: [FrequencyEstimator; 5] = [
+        FrequencyEstimator::Quadratic,
+        FrequencyEstimator::Gaussian,
+        FrequencyEstimator::Jacobsen,
+        FrequencyEstimator::ZeroCrossing,
+        FrequencyEstimator::Pll,
+    ];
+}
+
+/// What an estimator measures of the lines around a peak, from the lines relative to the peak
+/// line. Lines that give no estimate count as a sine on the peak line.
+fn estimate(estimator: FrequencyEstimator, previous: f64, next: f64) -> f64 {
+    let parabola = |[previous, centre, next]: [f64; 3]| {
+        0.5 * (previous - next) / (previous - 2.0 * centre + next)
+    };
+
+    let estimate = match estimator {
+        FrequencyEstimator::Quadratic => parabola([previous.abs(), 1.0, next.abs()]),
+        FrequencyEstimator::Gaussian => parabola([previous.abs().ln(), 0.0, next.abs().ln()]),
+        // Jacobsen's ratio, which also tunes and backs up the estimators in the time domain
+        FrequencyEstimator::Jacobsen
+        | FrequencyEstimator::ZeroCrossing
+        | FrequencyEstimator::Pll => (next - previous) / (previous + 2.0 + next),
+    };
+
+    if estimate.is_finite() {
+        estimate
+    } else {
+        0.0
+    }
+}
+
+/// Offset of a sine from the peak line, found by matching the estimate of the lines to the one
+/// of the spectrum of the window, which makes every estimator exact for every window.
+fn offset(measured: f64, window: FftWindow, estimator: FrequencyEstimator) -> f64 {
+    let model = |offset: f64| {
+        let centre = window.kernel(offset);
+
+        estimate(
+            estimator,
+            window.kernel(offset + 1.0) / centre,
+            window.kernel(offset - 1.0) / centre,
+        )
+    };
+
+    // the estimate grows with the offset within half a line of the peak line
+    let (mut low, mut high) = (-0.5, 0.5);
+    for _ in 0..SEARCH_STEPS {
+        let middle = (low + high) / 2.0;
+
+        if model(middle) < measured {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    (low + high) / 2.0
+}
+
+/// Fractional line of the strongest line of a windowed spectrum, interpolated between its
+/// neighbours. The dc line is skipped, it is never the fundamental.
+pub fn interpolate(
+    spectrum: &[Complex<f32>],
+    window: FftWindow,
+    estimator: FrequencyEstimator,
+) -> Option<f64> {
+    let lines = spectrum.len() / 2;
+
+    if lines < 3 {
+        return None;
+    }
+
+    let (peak, _) = spectrum[1..lines - 1]
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.norm_sqr().total_cmp(&b.norm_sqr()))?;
+    let peak = peak + 1;
+
+    let neighbours = [peak - 1, peak, peak + 1];
+    let values =
+        neighbours.map(|line| Complex::new(spectrum[line].re as f64, spectrum[line].im as f64));
+
+    let line = |[previous, centre, next]: [Complex<f64>; 3]| {
+        // the phase of the lines turns by half a turn from line to line
+        let relative = |value: Complex<f64>| -(value / centre).re;
+
+        let measured = estimate(estimator, relative(previous), relative(next));
+
+        peak as f64 + offset(measured, window, estimator).clamp(-1.0, 1.0)
+    };
+
+    let mut fractional_line = line(values);
+
+    // the sine of a real signal has an image at the negative frequency, the leakage of the image
+    // into the lines is taken out as far as it is known from the last estimate
+    for _ in 0..IMAGE_STEPS {
+        let amplitude = values[1] / window.transform(peak as f64 - fractional_line, spectrum.len());
+
+        let image = neighbours.map(|neighbour| {
+            amplitude.conj() * window.transform(neighbour as f64 + fractional_line, spectrum.len())
+        });
+
+        fractional_line = line([0, 1, 2].map(|index| values[index] - image[index]));
+    }
+
+    Some(fractional_line)
+}
+
+/// Removes the dc component of the signal and filters it with two low passes tuned to the
+/// fundamental, which keeps its period but suppresses harmonics and noise.
+struct Conditioner {
+    sample_rate: f64,
+
+    mean: f64,
+    low_passes: [f64; 2],
+    // smoothing of the low passes per sample and the frequency they are tuned to
+    smoothing: f64,
+    cutoff: Option<f64>,
+    envelope: f64,
+}
+
+impl Conditioner {
+    fn new(sample_rate: SampleRate) -> Self {
+        Self {
+            sample_rate: sample_rate as f64,
+            mean: 0.0,
+            low_passes: [0.0; 2],
+            smoothing: 1.0,
+            cutoff: None,
+            envelope: 0.0,
+        }
+    }
+
+    fn tune(&mut self, fundamental: f64) {
+        let retune = self
+            .cutoff
+            .is_none_or(|cutoff| (fundamental - cutoff).abs() > cutoff * RETUNE_THRESHOLD);
+
+        if retune && fundamental > 0.0 {
+            self.cutoff = Some(fundamental);
+            self.smoothing = 1.0 - (-TAU * fundamental / self.sample_rate).exp();
+        }
+    }
+
+    fn push(&mut self, sample: f64) -> f64 {
+        self.mean += (sample - self.mean) / (DC_TIME_CONSTANT * self.sample_rate).max(1.0);
+
+        let mut value = sample - self.mean;
+        for low_pass in &mut self.low_passes {
+            *low_pass += (value - *low_pass) * self.smoothing;
+            value = *low_pass;
+        }
+
+        let decay = (-1.0 / (ENVELOPE_TIME_CONSTANT * self.sample_rate)).exp();
+        self.envelope = (self.envelope * decay).max(value.abs());
+
+        value
+    }
+}
+
+/// Counts the rising zero crossings of the conditioned signal.
+#[derive(Default)]
+struct ZeroCrossings {
+    detector: ZeroCrossingDetector,
+    sample: u64,
+    // first and last crossing of the interval, in samples
+    first: Option<f64>,
+    last: f64,
+    periods: usize,
+}
+
+impl ZeroCrossings {
+    fn push(&mut self, value: f64, envelope: f64) {
+        if let Some(fraction) = self.detector.push(value, envelope * HYSTERESIS) {
+            let crossing = self.sample as f64 - 1.0 + fraction;
+
+            match self.first {
+                Some(_) => self.periods += 1,
+                None => self.first = Some(crossing),
+            }
+
+            self.last = crossing;
+        }
+
+        self.sample += 1;
+    }
+
+    /// Frequency over the whole periods since the last call, the last crossing starts the next
+    /// interval.
+    fn take(&mut self, sample_rate: f64) -> Option<f64> {
+        let first = self.first?;
+        let periods = self.periods;
+
+        self.first = Some(self.last);
+        self.periods = 0;
+
+        (periods > 0).then(|| periods as f64 / (self.last - first) * sample_rate)
+    }
+}
+
+/// Second order phase locked loop, following the phase of the conditioned signal.
+struct PhaseLockedLoop {
+    sample_period: f64,
+
+    // radians
+    phase: f64,
+    // radians per second, set by `seed` and corrected by the loop filter
+    centre: f64,
+    integrator: f64,
+    // phase advanced since the last call of `take` and the samples it took
+    advance: f64,
+    samples: u64,
+}
+
+impl PhaseLockedLoop {
+    fn new(sample_rate: SampleRate) -> Self {
+        Self {
+            sample_period: 1.0 / sample_rate as f64,
+            phase: 0.0,
+            centre: 0.0,
+            integrator: 0.0,
+            advance: 0.0,
+            samples: 0,
+        }
+    }
+
+    fn is_seeded(&self) -> bool {
+        self.centre > 0.0
+    }
+
+    /// Starts the oscillator at a frequency in hertz, keeping its phase.
+    fn seed(&mut self, frequency: f64) {
+        self.centre = TAU * frequency;
+        self.integrator = 0.0;
+        self.advance = 0.0;
+        self.samples = 0;
+    }
+
+    fn push(&mut self, value: f64, envelope: f64) {
+        if !self.is_seeded() || envelope <= 0.0 {
+            return;
+        }
+
+        let natural_frequency = TAU * PLL_BANDWIDTH;
+        let proportional = 2.0 * PLL_DAMPING * natural_frequency / PLL_DETECTOR_GAIN;
+        let integral = natural_frequency * natural_frequency / PLL_DETECTOR_GAIN;
+
+        // the signal follows a sine, the oscillator a cosine, so they are locked in quadrature
+        let error = value / envelope * self.phase.cos();
+
+        self.integrator += integral * error * self.sample_period;
+
+        let step = (self.centre + proportional * error + self.integrator) * self.sample_period;
+
+        self.phase = (self.phase + step) % TAU;
+        self.advance += step;
+        self.samples += 1;
+    }
+
+    /// Mean frequency since the last call.
+    fn take(&mut self) -> Option<f64> {
+        let frequency = (self.samples > 0)
+            .then(|| self.advance / (self.samples as f64 * self.sample_period) / TAU);
+
+        self.advance = 0.0;
+        self.samples = 0;
+
+        frequency
+    }
+}
+
+struct FrequencyEstimationRunner {
+    samples: NodeRunnerInputPort<f32>,
+    spectrum: NodeRunnerInputPort<Vec<Complex<f32>>>,
+    frequency: NodeRunnerOutputPort<f64>,
+
+    estimator: NodeRunnerInputPort<FrequencyEstimator>,
+    window: NodeRunnerInputPort<FftWindow>,
+    sample_rate: NodeRunnerInputPort<SampleRate>,
+}
+
+impl NodeRunner for FrequencyEstimationRunner {
+    fn run(self: Box<Self>) {
+        let mut estimator = self.estimator.recv();
+        let mut window = self.window.recv();
+        let mut sample_rate = self.sample_rate.recv();
+
+        let mut conditioner = Conditioner::new(sample_rate);
+        let mut zero_crossings = ZeroCrossings::default();
+        let mut pll = PhaseLockedLoop::new(sample_rate);
+
+        loop {
+            receive! {
+                (self.samples): sample => {
+                    let value = conditioner.push(sample as f64);
+
+                    match estimator {
+                        FrequencyEstimator::ZeroCrossing => {
+                            zero_crossings.push(value, conditioner.envelope);
+                        }
+                        FrequencyEstimator::Pll => pll.push(value, conditioner.envelope),
+                        FrequencyEstimator::Quadratic
+                        | FrequencyEstimator::Gaussian
+                        | FrequencyEstimator::Jacobsen => {}
+                    }
+                },
+                // every spectrum asks for the frequency since the last one
+                (self.spectrum): spectrum => {
+                    let line_width = sample_rate as f64 / spectrum.len().max(1) as f64;
+
+                    // the interpolated spectrum tunes the filters and backs up the other
+                    // estimators until they measured their first value
+                    let interpolated = interpolate(&spectrum, window, estimator)
+                        .map(|line| line * line_width);
+
+                    let Some(interpolated) = interpolated else {
+                        continue;
+                    };
+
+                    conditioner.tune(interpolated);
+
+                    let frequency = match estimator {
+                        FrequencyEstimator::ZeroCrossing => zero_crossings.take(sample_rate as f64),
+                        FrequencyEstimator::Pll => {
+                            let frequency = pll.take();
+
+                            // out of lock by more than a line, like after a jump of the frequency
+                            if frequency.is_none_or(|frequency| {
+                                (frequency - interpolated).abs() > line_width
+                            }) {
+                                pll.seed(interpolated);
+                                None
+                            } else {
+                                frequency
+                            }
+                        }
+                        FrequencyEstimator::Quadratic
+                        | FrequencyEstimator::Gaussian
+                        | FrequencyEstimator::Jacobsen => None,
+                    };
+
+                    self.frequency.send(&frequency.unwrap_or(interpolated));
+                },
+                (self.estimator): new_estimator => {
+                    estimator = new_estimator;
+
+                    zero_crossings = ZeroCrossings::default();
+                    pll = PhaseLockedLoop::new(sample_rate);
+                },
+                (self.window): new_window => {
+                    window = new_window;
+                },
+                (self.sample_rate): new_sample_rate => {
+                    sample_rate = new_sample_rate;
+
+                    conditioner = Conditioner::new(sample_rate);
+                    zero_crossings = ZeroCrossings::default();
+                    pll = PhaseLockedLoop::new(sample_rate);
+                },
+            };
+        }
+    }
+}
+
+/// Measures the frequency of the fundamental with the selected estimator, once per spectrum.
+pub struct FrequencyEstimation {
+    pub samples: NodeConfigInputPort<f32>,
+    pub spectrum: NodeConfigInputPort<Vec<Complex<f32>>>,
+    pub frequency: NodeConfigOutputPort<f64>,
+
+    pub estimator: NodeConfigInputPort<FrequencyEstimator>,
+    pub window: NodeConfigInputPort<FftWindow>,
+    pub sample_rate: NodeConfigInputPort<SampleRate>,
+}
+
+impl FrequencyEstimation {
+    pub fn new() -> Self {
+        Self {
+            samples: NodeConfigInputPort::new(),
+            spectrum: NodeConfigInputPort::new(),
+            frequency: NodeConfigOutputPort::new(),
+
+            estimator: NodeConfigInputPort::new(),
+            window: NodeConfigInputPort::new(),
+            sample_rate: NodeConfigInputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for FrequencyEstimation {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(FrequencyEstimationRunner {
+            samples: self.samples.into(),
+            spectrum: self.spectrum.into(),
+            frequency: self.frequency.into(),
+
+            estimator: self.estimator.into(),
+            window: self.window.into(),
+            sample_rate: self.sample_rate.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::FftPlanner;
+
+    const SAMPLE_RATE: f64 = 3125.0;
+    const FFT_SIZE: usize = 2048;
+
+    fn spectrum(window: FftWindow, frequency: f64) -> Vec<Complex<f32>> {
+        let mut spectrum = window
+            .weights(FFT_SIZE)
+            .into_iter()
+            .enumerate()
+            .map(|(sample, weight)| {
+                let value = 325.0 * (TAU * frequency * sample as f64 / SAMPLE_RATE + 0.3).sin();
+
+                Complex::new(value as f32 * weight, 0.0)
+            })
+            .collect::<Vec<_>>();
+
+        FftPlanner::new()
+            .plan_fft_forward(FFT_SIZE)
+            .process(&mut spectrum);
+
+        spectrum
+    }
+
+    #[test]
+    fn interpolates_between_lines() {
+        let estimators = [
+            FrequencyEstimator::Quadratic,
+            FrequencyEstimator::Gaussian,
+            FrequencyEstimator::Jacobsen,
+        ];
+
+        for window in FftWindow::ALL {
+            for estimator in estimators {
+                for frequency in [50.003, 50.021, 50.037, 50.058, 50.076, 50.099] {
+                    let line =
+                        interpolate(&spectrum(window, frequency), window, estimator).unwrap();
+                    let estimate = line * SAMPLE_RATE / FFT_SIZE as f64;
+
+                    assert!(
+                        (estimate - frequency).abs() < 0.01,
+                        "{} with {} window: {} Hz instead of {} Hz",
+                        estimator,
+                        window,
+                        estimate,
+                        frequency
+                    );
+                }
+            }
+        }
+    }
+
+    /// Frequencies the zero crossings and the phase locked loop measure over the last of ten
+    /// seconds of a sine with a dc offset and a 3rd harmonic, tuned by the spectrum like in the
+    /// pipeline.
+    fn measure_in_time_domain(frequency: f64) -> [f64; 2] {
+        let sample_rate = SAMPLE_RATE as SampleRate;
+
+        let mut conditioner = Conditioner::new(sample_rate);
+        let mut zero_crossings = ZeroCrossings::default();
+        let mut pll = PhaseLockedLoop::new(sample_rate);
+
+        let window = FftWindow::Hann;
+        let line = interpolate(
+            &spectrum(window, frequency),
+            window,
+            FrequencyEstimator::Jacobsen,
+        )
+        .unwrap();
+        let interpolated = line * SAMPLE_RATE / FFT_SIZE as f64;
+
+        conditioner.tune(interpolated);
+        pll.seed(interpolated);
+
+        let mut measured = [None; 2];
+
+        for second in 0..10 {
+            for sample in second * SAMPLE_RATE as u64..(second + 1) * SAMPLE_RATE as u64 {
+                let time = sample as f64 / SAMPLE_RATE;
+                let value = 2.0
+                    + 325.0 * (TAU * frequency * time).sin()
+                    + 30.0 * (TAU * 3.0 * frequency * time + 0.5).sin();
+
+                let value = conditioner.push(value);
+
+                zero_crossings.push(value, conditioner.envelope);
+                pll.push(value, conditioner.envelope);
+            }
+
+            measured = [zero_crossings.take(SAMPLE_RATE), pll.take()];
+        }
+
+        measured.map(Option::unwrap)
+    }
+
+    #[test]
+    fn measures_in_the_time_domain() {
+        for frequency in [49.73, 50.0, 50.037, 60.21] {
+            let estimators = [FrequencyEstimator::ZeroCrossing, FrequencyEstimator::Pll];
+
+            for (estimator, estimate) in estimators.iter().zip(measure_in_time_domain(frequency)) {
+                assert!(
+                    (estimate - frequency).abs() < 0.01,
+                    "{}: {} Hz instead of {} Hz",
+                    estimator,
+                    estimate,
+                    frequency
+                );
+            }
+        }
+    }
+}
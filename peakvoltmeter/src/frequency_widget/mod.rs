@@ -1,8 +1,13 @@
 mod chart;
+mod estimator;
 
 use crate::{
     application::{calculate_precision, Precision},
-    settings::{ChartSize, FftSize, RefreshPeriod, SampleRate},
+    channel::{channel_color, channel_name, channel_views, ChannelLayout},
+    cycle_measurement::{CycleValues, MeasurementMode},
+    harmonics::FftWindow,
+    settings::{Channel, ChartSize, RefreshPeriod, SampleRate},
+    streams::Publisher,
     DARK_GRAY,
 };
 use chart::Chart;
@@ -11,47 +16,72 @@ use core::f64;
 use eframe::egui::Frame;
 use egui::{Align, Color32, Layout, RichText, Rounding, Vec2b};
 use egui_plot::{CoordinatesFormatter, Line, Plot, PlotPoints};
+use estimator::FrequencyEstimation;
+pub use estimator::{interpolate, FrequencyEstimator};
+use rustfft::num_complex::Complex;
 use std::sync::{Arc, RwLock};
 
 pub struct FrequencyWidgetInputPorts {
-    pub fft_input: NodeConfigInputPort<Vec<f64>>,
+    // calibrated samples and the windowed spectrum of the harmonics, which sets the refresh rate
+    pub samples: NodeConfigInputPort<f32>,
+    pub spectrum: NodeConfigInputPort<Vec<Complex<f32>>>,
+    // values of the whole cycles, shown instead of the estimated frequency in the cycles mode
+    pub cycle_values: NodeConfigInputPort<Option<CycleValues>>,
+    pub mode: NodeConfigInputPort<MeasurementMode>,
+    pub estimator: NodeConfigInputPort<FrequencyEstimator>,
+    pub window: NodeConfigInputPort<FftWindow>,
     pub chart_size: NodeConfigInputPort<ChartSize>,
     pub sample_rate: NodeConfigInputPort<SampleRate>,
-    pub fft_size: NodeConfigInputPort<FftSize>,
     pub refresh_period: NodeConfigInputPort<RefreshPeriod>,
 }
 
 pub fn frequency_widget(
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 ) -> Pipeline<FrequencyWidgetInputPorts, ()> {
-    let chart = Chart::new(data);
+    let estimation = FrequencyEstimation::new();
+
+    let chart = Chart::new(data, publisher);
+
+    estimation.frequency.connect(&chart.frequency);
 
     let input_ports = FrequencyWidgetInputPorts {
-        fft_input: chart.fft_input.clone(),
+        samples: estimation.samples.clone(),
+        spectrum: estimation.spectrum.clone(),
+        cycle_values: chart.cycle_values.clone(),
+        mode: chart.mode.clone(),
+        estimator: estimation.estimator.clone(),
+        window: estimation.window.clone(),
         chart_size: chart.chart_size.clone(),
-        sample_rate: chart.sample_rate.clone(),
-        fft_size: chart.fft_size.clone(),
+        sample_rate: estimation.sample_rate.clone(),
         refresh_period: chart.refresh_period.clone(),
     };
 
-    Pipeline::new(vec![Box::new(chart)], input_ports, ())
+    Pipeline::new(vec![Box::new(estimation), Box::new(chart)], input_ports, ())
 }
 
 pub struct FrequencyWidget {
-    data: Arc<RwLock<Vec<[f64; 2]>>>,
+    // one per channel
+    data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>,
 
     prev_chart_size: f64,
 }
 
 impl FrequencyWidget {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>) -> Self {
         Self {
             data,
             prev_chart_size: f64::NEG_INFINITY,
         }
     }
 
-    pub fn ui(&mut self, ui: &mut egui::Ui, chart_size: ChartSize, precision: Precision) {
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        layout: ChannelLayout,
+        chart_size: ChartSize,
+        precision: Precision,
+    ) {
         let frame = Frame::default()
             .inner_margin(10.0)
             .fill(DARK_GRAY)
@@ -65,86 +95,101 @@ impl FrequencyWidget {
 
             ui.label(RichText::new("Frequency").size(16.0));
 
-            let last_value = self
-                .data
-                .read()
-                .unwrap()
-                .last()
-                .map(|v| v[1])
-                .unwrap_or(0.0);
-
-            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
-                ui.label(
-                    RichText::new(format!(
-                        "{:.precision$} Hz",
-                        last_value,
-                        precision = precision
-                    ))
-                    .size(30.0)
-                    .strong(),
-                );
-            });
-
             let chart_size = chart_size as f64;
 
-            let coordinates_formatter = CoordinatesFormatter::new(|plot_point, _| {
-                let x = plot_point.x;
-                let y = plot_point.y;
+            // We need to check if the chart size has changed to reset the plots, otherwise the
+            // plots will not update the chart size.
+            let reset = (self.prev_chart_size - chart_size).abs() > f64::EPSILON;
+            self.prev_chart_size = chart_size;
+
+            channel_views(ui, layout, self.data.len(), |ui, channels| {
+                for channel in channels.clone() {
+                    let last_value = self.data[channel]
+                        .read()
+                        .unwrap()
+                        .last()
+                        .map(|v| v[1])
+                        .unwrap_or(0.0);
+
+                    ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                        ui.label(
+                            RichText::new(format!(
+                                "{:.precision$} Hz",
+                                last_value,
+                                precision = precision
+                            ))
+                            .size(30.0)
+                            .strong(),
+                        );
+
+                        if self.data.len() > 1 {
+                            ui.label(
+                                RichText::new(channel_name(channel))
+                                    .size(16.0)
+                                    .color(channel_color(channel)),
+                            );
+                        }
+                    });
+                }
+
+                let coordinates_formatter = CoordinatesFormatter::new(|plot_point, _| {
+                    let x = plot_point.x;
+                    let y = plot_point.y;
 
-                format!(
-                    "x = {:.precision$} s\ny = {:.precision$} Hz",
-                    x,
-                    y,
-                    precision = precision
-                )
-            });
-
-            let mut plot = Plot::new("Frequency Widget")
-                .auto_bounds(Vec2b::new(false, true))
-                .y_axis_label("Frequency")
-                .x_axis_label("Time")
-                .allow_boxed_zoom(false)
-                .allow_drag(false)
-                .allow_zoom(false)
-                .allow_scroll(false)
-                .label_formatter(|_, _| "".to_owned())
-                .coordinates_formatter(egui_plot::Corner::LeftTop, coordinates_formatter)
-                .x_axis_formatter(|grid_mark, range| {
                     format!(
-                        "{:.precision$} s",
-                        grid_mark.value,
-                        precision = calculate_precision(range)
-                    )
-                })
-                .y_axis_formatter(|grid_mark, range| {
-                    format!(
-                        "{:.precision$} Hz",
-                        grid_mark.value,
-                        precision = calculate_precision(range)
+                        "x = {:.precision$} s\ny = {:.precision$} Hz",
+                        x,
+                        y,
+                        precision = precision
                     )
-                })
-                .include_y(0.0)
-                .include_x(0.0)
-                .include_x(-chart_size);
-
-            // We need to check if the chart size has changed to reset the plot, otherwise the
-            // plot will not update the chart size.
-            if (self.prev_chart_size - chart_size).abs() > f64::EPSILON {
-                plot = plot.reset();
-                self.prev_chart_size = chart_size;
-            }
-
-            plot.show(ui, |plot_ui| {
-                plot_ui.line(self.signal());
+                });
+
+                let mut plot = Plot::new(("Frequency Widget", channels.start))
+                    .auto_bounds(Vec2b::new(false, true))
+                    .y_axis_label("Frequency")
+                    .x_axis_label("Time")
+                    .allow_boxed_zoom(false)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .allow_scroll(false)
+                    .label_formatter(|_, _| "".to_owned())
+                    .coordinates_formatter(egui_plot::Corner::LeftTop, coordinates_formatter)
+                    .x_axis_formatter(|grid_mark, range| {
+                        format!(
+                            "{:.precision$} s",
+                            grid_mark.value,
+                            precision = calculate_precision(range)
+                        )
+                    })
+                    .y_axis_formatter(|grid_mark, range| {
+                        format!(
+                            "{:.precision$} Hz",
+                            grid_mark.value,
+                            precision = calculate_precision(range)
+                        )
+                    })
+                    .include_y(0.0)
+                    .include_x(0.0)
+                    .include_x(-chart_size);
+
+                if reset {
+                    plot = plot.reset();
+                }
+
+                plot.show(ui, |plot_ui| {
+                    for channel in channels {
+                        plot_ui.line(self.signal(channel));
+                    }
+                });
             });
         });
     }
 
-    fn signal(&self) -> Line {
-        let plot_points = PlotPoints::from_iter(self.data.read().unwrap().clone());
+    fn signal(&self, channel: Channel) -> Line {
+        let plot_points = PlotPoints::from_iter(self.data[channel].read().unwrap().clone());
 
         Line::new(plot_points)
-            .color(Color32::LIGHT_BLUE)
-            .name("Signal")
+            .color(channel_color(channel))
+            .name(channel_name(channel))
     }
 }
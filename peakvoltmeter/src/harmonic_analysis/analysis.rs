@@ -0,0 +1,271 @@
+use crate::{
+    harmonics::{line_rms, FftWindow},
+    measurements::HARMONICS,
+    settings::{RatedVoltage, SampleRate},
+};
+use conductor::prelude::*;
+use rustfft::num_complex::Complex;
+use std::sync::{Arc, RwLock};
+
+// IEC 61000-4-7 groups the lines within 5 Hz of a harmonic of 50 Hz into its subgroup
+const SUBGROUP_WIDTH: f64 = 0.1;
+// the lowest lines hold the leakage of the dc component
+const FIRST_FUNDAMENTAL_LINE: usize = 2;
+
+#[derive(Clone)]
+pub struct HarmonicAnalysis {
+    // hertz
+    pub fundamental: f64,
+    // rms volts of the subgroups of the fundamental and its harmonics, up to the 50th or the
+    // nyquist frequency
+    pub harmonics: Vec<f64>,
+    // percent of the fundamental
+    pub thd: f64,
+    // percent of the rated voltage
+    pub tdd: f64,
+}
+
+impl HarmonicAnalysis {
+    /// Analyses the windowed spectrum of a channel, `None` without a fundamental.
+    fn from_spectrum(
+        spectrum: &[Complex<f32>],
+        window: FftWindow,
+        sample_rate: SampleRate,
+        rated_voltage: RatedVoltage,
+    ) -> Option<Self> {
+        let fft_size = spectrum.len();
+        let lines = fft_size / 2;
+
+        if lines <= FIRST_FUNDAMENTAL_LINE + 1 {
+            return None;
+        }
+
+        let power = spectrum[..lines]
+            .iter()
+            .map(|value| value.norm_sqr() as f64)
+            .collect::<Vec<_>>();
+
+        let (peak, _) = power[FIRST_FUNDAMENTAL_LINE..lines - 1]
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+        let peak = peak + FIRST_FUNDAMENTAL_LINE;
+
+        // the lines a sine leaks into, at least the neighbouring ones
+        let spread = window.spread().max(1);
+        let lobe = peak.saturating_sub(spread)..=(peak + spread).min(lines - 1);
+
+        let peak_power = power[lobe.clone()].iter().sum::<f64>();
+
+        if peak_power == 0.0 {
+            return None;
+        }
+
+        // the centre of the power of the peak lies between the lines
+        let fundamental_line = lobe.map(|line| line as f64 * power[line]).sum::<f64>() / peak_power;
+
+        let line_width = sample_rate as f64 / fft_size as f64;
+
+        // the subgroups of neighbouring harmonics must not share lines
+        let max_half_width = ((fundamental_line / 2.0).floor() as usize).checked_sub(1)?;
+
+        // like the 5 Hz lines of IEC 61000-4-7, at least the lines a sine leaks into
+        let half_width = ((fundamental_line * SUBGROUP_WIDTH).round() as usize)
+            .max(spread)
+            .min(max_half_width);
+
+        let harmonics = (1..=HARMONICS)
+            .map_while(|harmonic| {
+                let centre = (harmonic as f64 * fundamental_line).round() as usize;
+
+                let subgroup =
+                    power.get(centre.saturating_sub(half_width)..=centre + half_width)?;
+
+                // the power of a subgroup is corrected by the bandwidth of the window
+                let magnitude = (subgroup.iter().sum::<f64>() / window.enbw()).sqrt();
+
+                Some(line_rms(centre, magnitude, fft_size))
+            })
+            .collect::<Vec<_>>();
+
+        let (&fundamental, distortion) = harmonics.split_first()?;
+
+        let distortion = distortion
+            .iter()
+            .map(|harmonic| harmonic * harmonic)
+            .sum::<f64>()
+            .sqrt();
+
+        Some(Self {
+            fundamental: fundamental_line * line_width,
+            thd: if fundamental > 0.0 {
+                distortion / fundamental * 100.0
+            } else {
+                0.0
+            },
+            tdd: distortion / rated_voltage as f64 * 100.0,
+            harmonics,
+        })
+    }
+}
+
+struct HarmonicAnalyzerRunner {
+    data: Arc<RwLock<Option<HarmonicAnalysis>>>,
+
+    spectrum: NodeRunnerInputPort<Vec<Complex<f32>>>,
+
+    window: NodeRunnerInputPort<FftWindow>,
+    sample_rate: NodeRunnerInputPort<SampleRate>,
+    rated_voltage: NodeRunnerInputPort<RatedVoltage>,
+}
+
+impl NodeRunner for HarmonicAnalyzerRunner {
+    fn run(self: Box<Self>) {
+        let mut window = self.window.recv();
+        let mut sample_rate = self.sample_rate.recv();
+        let mut rated_voltage = self.rated_voltage.recv();
+
+        loop {
+            receive! {
+                (self.spectrum): spectrum => {
+                    *self.data.write().unwrap() =
+                        HarmonicAnalysis::from_spectrum(
+                            &spectrum,
+                            window,
+                            sample_rate,
+                            rated_voltage,
+                        );
+                },
+                (self.window): new_window => {
+                    window = new_window;
+                },
+                (self.sample_rate): new_sample_rate => {
+                    sample_rate = new_sample_rate;
+                },
+                (self.rated_voltage): new_rated_voltage => {
+                    rated_voltage = new_rated_voltage;
+                },
+            };
+        }
+    }
+}
+
+/// Measures the fundamental and the harmonic subgroups of a channel, with the total harmonic
+/// and the total demand distortion.
+pub struct HarmonicAnalyzer {
+    data: Arc<RwLock<Option<HarmonicAnalysis>>>,
+
+    pub spectrum: NodeConfigInputPort<Vec<Complex<f32>>>,
+
+    pub window: NodeConfigInputPort<FftWindow>,
+    pub sample_rate: NodeConfigInputPort<SampleRate>,
+    pub rated_voltage: NodeConfigInputPort<RatedVoltage>,
+}
+
+impl HarmonicAnalyzer {
+    pub fn new(data: Arc<RwLock<Option<HarmonicAnalysis>>>) -> Self {
+        Self {
+            data,
+
+            spectrum: NodeConfigInputPort::new(),
+
+            window: NodeConfigInputPort::new(),
+            sample_rate: NodeConfigInputPort::new(),
+            rated_voltage: NodeConfigInputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for HarmonicAnalyzer {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(HarmonicAnalyzerRunner {
+            data: self.data,
+
+            spectrum: self.spectrum.into(),
+
+            window: self.window.into(),
+            sample_rate: self.sample_rate.into(),
+            rated_voltage: self.rated_voltage.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::FftPlanner;
+    use std::f64::consts::{SQRT_2, TAU};
+
+    const SAMPLE_RATE: SampleRate = 3125.0;
+    const FFT_SIZE: usize = 2048;
+
+    /// Windowed spectrum of a 230 V fundamental with 5 % of 3rd harmonic.
+    fn spectrum(window: FftWindow, fundamental: f64) -> Vec<Complex<f32>> {
+        let mut spectrum = (0..FFT_SIZE)
+            .zip(window.weights(FFT_SIZE))
+            .map(|(sample, weight)| {
+                let phase = TAU * fundamental * sample as f64 / SAMPLE_RATE as f64;
+                let value = 230.0 * SQRT_2 * (phase.sin() + 0.05 * (3.0 * phase).sin());
+
+                Complex::new(value as f32 * weight, 0.0)
+            })
+            .collect::<Vec<_>>();
+
+        FftPlanner::new()
+            .plan_fft_forward(FFT_SIZE)
+            .process(&mut spectrum);
+
+        spectrum
+    }
+
+    #[test]
+    fn thd_of_a_known_harmonic() {
+        // the rectangular window leaks too far for a fundamental between the lines
+        for window in [
+            FftWindow::Hann,
+            FftWindow::BlackmanHarris,
+            FftWindow::FlatTop,
+        ] {
+            let analysis = HarmonicAnalysis::from_spectrum(
+                &spectrum(window, 50.02),
+                window,
+                SAMPLE_RATE,
+                230.0,
+            )
+            .unwrap();
+
+            assert!(
+                (analysis.thd - 5.0).abs() < 0.05,
+                "{}: {} % THD",
+                window,
+                analysis.thd
+            );
+            assert!(
+                (analysis.harmonics[0] - 230.0).abs() < 1.0,
+                "{}: {} V fundamental",
+                window,
+                analysis.harmonics[0]
+            );
+            assert!((analysis.harmonics[2] - 11.5).abs() < 0.1);
+            assert!((analysis.tdd - 5.0).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn subgroups_do_not_overlap() {
+        // on the 8th line the lines the flat top window leaks into reach the 2nd harmonic
+        let window = FftWindow::FlatTop;
+        let fundamental = 8.0 * SAMPLE_RATE as f64 / FFT_SIZE as f64;
+
+        let analysis = HarmonicAnalysis::from_spectrum(
+            &spectrum(window, fundamental),
+            window,
+            SAMPLE_RATE,
+            230.0,
+        )
+        .unwrap();
+
+        // no line of the fundamental counts to the subgroup of a harmonic
+        assert!((analysis.thd - 5.0).abs() < 0.05, "{} % THD", analysis.thd);
+    }
+}
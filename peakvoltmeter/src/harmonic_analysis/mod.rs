@@ -0,0 +1,147 @@
+mod analysis;
+
+use crate::{
+    application::{Precision, VoltageUnit},
+    channel::{channel_color, channel_name},
+    harmonics::FftWindow,
+    measurements::HARMONICS,
+    settings::{RatedVoltage, SampleRate},
+};
+pub use analysis::HarmonicAnalysis;
+use analysis::HarmonicAnalyzer;
+use conductor::{core::pipeline::Pipeline, prelude::*};
+use egui::RichText;
+use rustfft::num_complex::Complex;
+use std::sync::{Arc, RwLock};
+
+pub struct HarmonicAnalysisInputPorts {
+    // windowed spectrum of the channel
+    pub spectrum: NodeConfigInputPort<Vec<Complex<f32>>>,
+    pub window: NodeConfigInputPort<FftWindow>,
+    pub sample_rate: NodeConfigInputPort<SampleRate>,
+    pub rated_voltage: NodeConfigInputPort<RatedVoltage>,
+}
+
+pub fn harmonic_analysis(
+    data: Arc<RwLock<Option<HarmonicAnalysis>>>,
+) -> Pipeline<HarmonicAnalysisInputPorts, ()> {
+    let analyzer = HarmonicAnalyzer::new(data);
+
+    let input_ports = HarmonicAnalysisInputPorts {
+        spectrum: analyzer.spectrum.clone(),
+        window: analyzer.window.clone(),
+        sample_rate: analyzer.sample_rate.clone(),
+        rated_voltage: analyzer.rated_voltage.clone(),
+    };
+
+    Pipeline::new(vec![Box::new(analyzer)], input_ports, ())
+}
+
+/// Fundamental, distortion and harmonics of every channel side by side.
+pub struct HarmonicTable {
+    // one per channel
+    data: Vec<Arc<RwLock<Option<HarmonicAnalysis>>>>,
+}
+
+impl HarmonicTable {
+    pub fn new(data: Vec<Arc<RwLock<Option<HarmonicAnalysis>>>>) -> Self {
+        Self { data }
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui, unit: VoltageUnit, precision: Precision) {
+        ui.label(RichText::new("Harmonic Analysis").size(20.0).strong());
+
+        let analyses = self
+            .data
+            .iter()
+            .map(|data| data.read().unwrap().clone())
+            .collect::<Vec<_>>();
+
+        let percent = |value: f64| format!("{:.precision$} %", value, precision = precision);
+        // not measured, like harmonics beyond the nyquist frequency
+        let missing = || "–".to_string();
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            egui::Grid::new("Harmonic Table")
+                .num_columns(1 + 2 * analyses.len())
+                .striped(true)
+                .min_col_width(80.0)
+                .show(ui, |ui| {
+                    ui.label(RichText::new("Order").strong());
+                    for channel in 0..analyses.len() {
+                        let name = RichText::new(channel_name(channel))
+                            .color(channel_color(channel))
+                            .strong();
+
+                        ui.label(name.clone());
+                        ui.label(name);
+                    }
+                    ui.end_row();
+
+                    ui.label("Fundamental");
+                    for analysis in &analyses {
+                        ui.label(analysis.as_ref().map_or_else(missing, |analysis| {
+                            format!(
+                                "{:.precision$} Hz",
+                                analysis.fundamental,
+                                precision = precision
+                            )
+                        }));
+                        ui.label("");
+                    }
+                    ui.end_row();
+
+                    ui.label("THD");
+                    for analysis in &analyses {
+                        ui.label("");
+                        ui.label(
+                            analysis
+                                .as_ref()
+                                .map_or_else(missing, |analysis| percent(analysis.thd)),
+                        );
+                    }
+                    ui.end_row();
+
+                    ui.label("TDD");
+                    for analysis in &analyses {
+                        ui.label("");
+                        ui.label(
+                            analysis
+                                .as_ref()
+                                .map_or_else(missing, |analysis| percent(analysis.tdd)),
+                        );
+                    }
+                    ui.end_row();
+
+                    // rms and percent of the fundamental
+                    for harmonic in 0..HARMONICS {
+                        ui.label((harmonic + 1).to_string());
+                        for analysis in &analyses {
+                            let value = analysis.as_ref().and_then(|analysis| {
+                                Some((
+                                    *analysis.harmonics.get(harmonic)?,
+                                    *analysis.harmonics.first()?,
+                                ))
+                            });
+
+                            match value {
+                                Some((rms, fundamental)) => {
+                                    ui.label(unit.apply_unit_with_precision(rms, precision));
+                                    ui.label(if fundamental > 0.0 {
+                                        percent(rms / fundamental * 100.0)
+                                    } else {
+                                        missing()
+                                    });
+                                }
+                                None => {
+                                    ui.label(missing());
+                                    ui.label(missing());
+                                }
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+}
@@ -1,10 +1,14 @@
 use conductor::prelude::*;
 use std::sync::{Arc, RwLock};
 
-use crate::settings::{FftSize, SampleRate};
+use crate::{
+    settings::{FftSize, SampleRate},
+    streams::Publisher,
+};
 
 struct ChartRunner {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 
     input: NodeRunnerInputPort<Vec<f64>>,
 
@@ -24,6 +28,9 @@ impl NodeRunner for ChartRunner {
         loop {
             receive! {
                 (self.input): buffer => {
+                    self.publisher
+                        .publish(index_to_hz(1, fft_size, sample_rate), || buffer.clone());
+
                     *self.data.write().unwrap() = buffer
                         .into_iter()
                         .enumerate()
@@ -43,6 +50,7 @@ impl NodeRunner for ChartRunner {
 
 pub struct Chart {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 
     pub input: NodeConfigInputPort<Vec<f64>>,
 
@@ -51,9 +59,10 @@ pub struct Chart {
 }
 
 impl Chart {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>, publisher: Publisher) -> Self {
         Self {
             data,
+            publisher,
 
             input: NodeConfigInputPort::new(),
 
@@ -67,6 +76,7 @@ impl NodeConfig for Chart {
     fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
         Box::new(ChartRunner {
             data: self.data,
+            publisher: self.publisher,
 
             input: self.input.into(),
 
@@ -1,15 +1,110 @@
 mod chart;
+mod window;
 
 use crate::{
     application::{calculate_precision, Precision},
-    settings::{FftSize, RefreshPeriod, SampleRate},
+    channel::{channel_color, channel_name, channel_views, ChannelLayout},
+    harmonic_analysis::HarmonicAnalysis,
+    settings::{Channel, FftSize, RefreshPeriod, SampleRate},
+    streams::Publisher,
 };
 use chart::Chart;
 use conductor::{core::pipeline::Pipeline, prelude::*};
-use egui::{Color32, RichText, Vec2b};
-use egui_plot::{CoordinatesFormatter, Line, Plot, PlotPoints};
+use core::fmt;
+use egui::{RichText, Vec2b};
+use egui_plot::{CoordinatesFormatter, Legend, Line, Plot, PlotPoints};
 use rustfft::num_complex::Complex;
-use std::sync::{Arc, RwLock};
+use serde::{Deserialize, Serialize};
+use std::{
+    f64::consts::SQRT_2,
+    fmt::{Display, Formatter},
+    sync::{Arc, RwLock},
+};
+pub use window::FftWindow;
+use window::Windowing;
+
+// 1 µV in dBV
+const MICROVOLT_DBV: f64 = -120.0;
+// level of silent lines, a line of exactly 0 V would be -inf dBV
+const MIN_LEVEL_DBV: f64 = -200.0;
+
+/// Rms volts of a line of the spectrum of `fft_size` windowed samples. The window keeps the
+/// amplitude of a sine, which shows up with `a * fft_size / 2` in the positive frequencies, the dc
+/// component has no negative frequency to share its magnitude with.
+pub fn line_rms(line: usize, magnitude: f64, fft_size: usize) -> f64 {
+    let amplitude = magnitude / fft_size as f64;
+
+    if line == 0 {
+        amplitude
+    } else {
+        amplitude * 2.0 / SQRT_2
+    }
+}
+
+/// Scale of the y axis of the harmonics chart.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpectrumScale {
+    // dB relative to 1 V rms
+    Dbv,
+    // dB relative to 1 µV rms
+    DbMicrovolt,
+    // dB relative to the rms voltage of the fundamental
+    DbFundamental,
+    // rms volts
+    Volt,
+}
+
+impl Display for SpectrumScale {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SpectrumScale::Dbv => write!(f, "dBV"),
+            SpectrumScale::DbMicrovolt => write!(f, "dBµV"),
+            SpectrumScale::DbFundamental => write!(f, "dB relative to the Fundamental"),
+            SpectrumScale::Volt => write!(f, "Volt"),
+        }
+    }
+}
+
+impl SpectrumScale {
+    pub const ALL: [SpectrumScale; 4] = [
+        SpectrumScale::Dbv,
+        SpectrumScale::DbMicrovolt,
+        SpectrumScale::DbFundamental,
+        SpectrumScale::Volt,
+    ];
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            SpectrumScale::Dbv => "dBV",
+            SpectrumScale::DbMicrovolt => "dBµV",
+            SpectrumScale::DbFundamental => "dB",
+            SpectrumScale::Volt => "V",
+        }
+    }
+
+    /// Converts a level in dBV, `fundamental` is the rms voltage of the fundamental.
+    fn apply(&self, level: f64, fundamental: Option<f64>) -> Option<f64> {
+        match self {
+            SpectrumScale::Dbv => Some(level),
+            SpectrumScale::DbMicrovolt => Some(level - MICROVOLT_DBV),
+            SpectrumScale::DbFundamental => {
+                Some(level - (20.0 * fundamental?.log10()).max(MIN_LEVEL_DBV))
+            }
+            SpectrumScale::Volt => Some(10f64.powf(level / 20.0)),
+        }
+    }
+
+    // initial bounds of the y axis, the linear scale follows the spectrum
+    fn y_bounds(&self) -> Option<[f64; 2]> {
+        match self {
+            SpectrumScale::Dbv => Some([-140.0, 120.0]),
+            SpectrumScale::DbMicrovolt => Some([-20.0, 240.0]),
+            SpectrumScale::DbFundamental => Some([-200.0, 0.0]),
+            SpectrumScale::Volt => None,
+        }
+    }
+}
 
 pub struct HarmonicsInputPorts {
     pub data: NodeConfigInputPort<f32>,
@@ -19,14 +114,17 @@ pub struct HarmonicsInputPorts {
         NodeConfigInputPort<SampleRate>,
     ),
     pub refresh_period: NodeConfigInputPort<RefreshPeriod>,
+    pub window: NodeConfigInputPort<FftWindow>,
 }
 
 pub struct HarmonicsOutputPorts {
-    pub fft_output: NodeConfigOutputPort<Vec<f64>>,
+    // complex spectrum of the windowed signal, scaled like `line_rms`
+    pub spectrum: NodeConfigOutputPort<Vec<Complex<f32>>>,
 }
 
 pub fn harmonics(
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 ) -> Pipeline<HarmonicsInputPorts, HarmonicsOutputPorts> {
     let fft_buffer = Buffer::new(false);
 
@@ -36,27 +134,24 @@ pub fn harmonics(
 
     let refresh_period_downsampler = Downsample::new();
 
-    let hann_window = Window::new(WindowType::Hann);
+    let windowing = Windowing::new();
 
     let fft = FFT::new();
 
+    // the signal is calibrated, so the lines are given in dBV
     let lambda = Lambda::new(|fft: Vec<Complex<f32>>| {
         let length = fft.len();
 
-        let fft = fft
-            .into_iter()
-            .take(length / 2)
-            .map(|value| value.norm() as f64)
-            .collect::<Vec<_>>();
-
-        let max = fft.iter().cloned().fold(f64::MIN, f64::max);
-
         fft.into_iter()
-            .map(|value| 20.0 * (value / max).log10())
+            .take(length / 2)
+            .enumerate()
+            .map(|(line, value)| {
+                (20.0 * line_rms(line, value.norm() as f64, length).log10()).max(MIN_LEVEL_DBV)
+            })
             .collect()
     });
 
-    let chart = Chart::new(data);
+    let chart = Chart::new(data, publisher);
 
     refresh_factor
         .output
@@ -68,11 +163,9 @@ pub fn harmonics(
 
     fft_buffer.output.connect(&refresh_period_downsampler.input);
 
-    refresh_period_downsampler
-        .output
-        .connect(&hann_window.input);
+    refresh_period_downsampler.output.connect(&windowing.input);
 
-    hann_window.output.connect(&fft.input);
+    windowing.output.connect(&fft.input);
 
     fft.output.connect(&lambda.input);
 
@@ -83,10 +176,11 @@ pub fn harmonics(
         fft_size: (fft_buffer.size.clone(), chart.fft_size.clone()),
         sample_rate: (chart.sample_rate.clone(), refresh_factor.input2.clone()),
         refresh_period: refresh_factor.input1.clone(),
+        window: windowing.window.clone(),
     };
 
     let output_ports = HarmonicsOutputPorts {
-        fft_output: lambda.output.clone(),
+        spectrum: fft.output.clone(),
     };
 
     Pipeline::new(
@@ -95,7 +189,7 @@ pub fn harmonics(
             Box::new(refresh_factor),
             Box::new(refresh_factor_to_usize),
             Box::new(refresh_period_downsampler),
-            Box::new(hann_window),
+            Box::new(windowing),
             Box::new(fft),
             Box::new(lambda),
             Box::new(chart),
@@ -106,20 +200,36 @@ pub fn harmonics(
 }
 
 pub struct Harmonics {
-    data: Arc<RwLock<Vec<[f64; 2]>>>,
+    // one per channel, in dBV
+    data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>,
+    // reference of the levels relative to the fundamental
+    harmonic_analysis: Vec<Arc<RwLock<Option<HarmonicAnalysis>>>>,
 
     prev_x_bound: f64,
+    prev_scale: Option<SpectrumScale>,
 }
 
 impl Harmonics {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(
+        data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>,
+        harmonic_analysis: Vec<Arc<RwLock<Option<HarmonicAnalysis>>>>,
+    ) -> Self {
         Self {
             data,
+            harmonic_analysis,
             prev_x_bound: f64::NEG_INFINITY,
+            prev_scale: None,
         }
     }
 
-    pub fn ui(&mut self, ui: &mut egui::Ui, sample_rate: SampleRate, precision: Precision) {
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        layout: ChannelLayout,
+        sample_rate: SampleRate,
+        scale: SpectrumScale,
+        precision: Precision,
+    ) {
         let available_size = ui.available_size();
 
         ui.allocate_ui_with_layout(
@@ -132,66 +242,97 @@ impl Harmonics {
 
                 let x_bound = sample_rate as f64 / 2.0;
 
-                let coordinates_formatter = CoordinatesFormatter::new(|plot_point, _| {
-                    let x = plot_point.x;
-                    let y = plot_point.y;
+                // We need to check if the x bound or the scale has changed to reset the plots,
+                // otherwise the plots will not update their bounds.
+                let reset = (self.prev_x_bound - x_bound).abs() > f64::EPSILON
+                    || self.prev_scale != Some(scale);
+                self.prev_x_bound = x_bound;
+                self.prev_scale = Some(scale);
 
-                    format!(
-                        "x = {:.precision$} Hz\ny = {:.precision$} dBV",
-                        x,
-                        y,
-                        precision = precision
-                    )
-                });
+                let unit = scale.symbol();
+
+                channel_views(ui, layout, self.data.len(), |ui, channels| {
+                    let coordinates_formatter = CoordinatesFormatter::new(|plot_point, _| {
+                        let x = plot_point.x;
+                        let y = plot_point.y;
 
-                let mut plot = Plot::new("Harmonics")
-                    .auto_bounds(Vec2b::FALSE)
-                    .y_axis_label("Signal Strength (dBV)")
-                    .x_axis_label("Frequency (Hz)")
-                    .allow_boxed_zoom(false)
-                    .allow_drag(false)
-                    .allow_zoom(false)
-                    .allow_scroll(false)
-                    .label_formatter(|_, _| "".to_owned())
-                    .coordinates_formatter(egui_plot::Corner::LeftTop, coordinates_formatter)
-                    .x_axis_formatter(|grid_mark, range| {
-                        format!(
-                            "{:.precision$} Hz",
-                            grid_mark.value,
-                            precision = calculate_precision(range)
-                        )
-                    })
-                    .y_axis_formatter(|grid_mark, range| {
                         format!(
-                            "{:.precision$} dBV",
-                            grid_mark.value,
-                            precision = calculate_precision(range)
+                            "x = {:.precision$} Hz\ny = {:.precision$} {}",
+                            x,
+                            y,
+                            unit,
+                            precision = precision
                         )
-                    })
-                    .include_y(0.0)
-                    .include_y(-200)
-                    .include_x(0.0)
-                    .include_x(x_bound);
-
-                // We need to check if the x bound has changed to reset the plot, otherwise the
-                // plot will not update the x bound.
-                if (self.prev_x_bound - x_bound).abs() > f64::EPSILON {
-                    plot = plot.reset();
-                    self.prev_x_bound = x_bound;
-                }
-
-                plot.show(ui, |plot_ui| {
-                    plot_ui.line(self.signal());
+                    });
+
+                    let mut plot = Plot::new(("Harmonics", channels.start))
+                        .auto_bounds(Vec2b::new(false, scale.y_bounds().is_none()))
+                        .y_axis_label(format!("Signal Strength ({})", unit))
+                        .x_axis_label("Frequency (Hz)")
+                        .allow_boxed_zoom(false)
+                        .allow_drag(false)
+                        .allow_zoom(false)
+                        .allow_scroll(false)
+                        .label_formatter(|_, _| "".to_owned())
+                        .coordinates_formatter(egui_plot::Corner::LeftTop, coordinates_formatter)
+                        .x_axis_formatter(|grid_mark, range| {
+                            format!(
+                                "{:.precision$} Hz",
+                                grid_mark.value,
+                                precision = calculate_precision(range)
+                            )
+                        })
+                        .y_axis_formatter(|grid_mark, range| {
+                            format!(
+                                "{:.precision$} {}",
+                                grid_mark.value,
+                                unit,
+                                precision = calculate_precision(range)
+                            )
+                        })
+                        .include_x(0.0)
+                        .include_x(x_bound);
+
+                    plot = match scale.y_bounds() {
+                        Some([min, max]) => plot.include_y(min).include_y(max),
+                        None => plot.include_y(0.0),
+                    };
+
+                    if self.data.len() > 1 {
+                        plot = plot.legend(Legend::default());
+                    }
+
+                    if reset {
+                        plot = plot.reset();
+                    }
+
+                    plot.show(ui, |plot_ui| {
+                        for channel in channels {
+                            plot_ui.line(self.signal(channel, scale));
+                        }
+                    });
                 });
             },
         );
     }
 
-    fn signal(&self) -> Line {
-        let plot_points = PlotPoints::from_iter(self.data.read().unwrap().clone());
+    fn signal(&self, channel: Channel, scale: SpectrumScale) -> Line {
+        let fundamental = self.harmonic_analysis[channel]
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|analysis| analysis.harmonics.first().copied());
+
+        let plot_points = PlotPoints::from_iter(
+            self.data[channel]
+                .read()
+                .unwrap()
+                .iter()
+                .filter_map(|&[x, y]| Some([x, scale.apply(y, fundamental)?])),
+        );
 
         Line::new(plot_points)
-            .color(Color32::LIGHT_BLUE)
-            .name("Signal")
+            .color(channel_color(channel))
+            .name(channel_name(channel))
     }
 }
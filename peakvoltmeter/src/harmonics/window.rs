@@ -0,0 +1,244 @@
+use conductor::prelude::*;
+use core::fmt;
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::{
+    f64::consts::{PI, TAU},
+    fmt::{Display, Formatter},
+};
+
+// coefficients of the cosine terms, alternating in sign
+const RECTANGULAR: [f64; 1] = [1.0];
+const HANN: [f64; 2] = [0.5, 0.5];
+// 4 term Blackman-Harris, sidelobes below -92 dB
+const BLACKMAN_HARRIS: [f64; 4] = [0.35875, 0.48829, 0.14128, 0.01168];
+// flat top of the SR785, scalloping below 0.01 dB
+const FLAT_TOP: [f64; 5] = [
+    0.21557895,
+    0.41663158,
+    0.277263158,
+    0.083578947,
+    0.006947368,
+];
+
+/// Window applied to the samples of the harmonics before the FFT.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FftWindow {
+    // for signals sampled synchronously to their fundamental
+    Rectangular,
+    Hann,
+    // for low leakage
+    BlackmanHarris,
+    // for accurate amplitudes
+    FlatTop,
+}
+
+impl Display for FftWindow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FftWindow::Rectangular => write!(f, "Rectangular"),
+            FftWindow::Hann => write!(f, "Hann"),
+            FftWindow::BlackmanHarris => write!(f, "Blackman-Harris"),
+            FftWindow::FlatTop => write!(f, "Flat Top"),
+        }
+    }
+}
+
+impl FftWindow {
+    pub const ALL: [FftWindow; 4] = [
+        FftWindow::Rectangular,
+        FftWindow::Hann,
+        FftWindow::BlackmanHarris,
+        FftWindow::FlatTop,
+    ];
+
+    fn coefficients(&self) -> &'static [f64] {
+        match self {
+            FftWindow::Rectangular => &RECTANGULAR,
+            FftWindow::Hann => &HANN,
+            FftWindow::BlackmanHarris => &BLACKMAN_HARRIS,
+            FftWindow::FlatTop => &FLAT_TOP,
+        }
+    }
+
+    /// Mean of the window, the amplitude of a sine is scaled by it.
+    pub fn coherent_gain(&self) -> f64 {
+        self.coefficients()[0]
+    }
+
+    /// Equivalent noise bandwidth in lines, a sine spreads its power over it.
+    pub fn enbw(&self) -> f64 {
+        let coefficients = self.coefficients();
+
+        let power = coefficients[0].powi(2)
+            + coefficients[1..]
+                .iter()
+                .map(|coefficient| coefficient.powi(2) / 2.0)
+                .sum::<f64>();
+
+        power / self.coherent_gain().powi(2)
+    }
+
+    /// Lines on either side a sine on a line leaks into.
+    pub fn spread(&self) -> usize {
+        self.coefficients().len() - 1
+    }
+
+    /// Line `offset` lines away from a sine in the spectrum, relative to a line on the sine. The
+    /// phase common to all lines is left out, so is the curvature of small FFT sizes.
+    pub fn kernel(&self, offset: f64) -> f64 {
+        let sinc = |x: f64| {
+            if x == 0.0 {
+                1.0
+            } else {
+                (PI * x).sin() / (PI * x)
+            }
+        };
+
+        let kernel = self
+            .coefficients()
+            .iter()
+            .enumerate()
+            .map(|(term, coefficient)| {
+                if term == 0 {
+                    coefficient * sinc(offset)
+                } else {
+                    let term = term as f64;
+
+                    coefficient / 2.0 * (sinc(offset - term) + sinc(offset + term))
+                }
+            })
+            .sum::<f64>();
+
+        kernel / self.coherent_gain()
+    }
+
+    /// Line `offset` lines away from a complex sine of amplitude one in the spectrum of `size`
+    /// windowed samples, with its phase. Unlike `kernel` it is exact for every FFT size.
+    pub fn transform(&self, offset: f64, size: usize) -> Complex<f64> {
+        let size = size as f64;
+
+        // spectrum of the rectangular window
+        let dirichlet = |x: f64| {
+            let denominator = (PI * x / size).sin();
+            let magnitude = if denominator == 0.0 {
+                size
+            } else {
+                (PI * x).sin() / denominator
+            };
+
+            Complex::from_polar(magnitude, -PI * x * (size - 1.0) / size)
+        };
+
+        let transform = self
+            .coefficients()
+            .iter()
+            .enumerate()
+            .map(|(term, coefficient)| {
+                if term == 0 {
+                    dirichlet(offset) * coefficient
+                } else {
+                    let sign = if term % 2 == 0 { 1.0 } else { -1.0 };
+                    let term = term as f64;
+
+                    (dirichlet(offset - term) + dirichlet(offset + term))
+                        * (sign * coefficient / 2.0)
+                }
+            })
+            .sum::<Complex<f64>>();
+
+        transform / self.coherent_gain()
+    }
+
+    /// Periodic window of `size` samples, divided by its coherent gain so a sine keeps its
+    /// amplitude in the spectrum whichever window is chosen.
+    pub fn weights(&self, size: usize) -> Vec<f32> {
+        let coefficients = self.coefficients();
+
+        (0..size)
+            .map(|sample| {
+                let phase = TAU * sample as f64 / size as f64;
+
+                let weight = coefficients
+                    .iter()
+                    .enumerate()
+                    .map(|(term, coefficient)| {
+                        let sign = if term % 2 == 0 { 1.0 } else { -1.0 };
+
+                        sign * coefficient * (term as f64 * phase).cos()
+                    })
+                    .sum::<f64>();
+
+                (weight / self.coherent_gain()) as f32
+            })
+            .collect()
+    }
+}
+
+struct WindowingRunner {
+    input: NodeRunnerInputPort<Vec<f32>>,
+    output: NodeRunnerOutputPort<Vec<f32>>,
+
+    window: NodeRunnerInputPort<FftWindow>,
+}
+
+impl NodeRunner for WindowingRunner {
+    fn run(self: Box<Self>) {
+        let mut window = self.window.recv();
+        // computed again whenever the window or the FFT size changes
+        let mut weights = Vec::new();
+
+        loop {
+            receive! {
+                (self.input): samples => {
+                    if weights.len() != samples.len() {
+                        weights = window.weights(samples.len());
+                    }
+
+                    self.output.send(
+                        &samples
+                            .iter()
+                            .zip(&weights)
+                            .map(|(sample, weight)| sample * weight)
+                            .collect(),
+                    );
+                },
+                (self.window): new_window => {
+                    window = new_window;
+                    weights.clear();
+                },
+            };
+        }
+    }
+}
+
+/// Applies the selected window to every buffer of samples.
+pub struct Windowing {
+    pub input: NodeConfigInputPort<Vec<f32>>,
+    pub output: NodeConfigOutputPort<Vec<f32>>,
+
+    pub window: NodeConfigInputPort<FftWindow>,
+}
+
+impl Windowing {
+    pub fn new() -> Self {
+        Self {
+            input: NodeConfigInputPort::new(),
+            output: NodeConfigOutputPort::new(),
+
+            window: NodeConfigInputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for Windowing {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(WindowingRunner {
+            input: self.input.into(),
+            output: self.output.into(),
+
+            window: self.window.into(),
+        })
+    }
+}
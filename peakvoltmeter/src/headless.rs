@@ -0,0 +1,146 @@
+use crate::{config::HeadlessConfig, measurements::Measurements, Buffers};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{File, OpenOptions},
+    io::{self, Stdout, Write},
+    net::{TcpStream, UdpSocket},
+    path::PathBuf,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+pub const INTERVAL_MIN: f32 = 0.01;
+
+/// Destination of the measurements in headless mode.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Output {
+    // written as `-`
+    Stdout,
+    // written as `udp://host:port`
+    Udp(String),
+    // written as `tcp://host:port`, the connection is reopened when it fails
+    Tcp(String),
+    // any other value, measurements are appended
+    File(PathBuf),
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+
+        if value == "-" {
+            return Ok(Output::Stdout);
+        }
+
+        if let Some(address) = value.strip_prefix("udp://") {
+            return Ok(Output::Udp(address.to_string()));
+        }
+
+        if let Some(address) = value.strip_prefix("tcp://") {
+            return Ok(Output::Tcp(address.to_string()));
+        }
+
+        if value.is_empty() {
+            return Err("output must not be empty".to_string());
+        }
+
+        Ok(Output::File(PathBuf::from(value)))
+    }
+}
+
+impl TryFrom<String> for Output {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<Output> for String {
+    fn from(output: Output) -> Self {
+        output.to_string()
+    }
+}
+
+impl Display for Output {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Stdout => write!(f, "-"),
+            Output::Udp(address) => write!(f, "udp://{}", address),
+            Output::Tcp(address) => write!(f, "tcp://{}", address),
+            Output::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+enum Sink {
+    Stdout(Stdout),
+    File(File),
+    Udp(UdpSocket, String),
+    Tcp(String, Option<TcpStream>),
+}
+
+impl Sink {
+    fn open(output: &Output) -> io::Result<Self> {
+        Ok(match output {
+            Output::Stdout => Sink::Stdout(io::stdout()),
+            Output::File(path) => {
+                Sink::File(OpenOptions::new().create(true).append(true).open(path)?)
+            }
+            Output::Udp(address) => Sink::Udp(UdpSocket::bind("0.0.0.0:0")?, address.clone()),
+            Output::Tcp(address) => Sink::Tcp(address.clone(), None),
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        match self {
+            Sink::Stdout(stdout) => {
+                let mut stdout = stdout.lock();
+                writeln!(stdout, "{}", line)?;
+                stdout.flush()
+            }
+            Sink::File(file) => writeln!(file, "{}", line),
+            Sink::Udp(socket, address) => socket
+                .send_to(line.as_bytes(), address.as_str())
+                .map(|_| ()),
+            Sink::Tcp(address, stream) => {
+                if stream.is_none() {
+                    *stream = Some(TcpStream::connect(address.as_str())?);
+                }
+
+                let result = writeln!(stream.as_mut().unwrap(), "{}", line);
+
+                // the next measurement reconnects
+                if result.is_err() {
+                    *stream = None;
+                }
+
+                result
+            }
+        }
+    }
+}
+
+/// Writes the measurements as one JSON object per line until the process is stopped.
+pub fn run(buffers: Buffers, config: &HeadlessConfig) -> io::Result<()> {
+    let mut sink = Sink::open(&config.output)?;
+
+    let interval = Duration::from_secs_f32(config.interval.max(INTERVAL_MIN));
+
+    loop {
+        thread::sleep(interval);
+
+        let line = serde_json::to_string(&Measurements::read(&buffers))
+            .expect("measurements are always serializable");
+
+        // a failing sink must not stop the measurements, it may recover later
+        if let Err(e) = sink.write_line(&line) {
+            eprintln!("Failed to write measurements to {}: {}", config.output, e);
+        }
+    }
+}
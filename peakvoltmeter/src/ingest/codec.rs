@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+// bit of the sample format code that marks big endian samples
+const BIG_ENDIAN_FLAG: u8 = 0x80;
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleEncoding {
+    I16,
+    I24,
+    I32,
+    U16,
+    U32,
+    F32,
+}
+
+impl SampleEncoding {
+    pub const ALL: [SampleEncoding; 6] = [
+        SampleEncoding::I16,
+        SampleEncoding::I24,
+        SampleEncoding::I32,
+        SampleEncoding::U16,
+        SampleEncoding::U32,
+        SampleEncoding::F32,
+    ];
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(SampleEncoding::I32),
+            2 => Some(SampleEncoding::I16),
+            3 => Some(SampleEncoding::I24),
+            4 => Some(SampleEncoding::U16),
+            5 => Some(SampleEncoding::U32),
+            6 => Some(SampleEncoding::F32),
+            _ => None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            SampleEncoding::I16 | SampleEncoding::U16 => 2,
+            SampleEncoding::I24 => 3,
+            SampleEncoding::I32 | SampleEncoding::U32 | SampleEncoding::F32 => 4,
+        }
+    }
+}
+
+impl Display for SampleEncoding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SampleEncoding::I16 => write!(f, "i16"),
+            SampleEncoding::I24 => write!(f, "i24 (packed)"),
+            SampleEncoding::I32 => write!(f, "i32"),
+            SampleEncoding::U16 => write!(f, "u16 (offset binary)"),
+            SampleEncoding::U32 => write!(f, "u32 (offset binary)"),
+            SampleEncoding::F32 => write!(f, "f32"),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl Display for Endianness {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Endianness::Little => write!(f, "Little Endian"),
+            Endianness::Big => write!(f, "Big Endian"),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SampleCodec {
+    pub encoding: SampleEncoding,
+    pub endianness: Endianness,
+}
+
+impl SampleCodec {
+    /// Decodes the sample format code of a packet header. The lower bits select the encoding,
+    /// the highest bit marks big endian samples.
+    pub fn from_code(code: u8) -> Option<Self> {
+        let encoding = SampleEncoding::from_code(code & !BIG_ENDIAN_FLAG)?;
+
+        let endianness = if code & BIG_ENDIAN_FLAG != 0 {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        };
+
+        Some(Self {
+            encoding,
+            endianness,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.encoding.size()
+    }
+
+    /// Decodes a single sample, `bytes` has to be exactly `size()` bytes long.
+    pub fn decode(&self, bytes: &[u8]) -> f32 {
+        fn array<const N: usize>(bytes: &[u8], endianness: Endianness) -> [u8; N] {
+            let mut array: [u8; N] = bytes.try_into().unwrap();
+
+            // everything is decoded as little endian
+            if endianness == Endianness::Big {
+                array.reverse();
+            }
+
+            array
+        }
+
+        match self.encoding {
+            SampleEncoding::I16 => i16::from_le_bytes(array(bytes, self.endianness)) as f32,
+            SampleEncoding::I24 => {
+                let [b0, b1, b2] = array(bytes, self.endianness);
+                let sign = if b2 & 0x80 != 0 { 0xff } else { 0x00 };
+
+                i32::from_le_bytes([b0, b1, b2, sign]) as f32
+            }
+            SampleEncoding::I32 => i32::from_le_bytes(array(bytes, self.endianness)) as f32,
+            SampleEncoding::U16 => {
+                (u16::from_le_bytes(array(bytes, self.endianness)) as i32 - (1 << 15)) as f32
+            }
+            SampleEncoding::U32 => {
+                (u32::from_le_bytes(array(bytes, self.endianness)) as i64 - (1 << 31)) as f32
+            }
+            SampleEncoding::F32 => f32::from_le_bytes(array(bytes, self.endianness)),
+        }
+    }
+}
+
+impl Display for SampleCodec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, {}", self.encoding, self.endianness)
+    }
+}
+
+/// Position of the samples within a raw datagram without packet header.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct SampleLayout {
+    pub codec: SampleCodec,
+    // byte offset of the first sample
+    pub offset: usize,
+    // distance in bytes between the start of two consecutive samples
+    pub stride: usize,
+}
+
+impl SampleLayout {
+    pub fn decode(&self, bytes: &[u8]) -> Vec<f32> {
+        let size = self.codec.size();
+        let stride = self.stride.max(size);
+
+        bytes
+            .get(self.offset..)
+            .unwrap_or_default()
+            .windows(size)
+            .step_by(stride)
+            .map(|sample| self.codec.decode(sample))
+            .collect()
+    }
+}
@@ -0,0 +1,122 @@
+use super::{
+    codec::SampleLayout,
+    packet::{Frame, PacketError, PeakVoltmeterPacket},
+};
+use conductor::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PacketFormat {
+    // datagrams with packet header
+    Framed,
+    // datagrams without header, decoded with the configured sample layout
+    Raw,
+}
+
+impl Display for PacketFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PacketFormat::Framed => write!(f, "Framed"),
+            PacketFormat::Raw => write!(f, "Raw"),
+        }
+    }
+}
+
+struct DecoderRunner {
+    channels: usize,
+
+    input: NodeRunnerInputPort<PeakVoltmeterPacket>,
+    output: NodeRunnerOutputPort<PeakVoltmeterPacket>,
+
+    packet_format: NodeRunnerInputPort<PacketFormat>,
+    sample_layout: NodeRunnerInputPort<SampleLayout>,
+}
+
+impl NodeRunner for DecoderRunner {
+    fn run(self: Box<Self>) {
+        let mut packet_format = self.packet_format.recv();
+        let mut sample_layout = self.sample_layout.recv();
+
+        loop {
+            receive! {
+                (self.input): packet => {
+                    let packet = match (packet, packet_format) {
+                        (PeakVoltmeterPacket::Raw(bytes), PacketFormat::Raw) => {
+                            let mut samples = sample_layout.decode(&bytes);
+
+                            // a trailing incomplete set of channel samples cannot be assigned
+                            samples.truncate(samples.len() - samples.len() % self.channels);
+
+                            PeakVoltmeterPacket::Frame(Frame {
+                                sequence: None,
+                                channels: self.channels,
+                                samples,
+                            })
+                        }
+                        (PeakVoltmeterPacket::Raw(_), PacketFormat::Framed) => {
+                            PeakVoltmeterPacket::Malformed(PacketError::InvalidMagic)
+                        }
+                        (PeakVoltmeterPacket::Frame(frame), _) if frame.channels != self.channels => {
+                            PeakVoltmeterPacket::Malformed(PacketError::ChannelCountMismatch {
+                                expected: self.channels,
+                                actual: frame.channels,
+                            })
+                        }
+                        (packet, _) => packet,
+                    };
+
+                    self.output.send(&packet);
+                },
+                (self.packet_format): new_packet_format => {
+                    packet_format = new_packet_format;
+                },
+                (self.sample_layout): new_sample_layout => {
+                    sample_layout = new_sample_layout;
+                },
+            };
+        }
+    }
+}
+
+/// Decodes raw datagrams according to the configured sample layout, their samples are
+/// interleaved over all channels. Framed packets describe their samples themselves and are passed
+/// through unchanged if they carry the expected number of channels.
+pub struct Decoder {
+    channels: usize,
+
+    pub input: NodeConfigInputPort<PeakVoltmeterPacket>,
+    pub output: NodeConfigOutputPort<PeakVoltmeterPacket>,
+
+    pub packet_format: NodeConfigInputPort<PacketFormat>,
+    pub sample_layout: NodeConfigInputPort<SampleLayout>,
+}
+
+impl Decoder {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            channels,
+
+            input: NodeConfigInputPort::new(),
+            output: NodeConfigOutputPort::new(),
+
+            packet_format: NodeConfigInputPort::new(),
+            sample_layout: NodeConfigInputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for Decoder {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(DecoderRunner {
+            channels: self.channels,
+
+            input: self.input.into(),
+            output: self.output.into(),
+
+            packet_format: self.packet_format.into(),
+            sample_layout: self.sample_layout.into(),
+        })
+    }
+}
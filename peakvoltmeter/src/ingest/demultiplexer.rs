@@ -0,0 +1,47 @@
+use super::packet::Frame;
+use conductor::prelude::*;
+
+struct DemultiplexerRunner {
+    input: NodeRunnerInputPort<Frame>,
+    outputs: Vec<NodeRunnerOutputPort<f32>>,
+}
+
+impl NodeRunner for DemultiplexerRunner {
+    fn run(self: Box<Self>) {
+        loop {
+            let frame = self.input.recv();
+
+            // the decoder only lets frames with the expected channel count through
+            for samples in frame.samples.chunks_exact(frame.channels) {
+                for (output, sample) in self.outputs.iter().zip(samples) {
+                    output.send(sample);
+                }
+            }
+        }
+    }
+}
+
+/// Splits the interleaved samples of every received frame into one stream of single values per
+/// channel.
+pub struct Demultiplexer {
+    pub input: NodeConfigInputPort<Frame>,
+    pub outputs: Vec<NodeConfigOutputPort<f32>>,
+}
+
+impl Demultiplexer {
+    pub fn new(channels: usize) -> Self {
+        Self {
+            input: NodeConfigInputPort::new(),
+            outputs: (0..channels).map(|_| NodeConfigOutputPort::new()).collect(),
+        }
+    }
+}
+
+impl NodeConfig for Demultiplexer {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(DemultiplexerRunner {
+            input: self.input.into(),
+            outputs: self.outputs.into_iter().map(Into::into).collect(),
+        })
+    }
+}
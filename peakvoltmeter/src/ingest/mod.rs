@@ -0,0 +1,104 @@
+mod codec;
+mod decoder;
+mod demultiplexer;
+mod packet;
+mod receiver;
+mod sequence;
+mod tcp;
+mod udp;
+
+pub use codec::{Endianness, SampleCodec, SampleEncoding, SampleLayout};
+pub use decoder::{Decoder, PacketFormat};
+pub use demultiplexer::Demultiplexer;
+pub use packet::Frame;
+pub use receiver::{NetworkReceiver, Source, StreamFraming, Transport};
+pub use sequence::{GapHandling, LinkStatistics, SequenceTracker};
+
+use egui::{Color32, RichText};
+use std::{
+    fmt::{self, Display, Formatter},
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+};
+
+#[derive(Clone, Default)]
+pub enum ConnectionStatus {
+    #[default]
+    Idle,
+    Listening(SocketAddr),
+    Connecting(SocketAddr),
+    // address of the peer the stream is received from
+    Connected(SocketAddr),
+    Reconnecting(SocketAddr, String),
+    Failed(SocketAddr, String),
+}
+
+impl Display for ConnectionStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionStatus::Idle => write!(f, "Idle"),
+            ConnectionStatus::Listening(address) => write!(f, "Listening on {}", address),
+            ConnectionStatus::Connecting(address) => write!(f, "Connecting to {}", address),
+            ConnectionStatus::Connected(peer) => write!(f, "Connected to {}", peer),
+            ConnectionStatus::Reconnecting(address, error) => {
+                write!(f, "{}: {}, reconnecting", address, error)
+            }
+            ConnectionStatus::Failed(address, error) => write!(f, "{}: {}", address, error),
+        }
+    }
+}
+
+pub struct LinkStatus {
+    statistics: Arc<RwLock<LinkStatistics>>,
+    pub status: Arc<RwLock<ConnectionStatus>>,
+}
+
+impl LinkStatus {
+    pub fn new(
+        statistics: Arc<RwLock<LinkStatistics>>,
+        status: Arc<RwLock<ConnectionStatus>>,
+    ) -> Self {
+        Self { statistics, status }
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        let status = self.status.read().unwrap().clone();
+
+        let status_color = match status {
+            ConnectionStatus::Connected(_) => Color32::GREEN,
+            ConnectionStatus::Connecting(_) | ConnectionStatus::Reconnecting(..) => Color32::YELLOW,
+            ConnectionStatus::Failed(..) => Color32::RED,
+            _ => ui.visuals().text_color(),
+        };
+
+        let statistics = *self.statistics.read().unwrap();
+
+        let color = if statistics.lost > 0 || statistics.malformed > 0 {
+            Color32::ORANGE
+        } else {
+            ui.visuals().text_color()
+        };
+
+        let mut details = format!(
+            "Malformed: {}\nSender resets: {}",
+            statistics.malformed, statistics.resets
+        );
+
+        if let Some(error) = statistics.last_error {
+            details.push_str(&format!("\nLast error: {}", error));
+        }
+
+        ui.label(
+            RichText::new(format!(
+                "Packets: {} received, {} lost, {} reordered, {} duplicated",
+                statistics.received, statistics.lost, statistics.reordered, statistics.duplicated
+            ))
+            .color(color),
+        )
+        .on_hover_text(details);
+
+        ui.separator();
+
+        ui.label(RichText::new(format!("{}", status)).color(status_color));
+    }
+}
@@ -0,0 +1,146 @@
+use super::codec::SampleCodec;
+use conductor::prelude::*;
+use std::fmt::{self, Display, Formatter};
+
+// Every datagram starts with a fixed size header followed by `sample_count` samples per channel:
+//
+// | offset | size | field         |
+// |--------|------|---------------|
+// | 0      | 4    | magic "PVMF"  |
+// | 4      | 1    | version       |
+// | 5      | 1    | sample codec  |
+// | 6      | 2    | sample count  |
+// | 8      | 4    | sequence      |
+// | 12     | 1    | channel count |
+// | 13     | 3    | reserved      |
+//
+// The samples of all channels are interleaved. Version 1 packets end the header after the
+// sequence and always carry a single channel. All multi-byte header fields are little endian.
+pub const PACKET_MAGIC: [u8; 4] = *b"PVMF";
+pub const PACKET_VERSION: u8 = 2;
+pub const HEADER_SIZE: usize = 16;
+const PACKET_VERSION_SINGLE_CHANNEL: u8 = 1;
+const HEADER_SIZE_SINGLE_CHANNEL: usize = 12;
+
+// largest payload a single UDP datagram can carry over IPv4
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+#[derive(Clone, Copy, Debug)]
+pub enum PacketError {
+    TooShort(usize),
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    UnsupportedSampleFormat(u8),
+    LengthMismatch { expected: usize, actual: usize },
+    ChannelCountMismatch { expected: usize, actual: usize },
+}
+
+impl Display for PacketError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PacketError::TooShort(length) => write!(f, "packet too short ({} bytes)", length),
+            PacketError::InvalidMagic => write!(f, "invalid packet magic"),
+            PacketError::UnsupportedVersion(version) => {
+                write!(f, "unsupported packet version {}", version)
+            }
+            PacketError::UnsupportedSampleFormat(format) => {
+                write!(f, "unsupported sample format {}", format)
+            }
+            PacketError::LengthMismatch { expected, actual } => write!(
+                f,
+                "payload length mismatch (expected {} bytes, got {})",
+                expected, actual
+            ),
+            PacketError::ChannelCountMismatch { expected, actual } => write!(
+                f,
+                "channel count mismatch (expected {} channels, got {})",
+                expected, actual
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Frame {
+    // raw datagrams carry no sequence number
+    pub sequence: Option<u32>,
+    // number of interleaved channels in `samples`
+    pub channels: usize,
+    pub samples: Vec<f32>,
+}
+
+impl Frame {
+    fn parse(bytes: &[u8]) -> Result<Self, PacketError> {
+        if bytes.len() < HEADER_SIZE_SINGLE_CHANNEL {
+            return Err(PacketError::TooShort(bytes.len()));
+        }
+
+        if bytes[0..4] != PACKET_MAGIC {
+            return Err(PacketError::InvalidMagic);
+        }
+
+        let version = bytes[4];
+        let (header_size, channels) = match version {
+            PACKET_VERSION_SINGLE_CHANNEL => (HEADER_SIZE_SINGLE_CHANNEL, 1),
+            PACKET_VERSION => {
+                if bytes.len() < HEADER_SIZE {
+                    return Err(PacketError::TooShort(bytes.len()));
+                }
+
+                (HEADER_SIZE, bytes[12] as usize)
+            }
+            _ => return Err(PacketError::UnsupportedVersion(version)),
+        };
+
+        let codec = SampleCodec::from_code(bytes[5])
+            .ok_or(PacketError::UnsupportedSampleFormat(bytes[5]))?;
+        let sample_count = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+        let sequence = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+
+        let payload = &bytes[header_size..];
+        let expected = sample_count * channels * codec.size();
+
+        if payload.len() != expected {
+            return Err(PacketError::LengthMismatch {
+                expected,
+                actual: payload.len(),
+            });
+        }
+
+        let samples = payload
+            .chunks_exact(codec.size())
+            .map(|bytes| codec.decode(bytes))
+            .collect();
+
+        Ok(Self {
+            sequence: Some(sequence),
+            channels,
+            samples,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum PeakVoltmeterPacket {
+    Frame(Frame),
+    // datagram without packet header, decoded according to the configured sample layout
+    Raw(Vec<u8>),
+    Malformed(PacketError),
+}
+
+impl UdpDeserializer for PeakVoltmeterPacket {
+    fn max_packet_size() -> usize {
+        MAX_DATAGRAM_SIZE
+    }
+
+    fn deserialize_packet(bytes: &[u8]) -> Self {
+        if !bytes.starts_with(&PACKET_MAGIC) {
+            return PeakVoltmeterPacket::Raw(bytes.to_vec());
+        }
+
+        match Frame::parse(bytes) {
+            Ok(frame) => PeakVoltmeterPacket::Frame(frame),
+            Err(error) => PeakVoltmeterPacket::Malformed(error),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use super::{packet::PeakVoltmeterPacket, tcp, udp, ConnectionStatus};
+use conductor::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    net::SocketAddr,
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc, RwLock,
+    },
+    thread,
+    time::Duration,
+};
+
+// how long a read may block before a pending source change is picked up
+pub(super) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transport {
+    // datagrams received on the listen address
+    Udp,
+    // byte stream from a client connecting to the listen address
+    TcpServer,
+    // byte stream from a server at the connect address
+    TcpClient,
+}
+
+impl Display for Transport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Transport::Udp => write!(f, "UDP"),
+            Transport::TcpServer => write!(f, "TCP Server"),
+            Transport::TcpClient => write!(f, "TCP Client"),
+        }
+    }
+}
+
+/// Splits a TCP byte stream into packets, which are then handled like UDP datagrams.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StreamFraming {
+    // every packet is preceded by its length as little endian u32
+    LengthPrefixed,
+    // every packet has the given number of bytes
+    FixedWidth(usize),
+}
+
+impl Display for StreamFraming {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamFraming::LengthPrefixed => write!(f, "Length Prefixed"),
+            StreamFraming::FixedWidth(width) => write!(f, "Fixed Width ({} bytes)", width),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Source {
+    Udp(SocketAddr),
+    TcpServer(SocketAddr, StreamFraming),
+    TcpClient(SocketAddr, StreamFraming),
+}
+
+/// Everything a transport needs while receiving from a source.
+pub(super) struct Link {
+    status: Arc<RwLock<ConnectionStatus>>,
+    changes: Receiver<Source>,
+
+    pub output: NodeRunnerOutputPort<PeakVoltmeterPacket>,
+}
+
+impl Link {
+    pub fn set_status(&self, status: ConnectionStatus) {
+        *self.status.write().unwrap() = status;
+    }
+
+    /// Returns the most recent source change, if there is one.
+    pub fn changed(&self) -> Option<Source> {
+        self.changes.try_iter().last()
+    }
+
+    /// Blocks until the source changes, `None` once no more changes can arrive.
+    pub fn wait_for_change(&self, timeout: Option<Duration>) -> Option<Source> {
+        let source = match timeout {
+            Some(timeout) => self.changes.recv_timeout(timeout).ok()?,
+            None => self.changes.recv().ok()?,
+        };
+
+        Some(self.changed().unwrap_or(source))
+    }
+}
+
+struct NetworkReceiverRunner {
+    status: Arc<RwLock<ConnectionStatus>>,
+
+    source: NodeRunnerInputPort<Source>,
+    output: NodeRunnerOutputPort<PeakVoltmeterPacket>,
+}
+
+impl NodeRunner for NetworkReceiverRunner {
+    fn run(self: Box<Self>) {
+        let Self {
+            status,
+            source,
+            output,
+        } = *self;
+
+        // Receiving blocks, so source changes are forwarded from a separate thread and picked up
+        // whenever a read times out.
+        let (source_sender, source_receiver) = channel();
+
+        thread::spawn(move || loop {
+            if source_sender.send(source.recv()).is_err() {
+                break;
+            }
+        });
+
+        let link = Link {
+            status,
+            changes: source_receiver,
+            output,
+        };
+
+        let mut next_source = link.wait_for_change(None);
+
+        // every transport runs until the source changes, the previous socket is closed before
+        // the next one is opened, as the new source may use the same port
+        while let Some(source) = next_source {
+            next_source = match source {
+                Source::Udp(address) => udp::receive(&link, address),
+                Source::TcpServer(address, framing) => tcp::serve(&link, address, framing),
+                Source::TcpClient(address, framing) => tcp::connect(&link, address, framing),
+            };
+        }
+    }
+}
+
+/// Receives packets over UDP or TCP, switching transports whenever a new source arrives.
+pub struct NetworkReceiver {
+    status: Arc<RwLock<ConnectionStatus>>,
+
+    pub source: NodeConfigInputPort<Source>,
+    pub output: NodeConfigOutputPort<PeakVoltmeterPacket>,
+}
+
+impl NetworkReceiver {
+    pub fn new(status: Arc<RwLock<ConnectionStatus>>) -> Self {
+        Self {
+            status,
+
+            source: NodeConfigInputPort::new(),
+            output: NodeConfigOutputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for NetworkReceiver {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(NetworkReceiverRunner {
+            status: self.status,
+
+            source: self.source.into(),
+            output: self.output.into(),
+        })
+    }
+}
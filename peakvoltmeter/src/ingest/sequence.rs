@@ -0,0 +1,406 @@
+use super::{
+    packet::{Frame, PacketError, PeakVoltmeterPacket},
+    receiver::Source,
+};
+use conductor::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt::{self, Display, Formatter},
+    sync::{Arc, RwLock},
+};
+
+// Jumps in the sequence number larger than this are treated as a restarted sender instead of
+// lost packets, so a reset does not fill the charts with thousands of synthetic samples.
+const MAX_GAP: u32 = 1024;
+// consecutive packets behind the expected sequence number that are taken as a restarted sender
+// instead of duplicates, a sender restarting at 0 is recognized right away
+const RESTART_PACKETS: u32 = 3;
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GapHandling {
+    ZeroFill,
+    HoldLast,
+    MarkInvalid,
+}
+
+impl Display for GapHandling {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GapHandling::ZeroFill => write!(f, "Zero Fill"),
+            GapHandling::HoldLast => write!(f, "Hold Last"),
+            GapHandling::MarkInvalid => write!(f, "Mark Invalid"),
+        }
+    }
+}
+
+impl GapHandling {
+    fn fill_value(&self, last_sample: f32) -> f32 {
+        match self {
+            GapHandling::ZeroFill => 0.0,
+            GapHandling::HoldLast => last_sample,
+            // NaN propagates through every measurement, so values computed over a gap show up
+            // as invalid instead of silently being wrong
+            GapHandling::MarkInvalid => f32::NAN,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct LinkStatistics {
+    pub received: u64,
+    pub lost: u64,
+    pub duplicated: u64,
+    pub reordered: u64,
+    pub malformed: u64,
+    pub resets: u64,
+    // samples of all channels, received and synthesized for lost packets
+    pub samples: u64,
+    pub filled_samples: u64,
+
+    pub last_error: Option<PacketError>,
+}
+
+/// Sequence numbers seen so far, tells for every frame whether it is passed on and which lost
+/// frames are filled in before it.
+#[derive(Default)]
+struct SequenceState {
+    expected: Option<u32>,
+    // last sample of every channel
+    last_samples: Vec<f32>,
+
+    // sequence numbers that were skipped recently, used to tell late packets from duplicates
+    missing: VecDeque<u32>,
+    // last one and count of the consecutive packets behind `expected` that were not missing
+    behind: Option<(u32, u32)>,
+}
+
+impl SequenceState {
+    /// Takes the next frame, returns the frames to pass on, the frames filling a gap before it
+    /// first.
+    fn push(
+        &mut self,
+        frame: Frame,
+        gap_handling: GapHandling,
+        statistics: &mut LinkStatistics,
+    ) -> Vec<Frame> {
+        let Some(sequence) = frame.sequence else {
+            // without sequence numbers there is nothing to track
+            statistics.received += 1;
+            statistics.samples += frame.samples.len() as u64;
+
+            return vec![frame];
+        };
+
+        let mut distance = self
+            .expected
+            .map_or(0, |expected| sequence.wrapping_sub(expected) as i32);
+
+        if distance < 0 && distance.unsigned_abs() <= MAX_GAP {
+            if let Some(index) = self.missing.iter().position(|&missing| missing == sequence) {
+                // the gap this packet belongs to has already been filled, so it is dropped
+                self.missing.remove(index);
+                self.behind = None;
+                statistics.reordered += 1;
+                statistics.lost -= 1;
+
+                return Vec::new();
+            }
+
+            let behind = match self.behind {
+                Some((last, count)) if last.wrapping_add(1) == sequence => count + 1,
+                _ => 1,
+            };
+
+            // a sender starting again at 0 or a run of new packets behind the old ones restarted
+            if sequence != 0 && behind < RESTART_PACKETS {
+                self.behind = Some((sequence, behind));
+                statistics.duplicated += 1;
+
+                return Vec::new();
+            }
+
+            // the packets of the run before this one were counted as duplicates but are lost
+            statistics.duplicated -= behind as u64 - 1;
+            statistics.lost += behind as u64 - 1;
+
+            distance = i32::MAX;
+        }
+
+        self.behind = None;
+
+        statistics.received += 1;
+        statistics.samples += frame.samples.len() as u64;
+
+        let mut frames = Vec::new();
+
+        if distance as u32 > MAX_GAP {
+            statistics.resets += 1;
+            self.missing.clear();
+        } else if distance > 0 {
+            statistics.lost += distance as u64;
+
+            let fill_samples = (0..frame.samples.len())
+                .map(|i| {
+                    let last_sample = self.last_samples.get(i % frame.channels).copied();
+                    gap_handling.fill_value(last_sample.unwrap_or(0.0))
+                })
+                .collect::<Vec<_>>();
+
+            statistics.filled_samples += fill_samples.len() as u64 * distance as u64;
+
+            for offset in 0..distance as u32 {
+                let lost_sequence = self.expected.unwrap().wrapping_add(offset);
+
+                self.missing.push_back(lost_sequence);
+                if self.missing.len() > MAX_GAP as usize {
+                    self.missing.pop_front();
+                }
+
+                // the lost packet most likely had as many samples as the current one
+                frames.push(Frame {
+                    sequence: Some(lost_sequence),
+                    channels: frame.channels,
+                    samples: fill_samples.clone(),
+                });
+            }
+        }
+
+        self.expected = Some(sequence.wrapping_add(1));
+        if let Some(samples) = frame.samples.rchunks_exact(frame.channels).next() {
+            self.last_samples = samples.to_vec();
+        }
+
+        frames.push(frame);
+
+        frames
+    }
+}
+
+struct SequenceTrackerRunner {
+    statistics: Arc<RwLock<LinkStatistics>>,
+
+    input: NodeRunnerInputPort<PeakVoltmeterPacket>,
+    output: NodeRunnerOutputPort<Frame>,
+
+    gap_handling: NodeRunnerInputPort<GapHandling>,
+    source: NodeRunnerInputPort<Source>,
+}
+
+impl NodeRunner for SequenceTrackerRunner {
+    fn run(self: Box<Self>) {
+        let mut gap_handling = self.gap_handling.recv();
+
+        let mut state = SequenceState::default();
+
+        loop {
+            receive! {
+                (self.input): packet => {
+                    let frame = match packet {
+                        PeakVoltmeterPacket::Frame(frame) => frame,
+                        PeakVoltmeterPacket::Raw(_) => {
+                            // raw datagrams have to be decoded before their sequence can be tracked
+                            let mut statistics = self.statistics.write().unwrap();
+                            statistics.malformed += 1;
+                            statistics.last_error = Some(PacketError::InvalidMagic);
+                            continue;
+                        }
+                        PeakVoltmeterPacket::Malformed(error) => {
+                            let mut statistics = self.statistics.write().unwrap();
+                            statistics.malformed += 1;
+                            statistics.last_error = Some(error);
+                            continue;
+                        }
+                    };
+
+                    let frames =
+                        state.push(frame, gap_handling, &mut self.statistics.write().unwrap());
+
+                    for frame in &frames {
+                        self.output.send(frame);
+                    }
+                },
+                (self.gap_handling): new_gap_handling => {
+                    gap_handling = new_gap_handling;
+                },
+                (self.source): _source => {
+                    // the sequence numbers of another source have nothing to do with the last ones
+                    state = SequenceState::default();
+                },
+            };
+        }
+    }
+}
+
+/// Tracks the sequence numbers of incoming packets, counting lost, duplicated and reordered
+/// packets and filling gaps according to the selected gap handling.
+pub struct SequenceTracker {
+    statistics: Arc<RwLock<LinkStatistics>>,
+
+    pub input: NodeConfigInputPort<PeakVoltmeterPacket>,
+    pub output: NodeConfigOutputPort<Frame>,
+
+    pub gap_handling: NodeConfigInputPort<GapHandling>,
+    // the tracking starts over with every new source
+    pub source: NodeConfigInputPort<Source>,
+}
+
+impl SequenceTracker {
+    pub fn new(statistics: Arc<RwLock<LinkStatistics>>) -> Self {
+        Self {
+            statistics,
+
+            input: NodeConfigInputPort::new(),
+            output: NodeConfigOutputPort::new(),
+
+            gap_handling: NodeConfigInputPort::new(),
+            source: NodeConfigInputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for SequenceTracker {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(SequenceTrackerRunner {
+            statistics: self.statistics,
+
+            input: self.input.into(),
+            output: self.output.into(),
+
+            gap_handling: self.gap_handling.into(),
+            source: self.source.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(sequence: u32) -> Frame {
+        Frame {
+            sequence: Some(sequence),
+            channels: 2,
+            samples: vec![sequence as f32, -(sequence as f32)],
+        }
+    }
+
+    /// Sequence numbers of the frames passed on for the given ones.
+    fn track(
+        state: &mut SequenceState,
+        statistics: &mut LinkStatistics,
+        sequences: impl IntoIterator<Item = u32>,
+    ) -> Vec<u32> {
+        sequences
+            .into_iter()
+            .flat_map(|sequence| state.push(frame(sequence), GapHandling::ZeroFill, statistics))
+            .map(|frame| frame.sequence.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn fills_lost_packets() {
+        let mut state = SequenceState::default();
+        let mut statistics = LinkStatistics::default();
+
+        let frames = [0, 1, 4]
+            .into_iter()
+            .flat_map(|sequence| {
+                state.push(frame(sequence), GapHandling::HoldLast, &mut statistics)
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            frames
+                .iter()
+                .map(|frame| frame.sequence.unwrap())
+                .collect::<Vec<_>>(),
+            [0, 1, 2, 3, 4]
+        );
+        // the last samples of packet 1 are held over the gap
+        assert_eq!(frames[2].samples, [1.0, -1.0]);
+        assert_eq!(frames[3].samples, [1.0, -1.0]);
+
+        assert_eq!(statistics.received, 3);
+        assert_eq!(statistics.lost, 2);
+        assert_eq!(statistics.filled_samples, 4);
+        assert_eq!(statistics.samples, 6);
+    }
+
+    #[test]
+    fn drops_late_and_duplicated_packets() {
+        let mut state = SequenceState::default();
+        let mut statistics = LinkStatistics::default();
+
+        assert_eq!(
+            track(&mut state, &mut statistics, [0, 2, 1, 3, 3, 4]),
+            [0, 1, 2, 3, 4]
+        );
+
+        assert_eq!(statistics.received, 4);
+        assert_eq!(statistics.reordered, 1);
+        assert_eq!(statistics.lost, 0);
+        assert_eq!(statistics.duplicated, 1);
+        assert_eq!(statistics.resets, 0);
+    }
+
+    #[test]
+    fn follows_a_restarted_sender() {
+        let mut state = SequenceState::default();
+        let mut statistics = LinkStatistics::default();
+
+        track(&mut state, &mut statistics, 0..100);
+
+        // starting again at 0 is a restart right away
+        assert_eq!(track(&mut state, &mut statistics, 0..3), [0, 1, 2]);
+        assert_eq!(statistics.resets, 1);
+        assert_eq!(statistics.duplicated, 0);
+
+        track(&mut state, &mut statistics, 3..100);
+
+        // other restarts are recognized after a few consecutive packets
+        assert_eq!(track(&mut state, &mut statistics, 50..55), [52, 53, 54]);
+        assert_eq!(statistics.resets, 2);
+        assert_eq!(statistics.duplicated, 0);
+        assert_eq!(statistics.lost, 2);
+
+        // a jump far ahead is a restart as well, without filling the gap
+        assert_eq!(track(&mut state, &mut statistics, [10_000]), [10_000]);
+        assert_eq!(statistics.resets, 3);
+        assert_eq!(statistics.filled_samples, 0);
+    }
+
+    #[test]
+    fn does_not_restart_on_repeated_duplicates() {
+        let mut state = SequenceState::default();
+        let mut statistics = LinkStatistics::default();
+
+        track(&mut state, &mut statistics, 0..10);
+
+        assert!(track(&mut state, &mut statistics, [5, 5, 5, 5]).is_empty());
+        assert_eq!(statistics.duplicated, 4);
+        assert_eq!(statistics.resets, 0);
+
+        assert_eq!(track(&mut state, &mut statistics, [10]), [10]);
+    }
+
+    #[test]
+    fn passes_frames_without_sequence() {
+        let mut state = SequenceState::default();
+        let mut statistics = LinkStatistics::default();
+
+        let frames = state.push(
+            Frame {
+                sequence: None,
+                channels: 1,
+                samples: vec![1.0],
+            },
+            GapHandling::ZeroFill,
+            &mut statistics,
+        );
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(statistics.received, 1);
+    }
+}
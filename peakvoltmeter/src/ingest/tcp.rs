@@ -0,0 +1,171 @@
+use super::{
+    packet::PeakVoltmeterPacket,
+    receiver::{Link, Source, StreamFraming, POLL_INTERVAL},
+    ConnectionStatus,
+};
+use conductor::prelude::*;
+use std::{
+    io::{ErrorKind, Read},
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+    time::Duration,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const RECONNECT_DELAY_MIN: Duration = Duration::from_millis(250);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(8);
+
+const LENGTH_PREFIX_SIZE: usize = 4;
+const READ_BUFFER_SIZE: usize = 16 * 1024;
+
+enum StreamEnd {
+    Closed(String),
+    SourceChanged(Source),
+}
+
+impl StreamFraming {
+    /// Removes the next complete packet from the start of `buffer`. A length prefix larger than
+    /// any packet means the stream is out of sync and has to be reconnected.
+    fn next_packet(&self, buffer: &mut Vec<u8>) -> Result<Option<Vec<u8>>, String> {
+        let (skip, length) = match *self {
+            StreamFraming::LengthPrefixed => {
+                let Some(prefix) = buffer.get(..LENGTH_PREFIX_SIZE) else {
+                    return Ok(None);
+                };
+
+                let length = u32::from_le_bytes(prefix.try_into().unwrap()) as usize;
+
+                if length > PeakVoltmeterPacket::max_packet_size() {
+                    return Err(format!("packet length {} exceeds the maximum", length));
+                }
+
+                (LENGTH_PREFIX_SIZE, length)
+            }
+            StreamFraming::FixedWidth(width) => (0, width.max(1)),
+        };
+
+        if buffer.len() < skip + length {
+            return Ok(None);
+        }
+
+        let packet = buffer[skip..skip + length].to_vec();
+        buffer.drain(..skip + length);
+
+        Ok(Some(packet))
+    }
+}
+
+fn read_stream(link: &Link, mut stream: TcpStream, framing: StreamFraming) -> StreamEnd {
+    let peer = stream.peer_addr().and_then(|peer| {
+        // accepted streams inherit the non-blocking mode of the listener on some platforms
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        Ok(peer)
+    });
+
+    match peer {
+        Ok(peer) => link.set_status(ConnectionStatus::Connected(peer)),
+        Err(e) => return StreamEnd::Closed(e.to_string()),
+    }
+
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    let mut pending = Vec::new();
+
+    loop {
+        if let Some(source) = link.changed() {
+            return StreamEnd::SourceChanged(source);
+        }
+
+        match stream.read(&mut buffer) {
+            Ok(0) => return StreamEnd::Closed("connection closed by peer".to_string()),
+            Ok(length) => {
+                pending.extend_from_slice(&buffer[..length]);
+
+                loop {
+                    match framing.next_packet(&mut pending) {
+                        Ok(Some(packet)) => link
+                            .output
+                            .send(&PeakVoltmeterPacket::deserialize_packet(&packet)),
+                        Ok(None) => break,
+                        Err(e) => return StreamEnd::Closed(e),
+                    }
+                }
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                ) => {}
+            Err(e) => return StreamEnd::Closed(e.to_string()),
+        }
+    }
+}
+
+/// Accepts a single client at a time on the listen address until the source changes.
+pub(super) fn serve(link: &Link, address: SocketAddr, framing: StreamFraming) -> Option<Source> {
+    let listener = TcpListener::bind(address).and_then(|listener| {
+        // accepting is polled, so a source change is noticed while no client is connected
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    });
+
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(e) => {
+            link.set_status(ConnectionStatus::Failed(address, e.to_string()));
+
+            return link.wait_for_change(None);
+        }
+    };
+
+    loop {
+        link.set_status(ConnectionStatus::Listening(address));
+
+        let stream = loop {
+            if let Some(source) = link.changed() {
+                return Some(source);
+            }
+
+            match listener.accept() {
+                Ok((stream, _)) => break stream,
+                // errors of a single incoming connection do not affect the listener
+                Err(_) => thread::sleep(POLL_INTERVAL),
+            }
+        };
+
+        // after a disconnect the listener waits for the next client
+        if let StreamEnd::SourceChanged(source) = read_stream(link, stream, framing) {
+            return Some(source);
+        }
+    }
+}
+
+/// Connects to the server at the connect address, reconnecting with an increasing delay
+/// whenever the connection fails, until the source changes.
+pub(super) fn connect(link: &Link, address: SocketAddr, framing: StreamFraming) -> Option<Source> {
+    let mut reconnect_delay = RECONNECT_DELAY_MIN;
+
+    loop {
+        link.set_status(ConnectionStatus::Connecting(address));
+
+        let error = match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                reconnect_delay = RECONNECT_DELAY_MIN;
+
+                match read_stream(link, stream, framing) {
+                    StreamEnd::SourceChanged(source) => return Some(source),
+                    StreamEnd::Closed(error) => error,
+                }
+            }
+            Err(e) => e.to_string(),
+        };
+
+        link.set_status(ConnectionStatus::Reconnecting(address, error));
+
+        // a source change ends the delay early
+        match link.wait_for_change(Some(reconnect_delay)) {
+            Some(source) => return Some(source),
+            None => reconnect_delay = (reconnect_delay * 2).min(RECONNECT_DELAY_MAX),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use super::{
+    packet::PeakVoltmeterPacket,
+    receiver::{Link, Source, POLL_INTERVAL},
+    ConnectionStatus,
+};
+use conductor::prelude::*;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Receives datagrams on the listen address until the source changes.
+pub(super) fn receive(link: &Link, address: SocketAddr) -> Option<Source> {
+    let socket = UdpSocket::bind(address).and_then(|socket| {
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+        Ok(socket)
+    });
+
+    let socket = match socket {
+        Ok(socket) => socket,
+        Err(e) => {
+            link.set_status(ConnectionStatus::Failed(address, e.to_string()));
+
+            // nothing to receive from, so wait for a new source
+            return link.wait_for_change(None);
+        }
+    };
+
+    link.set_status(ConnectionStatus::Listening(address));
+
+    let mut buffer = vec![0; PeakVoltmeterPacket::max_packet_size()];
+
+    loop {
+        if let Some(source) = link.changed() {
+            return Some(source);
+        }
+
+        // Timeouts only give the loop the chance to pick up a new source. Other receive errors
+        // are transient for UDP sockets, the next datagram is received normally.
+        if let Ok(length) = socket.recv(&mut buffer) {
+            link.output
+                .send(&PeakVoltmeterPacket::deserialize_packet(&buffer[..length]));
+        }
+    }
+}
@@ -1,34 +1,73 @@
+mod alarms;
 mod application;
+mod capture;
+mod channel;
+mod config;
+mod cycle_measurement;
+mod export;
 mod frequency_widget;
+mod harmonic_analysis;
 mod harmonics;
+mod headless;
+mod ingest;
+mod measurements;
 mod peak_sqrt_widget;
+mod playback;
+mod profiles;
+mod recorder;
+mod remote;
 mod rms_trend;
 mod rms_widget;
 mod settings;
+mod streams;
+mod three_phase;
 mod time;
 mod time_chart;
 
 use application::{calculate_precision, Application, VoltageUnit};
+use channel::MAX_CHANNELS;
+use clap::Parser;
 use conductor::{core::pipeline::Pipeline, prelude::*};
+use config::{
+    Config, HttpConfig, MeasurementConfig, MetricsConfig, ModbusConfig, RecorderConfig, ScpiConfig,
+    WebSocketConfig,
+};
 use core::f64;
+use cycle_measurement::CycleMeasurement;
 use egui::ViewportBuilder;
 use egui_plot::CoordinatesFormatter;
 use frequency_widget::frequency_widget;
+use harmonic_analysis::{harmonic_analysis, HarmonicAnalysis};
 use harmonics::harmonics;
+use headless::Output;
+use ingest::{
+    ConnectionStatus, Decoder, Demultiplexer, LinkStatistics, NetworkReceiver, SequenceTracker,
+    Transport,
+};
 use peak_sqrt_widget::peak_sqrt;
+use playback::{Playback, PlaybackSource};
+use recorder::{Recorder, RecorderTap};
 use rms_trend::rms_trend;
 use settings::{Settings, SettingsPacket};
 use std::{
+    net::SocketAddr,
+    path::PathBuf,
     sync::{
+        atomic::AtomicU64,
         mpsc::{channel, Receiver},
         Arc, RwLock,
     },
     thread,
 };
-use time_chart::time_chart;
+use streams::{Stream, Streams};
+use three_phase::{three_phase, ThreePhase, PHASES};
+use time_chart::{time_chart, TimeChartCapture};
 
 const DARK_GRAY: egui::Color32 = egui::Color32::from_rgb(60, 60, 60);
 
+// warnings kept for the user interface, without one nobody takes them over
+pub const MAX_WARNINGS: usize = 20;
+
 pub fn coordinates_formatter<'a>(unit: VoltageUnit, precision: usize) -> CoordinatesFormatter<'a> {
     CoordinatesFormatter::new(move |plot_point, bounds| {
         let x_precision = precision.max(calculate_precision(&(bounds.min()[0]..=bounds.max()[0])));
@@ -46,152 +85,501 @@ pub fn coordinates_formatter<'a>(unit: VoltageUnit, precision: usize) -> Coordin
     })
 }
 
-#[derive(Clone, Copy)]
-struct PeakVoltmeterPacket(i32);
+#[derive(Debug, Clone, Parser)]
+pub struct Command {
+    /// Config file to load and save the settings, instead of the one in the user config directory
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// How samples are received, overrides the transport in the config file
+    #[arg(short, long, value_enum)]
+    pub transport: Option<Transport>,
+
+    /// Address to listen on for samples, overrides the address in the config file
+    #[arg(short, long)]
+    pub listen: Option<SocketAddr>,
+
+    /// Address of the TCP server to connect to, overrides the address in the config file
+    #[arg(short, long)]
+    pub connect: Option<SocketAddr>,
+
+    /// Number of interleaved channels in every packet, overrides the count in the config file
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=MAX_CHANNELS as i64))]
+    pub channels: Option<u8>,
+
+    /// Plays a capture back instead of receiving samples, the channel count, sample rate and
+    /// calibration factors are taken from the capture
+    #[arg(long)]
+    pub playback: Option<PathBuf>,
+
+    /// Runs without user interface, measurements are written to the headless output
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Destination of the measurements in headless mode: `-` for stdout, `udp://host:port`,
+    /// `tcp://host:port` or a file path, overrides the output in the config file
+    #[arg(short, long)]
+    pub output: Option<Output>,
+
+    /// Starts the SCPI server on the given address, overrides the SCPI settings in the config file
+    #[arg(long)]
+    pub scpi: Option<SocketAddr>,
+
+    /// Starts the HTTP server of the JSON API on the given address, overrides the HTTP settings in
+    /// the config file
+    #[arg(long)]
+    pub http: Option<SocketAddr>,
+
+    /// Starts the WebSocket server streaming the chart data on the given address, overrides the
+    /// WebSocket settings in the config file
+    #[arg(long)]
+    pub websocket: Option<SocketAddr>,
+
+    /// Serves the Prometheus metrics on `/metrics` of the given address, overrides the metrics
+    /// settings in the config file
+    #[arg(long)]
+    pub metrics: Option<SocketAddr>,
+
+    /// Publishes the measurements to the MQTT broker at `host:port`, overrides the broker in the
+    /// config file
+    #[arg(long)]
+    pub mqtt: Option<String>,
+
+    /// Starts the Modbus TCP server on the given address, overrides the Modbus settings in the
+    /// config file
+    #[arg(long)]
+    pub modbus: Option<SocketAddr>,
+}
 
-impl From<PeakVoltmeterPacket> for f32 {
-    fn from(packet: PeakVoltmeterPacket) -> Self {
-        packet.0 as f32
-    }
+/// Chart data of a single channel.
+#[derive(Clone, Default)]
+pub struct ChannelBuffers {
+    pub time_chart: Arc<RwLock<Vec<[f64; 2]>>>,
+    // capture plotted by the time chart, for the export
+    pub time_chart_capture: Arc<RwLock<Option<TimeChartCapture>>>,
+    pub harmonics: Arc<RwLock<Vec<[f64; 2]>>>,
+    pub rms_trend: Arc<RwLock<Vec<[f64; 2]>>>,
+    pub peak_sqrt: Arc<RwLock<Vec<[f64; 2]>>>,
+    pub frequency_widget: Arc<RwLock<Vec<[f64; 2]>>>,
+    pub harmonic_analysis: Arc<RwLock<Option<HarmonicAnalysis>>>,
 }
 
-impl UdpDeserializer for PeakVoltmeterPacket {
-    fn max_packet_size() -> usize {
-        size_of::<i32>()
+/// Data shared between the pipeline and the user interface.
+#[derive(Clone)]
+pub struct Buffers {
+    pub channels: Vec<ChannelBuffers>,
+    pub three_phase: Arc<RwLock<Option<ThreePhase>>>,
+    pub link_statistics: Arc<RwLock<LinkStatistics>>,
+    pub connection_status: Arc<RwLock<ConnectionStatus>>,
+    // samples of all channels processed by the time chart, the end of the pipeline
+    pub processed_samples: Arc<AtomicU64>,
+    // measurement settings the pipeline runs with
+    pub measurement: Arc<RwLock<MeasurementConfig>>,
+    pub streams: Streams,
+    pub recorder: Recorder,
+    // capture played back instead of receiving samples
+    pub playback: Option<Playback>,
+    // warnings of the background threads, taken over by the user interface
+    pub warnings: Arc<RwLock<Vec<String>>>,
+}
+
+impl Buffers {
+    pub fn new(channels: usize, recorder: RecorderConfig, playback: Option<Playback>) -> Self {
+        let measurement = Arc::<RwLock<MeasurementConfig>>::default();
+
+        Self {
+            // every channel needs buffers of its own, cloning would share them
+            channels: (0..channels).map(|_| ChannelBuffers::default()).collect(),
+            three_phase: Arc::default(),
+            link_statistics: Arc::default(),
+            connection_status: Arc::default(),
+            processed_samples: Arc::default(),
+            recorder: Recorder::new(recorder, channels, measurement.clone()),
+            measurement,
+            streams: Streams::default(),
+            playback,
+            warnings: Arc::default(),
+        }
+    }
+
+    /// Reports a warning that occurs while running, like the warnings of the config it is printed
+    /// and shown by the user interface. Only the latest `MAX_WARNINGS` are kept.
+    pub fn warn(&self, warning: String) {
+        eprintln!("{}", warning);
+
+        let mut warnings = self.warnings.write().unwrap();
+        if warnings.len() >= MAX_WARNINGS {
+            warnings.remove(0);
+        }
+        warnings.push(warning);
     }
 
-    fn deserialize_packet(bytes: &[u8]) -> Self {
-        // Self(i32::from_be_bytes([bytes[5], bytes[6], bytes[7], 0]))
-        Self(i32::from_ne_bytes(bytes.try_into().unwrap()))
+    /// Collects one of the buffers of every channel.
+    pub fn per_channel<T>(
+        &self,
+        buffer: impl Fn(&ChannelBuffers) -> &Arc<RwLock<T>>,
+    ) -> Vec<Arc<RwLock<T>>> {
+        self.channels
+            .iter()
+            .map(|channel| buffer(channel).clone())
+            .collect()
     }
 }
 
-fn create_pipeline(
-    time_chart_buffer: Arc<RwLock<Vec<[f64; 2]>>>,
-    harmonics_buffer: Arc<RwLock<Vec<[f64; 2]>>>,
-    rms_trend_buffer: Arc<RwLock<Vec<[f64; 2]>>>,
-    peak_sqrt_buffer: Arc<RwLock<Vec<[f64; 2]>>>,
-    frequency_widget_buffer: Arc<RwLock<Vec<[f64; 2]>>>,
-    receiver: Receiver<SettingsPacket>,
-) -> Pipeline<(), ()> {
-    let settings = Settings::new(receiver);
-
-    let udp_receiver = UdpReceiver::<PeakVoltmeterPacket>::new("127.0.0.1:8080");
-
-    let into_f32 = IntoNode::<_, f32>::new();
-
-    let calibrated_signal = Multiply::new();
-
-    let time_chart = time_chart(time_chart_buffer);
-    let harmonics = harmonics(harmonics_buffer);
-    let rms_trend = rms_trend(rms_trend_buffer);
-    let peak_sqrt = peak_sqrt(peak_sqrt_buffer);
-    let frequency_widget = frequency_widget(frequency_widget_buffer);
-
-    settings.sample_rate.connect(&time_chart.input.sample_rate);
-    settings.sample_rate.connect(&harmonics.input.sample_rate.0);
-    settings.sample_rate.connect(&harmonics.input.sample_rate.1);
-    settings.sample_rate.connect(&rms_trend.input.sample_rate.0);
-    settings.sample_rate.connect(&rms_trend.input.sample_rate.1);
-    settings
-        .sample_rate
-        .connect(&frequency_widget.input.sample_rate);
+fn create_pipeline(buffers: Buffers, receiver: Receiver<SettingsPacket>) -> Pipeline<(), ()> {
+    let channels = buffers.channels.len();
+
+    let settings = Settings::new(receiver, channels, buffers.measurement.clone());
+
+    let demultiplexer = Demultiplexer::new(channels);
+
+    let time_chart = time_chart(
+        buffers.per_channel(|channel| &channel.time_chart),
+        buffers.per_channel(|channel| &channel.time_chart_capture),
+        (0..channels)
+            .map(|channel| buffers.streams.publisher(Stream::Waveform, channel))
+            .collect(),
+        buffers.processed_samples.clone(),
+    );
+
+    let recorder_tap = RecorderTap::new(buffers.recorder.clone(), channels);
+
+    // the first three channels are analysed as phases of a three-phase system
+    let three_phase = (channels >= PHASES).then(|| three_phase(buffers.three_phase.clone()));
+
+    if let Some(three_phase) = &three_phase {
+        settings.fft_window.connect(&three_phase.input.window);
+    }
 
     settings
         .time_chart_periods
         .connect(&time_chart.input.periods);
 
-    settings
-        .calibration_factor
-        .connect(&calibrated_signal.input2);
+    let mut nodes: Vec<Box<dyn NodeConfig>> = Vec::new();
 
-    settings.fft_size.connect(&harmonics.input.fft_size.0);
-    settings.fft_size.connect(&harmonics.input.fft_size.1);
-    settings.fft_size.connect(&frequency_widget.input.fft_size);
+    // a capture played back replaces the received samples
+    if let Some(playback) = buffers.playback.clone() {
+        let playback_source = PlaybackSource::new(playback, buffers.processed_samples.clone());
 
-    settings
-        .harmonics_refresh_period
-        .connect(&harmonics.input.refresh_period);
-    settings
-        .harmonics_refresh_period
-        .connect(&frequency_widget.input.refresh_period);
+        playback_source.output.connect(&demultiplexer.input);
 
-    settings.window.connect(&rms_trend.input.window);
+        nodes.push(Box::new(playback_source));
+    } else {
+        let network_receiver = NetworkReceiver::new(buffers.connection_status.clone());
 
-    settings.chart_size.connect(&rms_trend.input.chart_size);
-    settings.chart_size.connect(&peak_sqrt.input.chart_size);
-    settings
-        .chart_size
-        .connect(&frequency_widget.input.chart_size);
+        let decoder = Decoder::new(channels);
 
-    settings
-        .rms_refresh_period
-        .connect(&rms_trend.input.refresh_preiod.0);
-    settings
-        .rms_refresh_period
-        .connect(&rms_trend.input.refresh_preiod.1);
-    settings
-        .rms_refresh_period
-        .connect(&peak_sqrt.input.refresh_period);
-
-    udp_receiver.output.connect(&into_f32.input);
-
-    into_f32.output.connect(&calibrated_signal.input1);
-
-    calibrated_signal.output.connect(&time_chart.input.data.0);
-    calibrated_signal.output.connect(&time_chart.input.data.1);
-    calibrated_signal.output.connect(&harmonics.input.data);
-    calibrated_signal.output.connect(&rms_trend.input.data);
-
-    harmonics
-        .output
-        .fft_output
-        .connect(&frequency_widget.input.fft_input);
-    rms_trend
-        .output
-        .windowed_downsampled_data
-        .connect(&peak_sqrt.input.windowed_downsampled_data);
-
-    pipeline!(
-        settings,
-        udp_receiver,
-        into_f32,
-        calibrated_signal,
-        time_chart,
-        harmonics,
-        rms_trend,
-        peak_sqrt,
-        frequency_widget
-    )
+        let sequence_tracker = SequenceTracker::new(buffers.link_statistics.clone());
+
+        settings.source.connect(&network_receiver.source);
+        settings.source.connect(&sequence_tracker.source);
+        settings.packet_format.connect(&decoder.packet_format);
+        settings.sample_layout.connect(&decoder.sample_layout);
+        settings
+            .gap_handling
+            .connect(&sequence_tracker.gap_handling);
+
+        network_receiver.output.connect(&decoder.input);
+
+        decoder.output.connect(&sequence_tracker.input);
+
+        sequence_tracker.output.connect(&demultiplexer.input);
+
+        nodes.push(Box::new(network_receiver));
+        nodes.push(Box::new(decoder));
+        nodes.push(Box::new(sequence_tracker));
+    }
+
+    for (channel, channel_buffers) in buffers.channels.into_iter().enumerate() {
+        let calibrated_signal = Multiply::new();
+
+        let publisher = |stream| buffers.streams.publisher(stream, channel);
+
+        let harmonics = harmonics(channel_buffers.harmonics, publisher(Stream::Spectrum));
+        let rms_trend = rms_trend(channel_buffers.rms_trend, publisher(Stream::Rms));
+        let peak_sqrt = peak_sqrt(channel_buffers.peak_sqrt, publisher(Stream::PeakSqrt));
+        let frequency_widget = frequency_widget(
+            channel_buffers.frequency_widget,
+            publisher(Stream::Frequency),
+        );
+        let harmonic_analysis = harmonic_analysis(channel_buffers.harmonic_analysis);
+        let cycle_measurement = CycleMeasurement::new();
+
+        settings
+            .sample_rate
+            .connect(&time_chart.input.sample_rate[channel]);
+        settings.sample_rate.connect(&harmonics.input.sample_rate.0);
+        settings.sample_rate.connect(&harmonics.input.sample_rate.1);
+        settings.sample_rate.connect(&rms_trend.input.sample_rate.0);
+        settings.sample_rate.connect(&rms_trend.input.sample_rate.1);
+        settings
+            .sample_rate
+            .connect(&frequency_widget.input.sample_rate);
+        settings
+            .sample_rate
+            .connect(&harmonic_analysis.input.sample_rate);
+        settings.sample_rate.connect(&cycle_measurement.sample_rate);
+
+        settings.calibration_factors[channel].connect(&calibrated_signal.input2);
+        settings.calibration_factors[channel]
+            .connect(&time_chart.input.calibration_factor[channel]);
+
+        settings.fft_size.connect(&harmonics.input.fft_size.0);
+        settings.fft_size.connect(&harmonics.input.fft_size.1);
+        settings.fft_window.connect(&harmonics.input.window);
+        settings.fft_window.connect(&frequency_widget.input.window);
+        settings
+            .frequency_estimator
+            .connect(&frequency_widget.input.estimator);
+        settings.fft_window.connect(&harmonic_analysis.input.window);
+
+        settings
+            .harmonics_refresh_period
+            .connect(&harmonics.input.refresh_period);
+        settings
+            .harmonics_refresh_period
+            .connect(&frequency_widget.input.refresh_period);
+
+        settings
+            .rated_voltage
+            .connect(&harmonic_analysis.input.rated_voltage);
+
+        settings.window.connect(&rms_trend.input.window);
+
+        settings.measurement_mode.connect(&rms_trend.input.mode);
+        settings.measurement_mode.connect(&peak_sqrt.input.mode);
+        settings
+            .measurement_mode
+            .connect(&frequency_widget.input.mode);
+        settings.cycles.connect(&cycle_measurement.cycles);
+
+        settings.chart_size.connect(&rms_trend.input.chart_size);
+        settings.chart_size.connect(&peak_sqrt.input.chart_size);
+        settings
+            .chart_size
+            .connect(&frequency_widget.input.chart_size);
+
+        settings
+            .rms_refresh_period
+            .connect(&rms_trend.input.refresh_preiod.0);
+        settings
+            .rms_refresh_period
+            .connect(&rms_trend.input.refresh_preiod.1);
+        settings
+            .rms_refresh_period
+            .connect(&peak_sqrt.input.refresh_period);
+
+        demultiplexer.outputs[channel].connect(&calibrated_signal.input1);
+        demultiplexer.outputs[channel].connect(&recorder_tap.raw[channel]);
+
+        // the first channel is the trigger reference of the time chart
+        if channel == 0 {
+            calibrated_signal.output.connect(&time_chart.input.trigger);
+        }
+        calibrated_signal
+            .output
+            .connect(&time_chart.input.data[channel]);
+        calibrated_signal.output.connect(&harmonics.input.data);
+        calibrated_signal.output.connect(&rms_trend.input.data);
+        calibrated_signal.output.connect(&cycle_measurement.input);
+        calibrated_signal
+            .output
+            .connect(&recorder_tap.calibrated[channel]);
+
+        harmonics
+            .output
+            .spectrum
+            .connect(&frequency_widget.input.spectrum);
+        calibrated_signal
+            .output
+            .connect(&frequency_widget.input.samples);
+        harmonics
+            .output
+            .spectrum
+            .connect(&harmonic_analysis.input.spectrum);
+        rms_trend
+            .output
+            .windowed_downsampled_data
+            .connect(&peak_sqrt.input.windowed_downsampled_data);
+        cycle_measurement
+            .output
+            .connect(&rms_trend.input.cycle_values);
+        cycle_measurement
+            .output
+            .connect(&peak_sqrt.input.cycle_values);
+        cycle_measurement
+            .output
+            .connect(&frequency_widget.input.cycle_values);
+
+        if let Some(spectrum) = three_phase
+            .as_ref()
+            .and_then(|three_phase| three_phase.input.spectra.get(channel))
+        {
+            harmonics.output.spectrum.connect(spectrum);
+        }
+
+        nodes.push(Box::new(calibrated_signal));
+        nodes.push(Box::new(harmonics));
+        nodes.push(Box::new(rms_trend));
+        nodes.push(Box::new(peak_sqrt));
+        nodes.push(Box::new(frequency_widget));
+        nodes.push(Box::new(harmonic_analysis));
+        nodes.push(Box::new(cycle_measurement));
+    }
+
+    nodes.push(Box::new(settings));
+    nodes.push(Box::new(demultiplexer));
+    nodes.push(Box::new(time_chart));
+    nodes.push(Box::new(recorder_tap));
+
+    if let Some(three_phase) = three_phase {
+        nodes.push(Box::new(three_phase));
+    }
+
+    Pipeline::new(nodes, (), ())
 }
 
 fn main() {
-    let time_chart_buffer = Arc::new(RwLock::new(Vec::new()));
-    let harmonics_buffer = Arc::new(RwLock::new(Vec::new()));
-    let rms_trend_buffer = Arc::new(RwLock::new(Vec::new()));
-    let peak_sqrt_buffer = Arc::new(RwLock::new(Vec::new()));
-    let frequency_widget_buffer = Arc::new(RwLock::new(Vec::new()));
+    let command = Command::parse();
+
+    let config_path = command.config.clone().or_else(Config::default_path);
+
+    let (config, mut warnings) = match &config_path {
+        Some(path) => Config::load(path).unwrap_or_else(|e| {
+            (
+                Config::default(),
+                vec![format!(
+                    "Failed to load config {}, using the defaults: {}",
+                    path.display(),
+                    e
+                )],
+            )
+        }),
+        None => (Config::default(), Vec::new()),
+    };
+
+    let mut ingest = config.ingest;
+
+    if let Some(transport) = command.transport {
+        ingest.transport = transport;
+    }
+    if let Some(listen_address) = command.listen {
+        ingest.listen_address = listen_address;
+    }
+    if let Some(connect_address) = command.connect {
+        ingest.connect_address = connect_address;
+    }
+
+    let playback = command.playback.map(|path| {
+        Playback::open(&path).unwrap_or_else(|e| {
+            eprintln!("Failed to open capture {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+
+    let channels = playback
+        .as_ref()
+        .map(Playback::channels)
+        .or(command.channels.map(|channels| channels as usize))
+        .unwrap_or(config.channels)
+        .clamp(1, MAX_CHANNELS);
+
+    let buffers = Buffers::new(channels, config.recorder.clone(), playback);
 
     let (sender, receiver) = channel();
 
-    let time_chart_buffer_cloned = time_chart_buffer.clone();
-    let harmonics_buffer_cloned = harmonics_buffer.clone();
-    let rms_trend_buffer_cloned = rms_trend_buffer.clone();
-    let peak_sqrt_buffer_cloned = peak_sqrt_buffer.clone();
-    let frequency_widget_buffer_cloned = frequency_widget_buffer.clone();
+    let buffers_cloned = buffers.clone();
 
     thread::spawn(move || {
-        create_pipeline(
-            time_chart_buffer_cloned,
-            harmonics_buffer_cloned,
-            rms_trend_buffer_cloned,
-            peak_sqrt_buffer_cloned,
-            frequency_widget_buffer_cloned,
-            receiver,
-        )
-        .run();
+        create_pipeline(buffers_cloned, receiver).run();
     });
 
+    // command line overrides of the remote interfaces are not persisted
+    let mut remote_config = config.clone();
+
+    if let Some(listen_address) = command.scpi {
+        remote_config.scpi = ScpiConfig {
+            enabled: true,
+            listen_address,
+        };
+    }
+
+    if let Some(listen_address) = command.http {
+        remote_config.http = HttpConfig {
+            enabled: true,
+            listen_address,
+        };
+    }
+
+    if let Some(listen_address) = command.websocket {
+        remote_config.websocket = WebSocketConfig {
+            enabled: true,
+            listen_address,
+        };
+    }
+
+    if let Some(listen_address) = command.metrics {
+        remote_config.metrics = MetricsConfig {
+            enabled: true,
+            listen_address,
+        };
+    }
+
+    if let Some(broker) = command.mqtt {
+        remote_config.mqtt.enabled = true;
+        remote_config.mqtt.broker = broker;
+    }
+
+    if let Some(listen_address) = command.modbus {
+        remote_config.modbus = ModbusConfig {
+            enabled: true,
+            listen_address,
+        };
+    }
+
+    let (remote_sender, remote_receiver) = channel();
+
+    warnings.extend(remote::start(&remote_config, &buffers, &remote_sender));
+
+    for warning in &warnings {
+        eprintln!("{}", warning);
+    }
+
+    if command.headless {
+        for packet in ingest
+            .settings_packets()
+            .into_iter()
+            .chain(config.measurement.settings_packets(channels))
+            .chain(buffers.playback.iter().flat_map(Playback::settings_packets))
+        {
+            sender.send(packet).unwrap();
+        }
+
+        // without user interface the remote settings go straight to the pipeline
+        let remote_settings = sender.clone();
+        thread::spawn(move || {
+            for packet in remote_receiver {
+                if remote_settings.send(packet).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut headless = config.headless;
+
+        if let Some(output) = command.output {
+            headless.output = output;
+        }
+
+        // the sender stays alive while the measurements are written, the pipeline stops without it
+        if let Err(e) = headless::run(buffers, &headless) {
+            eprintln!("Failed to open {}: {}", headless.output, e);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     let viewport = ViewportBuilder::default().with_fullscreen(true);
 
     let options = eframe::NativeOptions {
@@ -202,14 +590,40 @@ fn main() {
     eframe::run_native(
         "Plotter",
         options,
-        Box::new(|_cc| {
+        Box::new(|cc| {
+            // the remote settings go straight to the pipeline as well, the user interface takes
+            // them over with the frame requested for them
+            let (remote_changes_sender, remote_changes) = channel();
+            let remote_settings = sender.clone();
+            let playback = buffers.playback.clone();
+            let ctx = cc.egui_ctx.clone();
+            thread::spawn(move || {
+                for packet in remote_receiver {
+                    if remote_settings.send(packet.clone()).is_err() {
+                        break;
+                    }
+
+                    // the settings of the capture played back take precedence
+                    if let Some(playback) = &playback {
+                        let _ = remote_settings
+                            .send(SettingsPacket::Batch(playback.settings_packets()));
+                    }
+
+                    if remote_changes_sender.send(packet).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            });
+
             Ok(Box::new(Application::new(
-                time_chart_buffer,
-                harmonics_buffer,
-                rms_trend_buffer,
-                peak_sqrt_buffer,
-                frequency_widget_buffer,
+                buffers,
+                config,
+                config_path,
+                warnings,
+                ingest,
                 sender,
+                remote_changes,
             )))
         }),
     )
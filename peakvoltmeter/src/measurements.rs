@@ -0,0 +1,200 @@
+use crate::{settings::Channel, three_phase::ThreePhase, Buffers, ChannelBuffers};
+use chrono::Local;
+use serde::Serialize;
+use std::sync::{atomic::Ordering, Arc, RwLock};
+
+// harmonics reported per channel, including the fundamental
+pub const HARMONICS: usize = 50;
+
+fn last_value(data: &Arc<RwLock<Vec<[f64; 2]>>>) -> Option<f64> {
+    data.read().unwrap().last().map(|point| point[1])
+}
+
+/// Smallest and largest value of a chart.
+fn value_range(data: &Arc<RwLock<Vec<[f64; 2]>>>) -> Option<[f64; 2]> {
+    data.read()
+        .unwrap()
+        .iter()
+        .map(|point| [point[1], point[1]])
+        .reduce(|range, value| [range[0].min(value[0]), range[1].max(value[1])])
+}
+
+/// Levels of the fundamental and its harmonics up to the nyquist frequency, taken from the
+/// spectrum of the harmonics chart, in dBV. The spectrum starts at 0 Hz, so the line of a
+/// frequency is found by its index.
+fn harmonic_levels(spectrum: &Arc<RwLock<Vec<[f64; 2]>>>, fundamental: Option<f64>) -> Vec<f64> {
+    let spectrum = spectrum.read().unwrap();
+
+    let (Some(fundamental), Some(bin)) = (fundamental, spectrum.get(1)) else {
+        return Vec::new();
+    };
+
+    let bin_width = bin[0];
+
+    if fundamental <= 0.0 || bin_width <= 0.0 {
+        return Vec::new();
+    }
+
+    (1..=HARMONICS)
+        .map_while(|harmonic| {
+            let index = (harmonic as f64 * fundamental / bin_width).round() as usize;
+
+            // the neighbouring lines catch a harmonic between two lines
+            let lines = spectrum.get(index.saturating_sub(1)..(index + 2).min(spectrum.len()))?;
+
+            lines.iter().map(|line| line[1]).reduce(f64::max)
+        })
+        .collect()
+}
+
+#[derive(Clone, Serialize)]
+pub struct ChannelMeasurements {
+    pub channel: Channel,
+    // volts, `None` until the first value has been computed
+    pub rms: Option<f64>,
+    pub peak_sqrt: Option<f64>,
+    // hertz
+    pub frequency: Option<f64>,
+    // percent of the fundamental and of the rated voltage
+    pub thd: Option<f64>,
+    pub tdd: Option<f64>,
+    // minimum and maximum over the chart size
+    pub rms_range: Option<[f64; 2]>,
+    pub peak_sqrt_range: Option<[f64; 2]>,
+    pub frequency_range: Option<[f64; 2]>,
+    // dBV of the largest line around every harmonic, starting with the fundamental
+    pub harmonics: Vec<f64>,
+    // rms volts of the harmonic subgroups, starting with the fundamental
+    pub harmonic_rms: Vec<f64>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ThreePhaseMeasurements {
+    // rms volts and degrees relative to the first phase
+    pub magnitudes: [f64; 3],
+    pub angles: [f64; 3],
+    pub line_to_line: [f64; 3],
+    pub positive_sequence: f64,
+    pub negative_sequence: f64,
+    pub zero_sequence: f64,
+    // percent
+    pub unbalance: f64,
+}
+
+impl From<ThreePhase> for ThreePhaseMeasurements {
+    fn from(three_phase: ThreePhase) -> Self {
+        Self {
+            magnitudes: three_phase.phases.map(|phasor| phasor.norm()),
+            angles: three_phase.phases.map(|phasor| phasor.arg().to_degrees()),
+            line_to_line: three_phase.line_to_line,
+            positive_sequence: three_phase.positive_sequence.norm(),
+            negative_sequence: three_phase.negative_sequence.norm(),
+            zero_sequence: three_phase.zero_sequence.norm(),
+            unbalance: three_phase.unbalance,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct LinkMeasurements {
+    pub received: u64,
+    pub lost: u64,
+    pub duplicated: u64,
+    pub reordered: u64,
+    pub malformed: u64,
+    pub resets: u64,
+    // samples of all channels
+    pub samples: u64,
+    pub filled_samples: u64,
+    // samples received but not yet processed by the pipeline
+    pub pipeline_lag: u64,
+    pub status: String,
+}
+
+/// Latest values of all measurements, taken from the buffers shared with the pipeline.
+#[derive(Clone, Serialize)]
+pub struct Measurements {
+    // RFC 3339 local time
+    pub timestamp: String,
+    pub channels: Vec<ChannelMeasurements>,
+    pub three_phase: Option<ThreePhaseMeasurements>,
+    pub link: LinkMeasurements,
+}
+
+impl Measurements {
+    pub fn read(buffers: &Buffers) -> Self {
+        let channels = buffers
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(channel, channel_buffers)| {
+                let frequency = last_value(&channel_buffers.frequency_widget);
+
+                let harmonics = harmonic_levels(&channel_buffers.harmonics, frequency);
+                let analysis = channel_buffers.harmonic_analysis.read().unwrap().clone();
+
+                ChannelMeasurements {
+                    channel,
+                    rms: last_value(&channel_buffers.rms_trend),
+                    peak_sqrt: last_value(&channel_buffers.peak_sqrt),
+                    frequency,
+                    thd: analysis.as_ref().map(|analysis| analysis.thd),
+                    tdd: analysis.as_ref().map(|analysis| analysis.tdd),
+                    rms_range: value_range(&channel_buffers.rms_trend),
+                    peak_sqrt_range: value_range(&channel_buffers.peak_sqrt),
+                    frequency_range: value_range(&channel_buffers.frequency_widget),
+                    harmonics,
+                    harmonic_rms: analysis
+                        .map(|analysis| analysis.harmonics)
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        let statistics = *buffers.link_statistics.read().unwrap();
+
+        Self {
+            timestamp: Local::now().to_rfc3339(),
+            channels,
+            three_phase: buffers.three_phase.read().unwrap().map(Into::into),
+            link: LinkMeasurements {
+                received: statistics.received,
+                lost: statistics.lost,
+                duplicated: statistics.duplicated,
+                reordered: statistics.reordered,
+                malformed: statistics.malformed,
+                resets: statistics.resets,
+                samples: statistics.samples,
+                filled_samples: statistics.filled_samples,
+                pipeline_lag: (statistics.samples + statistics.filled_samples)
+                    .saturating_sub(buffers.processed_samples.load(Ordering::Relaxed)),
+                status: buffers.connection_status.read().unwrap().to_string(),
+            },
+        }
+    }
+}
+
+/// Chart data of a single channel, as `[x, y]` points.
+#[derive(Clone, Serialize)]
+pub struct Trace {
+    pub channel: Channel,
+    pub points: Vec<[f64; 2]>,
+}
+
+impl Trace {
+    /// Copies one of the buffers of every channel.
+    pub fn read(
+        buffers: &Buffers,
+        buffer: impl Fn(&ChannelBuffers) -> &Arc<RwLock<Vec<[f64; 2]>>>,
+    ) -> Vec<Self> {
+        buffers
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(channel, channel_buffers)| Trace {
+                channel,
+                points: buffer(channel_buffers).read().unwrap().clone(),
+            })
+            .collect()
+    }
+}
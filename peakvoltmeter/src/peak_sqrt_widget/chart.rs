@@ -1,13 +1,20 @@
-use crate::settings::{ChartSize, RefreshPeriod};
+use crate::{
+    cycle_measurement::{CycleValues, MeasurementMode},
+    settings::{ChartSize, RefreshPeriod},
+    streams::Publisher,
+};
 
 use conductor::prelude::*;
 use std::sync::{Arc, RwLock};
 
 struct ChartRunner {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 
     windowed_downsampled_data: NodeRunnerInputPort<Vec<f32>>,
+    cycle_values: NodeRunnerInputPort<Option<CycleValues>>,
 
+    mode: NodeRunnerInputPort<MeasurementMode>,
     chart_size: NodeRunnerInputPort<ChartSize>,
     refresh_period: NodeRunnerInputPort<RefreshPeriod>,
 }
@@ -22,18 +29,34 @@ impl NodeRunner for ChartRunner {
             (chart_size as f32 / refresh_period) as usize + 1
         }
 
+        let mut mode = self.mode.recv();
         let mut chart_size = self.chart_size.recv();
         let mut refresh_period = self.refresh_period.recv();
 
         let mut peak_sqrt_data =
             CircularBuffer::new(calculate_buffer_size(chart_size, refresh_period));
+        // latest values of the whole cycles, taken over at the pace of the windows
+        let mut cycle_values = None;
 
         loop {
             receive! {
                 (self.windowed_downsampled_data): buffer => {
-                    let peak = buffer.into_iter().fold(f32::MIN, f32::max);
+                    let peak = match mode {
+                        MeasurementMode::Window => buffer.into_iter().fold(f32::MIN, f32::max) as f64,
+                        MeasurementMode::Cycles => {
+                            let Some(CycleValues { peak, .. }) = cycle_values else {
+                                continue;
+                            };
 
-                    peak_sqrt_data.push((peak as f64) / 2.0_f64.sqrt());
+                            peak
+                        }
+                    };
+
+                    let peak_sqrt = peak / 2.0_f64.sqrt();
+
+                    peak_sqrt_data.push(peak_sqrt);
+
+                    self.publisher.publish(0.0, || vec![peak_sqrt]);
 
                     *self.data.write().unwrap() = peak_sqrt_data
                         .clone()
@@ -42,6 +65,15 @@ impl NodeRunner for ChartRunner {
                         .map(|(i, v)| [index_to_time(i, peak_sqrt_data.len(), refresh_period), v])
                         .collect();
                 },
+                (self.cycle_values): new_cycle_values => {
+                    cycle_values = new_cycle_values;
+                },
+                (self.mode): new_mode => {
+                    mode = new_mode;
+
+                    // previous data is invalidated so new buffer must be created
+                    peak_sqrt_data = CircularBuffer::new(calculate_buffer_size(chart_size, refresh_period));
+                },
                 (self.chart_size): new_chart_size => {
                     chart_size = new_chart_size;
 
@@ -60,20 +92,26 @@ impl NodeRunner for ChartRunner {
 
 pub struct Chart {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 
     pub windowed_downsampled_data: NodeConfigInputPort<Vec<f32>>,
+    pub cycle_values: NodeConfigInputPort<Option<CycleValues>>,
 
+    pub mode: NodeConfigInputPort<MeasurementMode>,
     pub chart_size: NodeConfigInputPort<ChartSize>,
     pub refresh_period: NodeConfigInputPort<RefreshPeriod>,
 }
 
 impl Chart {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>, publisher: Publisher) -> Self {
         Self {
             data,
+            publisher,
 
             windowed_downsampled_data: NodeConfigInputPort::new(),
+            cycle_values: NodeConfigInputPort::new(),
 
+            mode: NodeConfigInputPort::new(),
             chart_size: NodeConfigInputPort::new(),
             refresh_period: NodeConfigInputPort::new(),
         }
@@ -84,9 +122,12 @@ impl NodeConfig for Chart {
     fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
         Box::new(ChartRunner {
             data: self.data,
+            publisher: self.publisher,
 
             windowed_downsampled_data: self.windowed_downsampled_data.into(),
+            cycle_values: self.cycle_values.into(),
 
+            mode: self.mode.into(),
             chart_size: self.chart_size.into(),
             refresh_period: self.refresh_period.into(),
         })
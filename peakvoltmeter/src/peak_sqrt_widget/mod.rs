@@ -2,8 +2,11 @@ mod chart;
 
 use crate::{
     application::{calculate_precision, Precision, VoltageUnit},
+    channel::{channel_color, channel_name, channel_views, ChannelLayout},
     coordinates_formatter,
-    settings::{ChartSize, RefreshPeriod},
+    cycle_measurement::{CycleValues, MeasurementMode},
+    settings::{Channel, ChartSize, RefreshPeriod},
+    streams::Publisher,
     DARK_GRAY,
 };
 use chart::Chart;
@@ -16,15 +19,23 @@ use std::sync::{Arc, RwLock};
 
 pub struct PeakSqrtInputPorts {
     pub windowed_downsampled_data: NodeConfigInputPort<Vec<f32>>,
+    // values of the whole cycles, shown instead of the windows in the cycles mode
+    pub cycle_values: NodeConfigInputPort<Option<CycleValues>>,
+    pub mode: NodeConfigInputPort<MeasurementMode>,
     pub chart_size: NodeConfigInputPort<ChartSize>,
     pub refresh_period: NodeConfigInputPort<RefreshPeriod>,
 }
 
-pub fn peak_sqrt(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Pipeline<PeakSqrtInputPorts, ()> {
-    let chart = Chart::new(data);
+pub fn peak_sqrt(
+    data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
+) -> Pipeline<PeakSqrtInputPorts, ()> {
+    let chart = Chart::new(data, publisher);
 
     let input_ports = PeakSqrtInputPorts {
         windowed_downsampled_data: chart.windowed_downsampled_data.clone(),
+        cycle_values: chart.cycle_values.clone(),
+        mode: chart.mode.clone(),
         chart_size: chart.chart_size.clone(),
         refresh_period: chart.refresh_period.clone(),
     };
@@ -33,13 +44,14 @@ pub fn peak_sqrt(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Pipeline<PeakSqrtInputPort
 }
 
 pub struct PeakSqrtChart {
-    data: Arc<RwLock<Vec<[f64; 2]>>>,
+    // one per channel
+    data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>,
 
     prev_chart_size: f64,
 }
 
 impl PeakSqrtChart {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>) -> Self {
         Self {
             data,
             prev_chart_size: f64::NEG_INFINITY,
@@ -49,6 +61,7 @@ impl PeakSqrtChart {
     pub fn ui(
         &mut self,
         ui: &mut egui::Ui,
+        layout: ChannelLayout,
         chart_size: ChartSize,
         unit: VoltageUnit,
         precision: Precision,
@@ -72,74 +85,91 @@ impl PeakSqrtChart {
 
                     ui.label(RichText::new("Vp / √2").size(16.0));
 
-                    let last_value = self
-                        .data
-                        .read()
-                        .unwrap()
-                        .last()
-                        .map(|v| v[1])
-                        .unwrap_or(0.0);
-
-                    ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
-                        ui.label(
-                            RichText::new(unit.apply_unit_with_precision(last_value, precision))
-                                .size(30.0)
-                                .strong(),
-                        );
-                    });
-
                     let chart_size = chart_size as f64;
 
-                    let mut plot = Plot::new("Peak Sqrt Widget")
-                        .auto_bounds(Vec2b::new(false, true))
-                        .y_axis_label("Voltage")
-                        .x_axis_label("Time")
-                        .allow_boxed_zoom(false)
-                        .allow_drag(false)
-                        .allow_zoom(false)
-                        .allow_scroll(false)
-                        .label_formatter(|_, _| "".to_owned())
-                        .coordinates_formatter(
-                            egui_plot::Corner::LeftTop,
-                            coordinates_formatter(unit, precision),
-                        )
-                        .x_axis_formatter(|grid_mark, range| {
-                            format!(
-                                "{:.precision$} s",
-                                grid_mark.value,
-                                precision = calculate_precision(range)
-                            )
-                        })
-                        .y_axis_formatter(|grid_mark, range| {
-                            unit.apply_unit_with_precision(
-                                grid_mark.value,
-                                calculate_precision(range),
+                    // We need to check if the chart size has changed to reset the plots, otherwise the
+                    // plots will not update the chart size.
+                    let reset = (self.prev_chart_size - chart_size).abs() > f64::EPSILON;
+                    self.prev_chart_size = chart_size;
+
+                    channel_views(ui, layout, self.data.len(), |ui, channels| {
+                        for channel in channels.clone() {
+                            let last_value = self.data[channel]
+                                .read()
+                                .unwrap()
+                                .last()
+                                .map(|v| v[1])
+                                .unwrap_or(0.0);
+
+                            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                                ui.label(
+                                    RichText::new(
+                                        unit.apply_unit_with_precision(last_value, precision),
+                                    )
+                                    .size(30.0)
+                                    .strong(),
+                                );
+
+                                if self.data.len() > 1 {
+                                    ui.label(
+                                        RichText::new(channel_name(channel))
+                                            .size(16.0)
+                                            .color(channel_color(channel)),
+                                    );
+                                }
+                            });
+                        }
+
+                        let mut plot = Plot::new(("Peak Sqrt Widget", channels.start))
+                            .auto_bounds(Vec2b::new(false, true))
+                            .y_axis_label("Voltage")
+                            .x_axis_label("Time")
+                            .allow_boxed_zoom(false)
+                            .allow_drag(false)
+                            .allow_zoom(false)
+                            .allow_scroll(false)
+                            .label_formatter(|_, _| "".to_owned())
+                            .coordinates_formatter(
+                                egui_plot::Corner::LeftTop,
+                                coordinates_formatter(unit, precision),
                             )
-                        })
-                        .include_y(0.0)
-                        .include_x(0.0)
-                        .include_x(-chart_size);
-
-                    // We need to check if the chart size has changed to reset the plot, otherwise the
-                    // plot will not update the chart size.
-                    if (self.prev_chart_size - chart_size).abs() > f64::EPSILON {
-                        plot = plot.reset();
-                        self.prev_chart_size = chart_size;
-                    }
-
-                    plot.show(ui, |plot_ui| {
-                        plot_ui.line(self.signal());
+                            .x_axis_formatter(|grid_mark, range| {
+                                format!(
+                                    "{:.precision$} s",
+                                    grid_mark.value,
+                                    precision = calculate_precision(range)
+                                )
+                            })
+                            .y_axis_formatter(|grid_mark, range| {
+                                unit.apply_unit_with_precision(
+                                    grid_mark.value,
+                                    calculate_precision(range),
+                                )
+                            })
+                            .include_y(0.0)
+                            .include_x(0.0)
+                            .include_x(-chart_size);
+
+                        if reset {
+                            plot = plot.reset();
+                        }
+
+                        plot.show(ui, |plot_ui| {
+                            for channel in channels {
+                                plot_ui.line(self.signal(channel));
+                            }
+                        });
                     });
                 });
             },
         );
     }
 
-    fn signal(&self) -> Line {
-        let plot_points = PlotPoints::from_iter(self.data.read().unwrap().clone());
+    fn signal(&self, channel: Channel) -> Line {
+        let plot_points = PlotPoints::from_iter(self.data[channel].read().unwrap().clone());
 
         Line::new(plot_points)
-            .color(Color32::LIGHT_BLUE)
-            .name("Signal")
+            .color(channel_color(channel))
+            .name(channel_name(channel))
     }
 }
@@ -0,0 +1,382 @@
+mod source;
+
+use crate::{
+    capture::{CaptureHeader, CaptureReader},
+    config::{ExportConfig, EVENT_WINDOW_RANGE},
+    export::{export_event, result_ui},
+    settings::{SampleRate, SettingsPacket},
+};
+use egui::{Color32, RichText};
+pub use source::PlaybackSource;
+use std::{
+    io,
+    ops::{Range, RangeInclusive},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+pub const SPEEDS: [f32; 5] = [1.0, 2.0, 5.0, 10.0, 50.0];
+
+const STEP_DEFAULT: f32 = 0.1;
+const STEP_RANGE: RangeInclusive<f32> = 0.001..=10.0;
+
+// frames read at once, about the size of a packet
+const BLOCK_FRAMES: u64 = 64;
+// frames that became due while the pipeline could not keep up are played at most this late
+const MAX_DUE_PERIOD: f64 = 0.1;
+
+struct PlaybackState {
+    reader: CaptureReader,
+    // frame played next
+    position: u64,
+    playing: bool,
+    speed: f32,
+    looping: bool,
+    // frames still to be played while paused
+    step: u64,
+    // frames due at the speed but not played yet
+    due: f64,
+    error: Option<String>,
+}
+
+/// A capture played back instead of the received samples, shared between the pipeline and the
+/// user interface.
+#[derive(Clone)]
+pub struct Playback {
+    path: PathBuf,
+    header: CaptureHeader,
+    frames: u64,
+
+    state: Arc<Mutex<PlaybackState>>,
+}
+
+impl Playback {
+    /// Opens a capture, it starts playing at real time.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let reader = CaptureReader::open(path)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            header: reader.header().clone(),
+            frames: reader.frames(),
+            state: Arc::new(Mutex::new(PlaybackState {
+                reader,
+                position: 0,
+                playing: true,
+                speed: SPEEDS[0],
+                looping: false,
+                step: 0,
+                due: 0.0,
+                error: None,
+            })),
+        })
+    }
+
+    pub fn channels(&self) -> usize {
+        self.header.channels.len()
+    }
+
+    pub fn sample_rate(&self) -> SampleRate {
+        self.header.sample_rate
+    }
+
+    /// Sample rate and calibration factors of the capture, they replace the measurement settings
+    /// while playing back.
+    pub fn settings_packets(&self) -> Vec<SettingsPacket> {
+        let mut packets = vec![SettingsPacket::SampleRate(self.header.sample_rate)];
+
+        packets.extend(
+            self.header
+                .channels
+                .iter()
+                .enumerate()
+                .map(|(channel, description)| {
+                    SettingsPacket::CalibrationFactor(channel, description.calibration_factor)
+                }),
+        );
+
+        packets
+    }
+
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    pub fn to_seconds(&self, frames: u64) -> f64 {
+        frames as f64 / self.header.sample_rate as f64
+    }
+
+    /// Frame at the given seconds, limited to the capture.
+    pub fn to_frames(&self, seconds: f64) -> u64 {
+        ((seconds.max(0.0) * self.header.sample_rate as f64).round() as u64).min(self.frames)
+    }
+
+    /// Length of the capture in seconds.
+    pub fn duration(&self) -> f64 {
+        self.to_seconds(self.frames)
+    }
+
+    /// Seconds played so far.
+    pub fn position(&self) -> f64 {
+        self.to_seconds(self.state.lock().unwrap().position)
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state.lock().unwrap().playing
+    }
+
+    /// Continues the playback, from the start if it has ended.
+    pub fn play(&self) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.position >= self.frames {
+            state.position = 0;
+        }
+
+        state.playing = true;
+        state.step = 0;
+        state.error = None;
+    }
+
+    pub fn pause(&self) {
+        self.state.lock().unwrap().playing = false;
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.state.lock().unwrap().speed
+    }
+
+    pub fn set_speed(&self, speed: f32) {
+        self.state.lock().unwrap().speed = speed;
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.state.lock().unwrap().looping
+    }
+
+    pub fn set_looping(&self, looping: bool) {
+        self.state.lock().unwrap().looping = looping;
+    }
+
+    pub fn seek(&self, seconds: f64) {
+        let mut state = self.state.lock().unwrap();
+
+        state.position = self.to_frames(seconds);
+        state.step = 0;
+    }
+
+    /// Plays the given seconds while paused.
+    pub fn step(&self, seconds: f64) {
+        let mut state = self.state.lock().unwrap();
+
+        if !state.playing {
+            state.step += self.to_frames(seconds);
+        }
+    }
+
+    /// Raw samples of the frames in the range, interleaved like the samples of a packet. The
+    /// position of the playback is left as it is.
+    pub fn read(&self, frames: Range<u64>) -> io::Result<Vec<f32>> {
+        let mut state = self.state.lock().unwrap();
+
+        let mut samples = Vec::new();
+        let mut frame = frames.start;
+
+        while frame < frames.end {
+            let block = state.reader.read(frame, frames.end - frame)?;
+
+            if block.is_empty() {
+                break;
+            }
+
+            frame += (block.len() / self.channels()) as u64;
+            samples.extend(block);
+        }
+
+        Ok(samples)
+    }
+
+    /// Raw samples due after `elapsed`, interleaved like the samples of a packet. At the end of
+    /// the capture the playback starts over when looping and pauses otherwise.
+    fn next_samples(&self, elapsed: Duration) -> Vec<f32> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.position >= self.frames {
+            if state.looping && self.frames > 0 {
+                state.position = 0;
+            } else {
+                state.playing = false;
+                state.step = 0;
+            }
+        }
+
+        let count = if state.playing {
+            let rate = self.header.sample_rate as f64 * state.speed as f64;
+
+            state.due = (state.due + elapsed.as_secs_f64() * rate).min(rate * MAX_DUE_PERIOD);
+            state.due as u64
+        } else {
+            state.due = 0.0;
+            state.step
+        };
+
+        let count = count.min(BLOCK_FRAMES);
+
+        if count == 0 {
+            return Vec::new();
+        }
+
+        let position = state.position;
+
+        let samples = match state.reader.read(position, count) {
+            Ok(samples) => samples,
+            Err(e) => {
+                state.playing = false;
+                state.error = Some(format!("Playback stopped: {}", e));
+                return Vec::new();
+            }
+        };
+
+        let frames = (samples.len() / self.channels()) as u64;
+
+        state.position += frames;
+
+        if state.playing {
+            state.due -= frames as f64;
+        } else {
+            state.step -= frames.min(state.step);
+        }
+
+        samples
+    }
+}
+
+/// Controls of the playback, below the charts.
+pub struct PlaybackBar {
+    playback: Playback,
+    // seconds played per step
+    step: f32,
+
+    // event of the last export
+    export_result: Option<Result<PathBuf, String>>,
+}
+
+impl PlaybackBar {
+    pub fn new(playback: Playback) -> Self {
+        Self {
+            playback,
+            step: STEP_DEFAULT,
+            export_result: None,
+        }
+    }
+
+    pub fn playback(&self) -> &Playback {
+        &self.playback
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, export: &mut ExportConfig) {
+        let playback = &self.playback;
+
+        ui.horizontal(|ui| {
+            if playback.is_playing() {
+                if ui.button("Pause").clicked() {
+                    playback.pause();
+                }
+            } else if ui.button("Play").clicked() {
+                playback.play();
+            }
+
+            ui.add_enabled_ui(!playback.is_playing(), |ui| {
+                if ui.button("Step").clicked() {
+                    playback.step(self.step as f64);
+                }
+
+                ui.add(
+                    egui::DragValue::new(&mut self.step)
+                        .range(STEP_RANGE)
+                        .speed(0.01)
+                        .suffix(" s"),
+                );
+            });
+
+            let speed = playback.speed();
+
+            egui::ComboBox::from_label("Speed")
+                .selected_text(format!("{}×", speed))
+                .show_ui(ui, |ui| {
+                    for option in SPEEDS {
+                        if ui
+                            .selectable_label(speed == option, format!("{}×", option))
+                            .clicked()
+                        {
+                            playback.set_speed(option);
+                        }
+                    }
+                });
+
+            let mut looping = playback.is_looping();
+            if ui.checkbox(&mut looping, "Loop").changed() {
+                playback.set_looping(looping);
+            }
+
+            ui.separator();
+
+            let duration = playback.duration();
+            let mut position = playback.position();
+
+            ui.spacing_mut().slider_width = (ui.available_width() - 500.0).max(100.0);
+
+            if ui
+                .add(
+                    egui::Slider::new(&mut position, 0.0..=duration)
+                        .suffix(" s")
+                        .fixed_decimals(2),
+                )
+                .changed()
+            {
+                playback.seek(position);
+            }
+
+            ui.label(format!("/ {:.2} s", duration));
+
+            ui.separator();
+
+            if ui
+                .button("Export Event")
+                .on_hover_text(format!(
+                    "COMTRADE {} around the playback position",
+                    export.comtrade_format
+                ))
+                .clicked()
+            {
+                self.export_result =
+                    Some(export_event(playback, export).map_err(|e| e.to_string()));
+            }
+
+            ui.add(
+                egui::DragValue::new(&mut export.event_window)
+                    .range(EVENT_WINDOW_RANGE)
+                    .speed(0.01)
+                    .suffix(" s"),
+            );
+
+            result_ui(ui, &self.export_result);
+
+            ui.separator();
+
+            let name = playback
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            ui.label(RichText::new(name).strong())
+                .on_hover_text(format!("Started {}", playback.header.start_time));
+
+            if let Some(error) = &playback.state.lock().unwrap().error {
+                ui.colored_label(Color32::RED, error);
+            }
+        });
+    }
+}
@@ -0,0 +1,92 @@
+use super::Playback;
+use crate::ingest::Frame;
+use conductor::prelude::*;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// how long the source waits when no samples are due
+const IDLE_INTERVAL: Duration = Duration::from_millis(5);
+// frames the source may be ahead of the end of the pipeline, so fast playback does not flood it
+const MAX_FRAMES_AHEAD: u64 = 8192;
+
+struct PlaybackSourceRunner {
+    playback: Playback,
+    processed_samples: Arc<AtomicU64>,
+
+    output: NodeRunnerOutputPort<Frame>,
+}
+
+impl NodeRunner for PlaybackSourceRunner {
+    fn run(self: Box<Self>) {
+        let channels = self.playback.channels();
+
+        let processed_start = self.processed_samples.load(Ordering::Relaxed);
+        let mut emitted: u64 = 0;
+
+        let mut clock = Instant::now();
+
+        loop {
+            let processed = self.processed_samples.load(Ordering::Relaxed) - processed_start;
+
+            // the time keeps running, the due frames are played once the pipeline caught up
+            if emitted.saturating_sub(processed) > MAX_FRAMES_AHEAD * channels as u64 {
+                thread::sleep(IDLE_INTERVAL);
+                continue;
+            }
+
+            let now = Instant::now();
+            let samples = self.playback.next_samples(now - clock);
+            clock = now;
+
+            if samples.is_empty() {
+                thread::sleep(IDLE_INTERVAL);
+                continue;
+            }
+
+            emitted += samples.len() as u64;
+
+            self.output.send(&Frame {
+                sequence: None,
+                channels,
+                samples,
+            });
+        }
+    }
+}
+
+/// Plays a capture into the pipeline in place of the network receiver, paced by the sample rate
+/// of the capture and the speed of the playback.
+pub struct PlaybackSource {
+    playback: Playback,
+    processed_samples: Arc<AtomicU64>,
+
+    pub output: NodeConfigOutputPort<Frame>,
+}
+
+impl PlaybackSource {
+    pub fn new(playback: Playback, processed_samples: Arc<AtomicU64>) -> Self {
+        Self {
+            playback,
+            processed_samples,
+
+            output: NodeConfigOutputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for PlaybackSource {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(PlaybackSourceRunner {
+            playback: self.playback,
+            processed_samples: self.processed_samples,
+
+            output: self.output.into(),
+        })
+    }
+}
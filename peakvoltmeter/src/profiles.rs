@@ -0,0 +1,131 @@
+use crate::config::{Config, MeasurementConfig, Profile};
+use egui::Key;
+
+const PROFILE_NONE: &str = "No Profile";
+
+/// Selects, creates, duplicates, renames and deletes the measurement profiles in the top panel.
+pub struct ProfileSelector {
+    // new name while the active profile is renamed
+    renaming: Option<String>,
+}
+
+impl ProfileSelector {
+    pub fn new() -> Self {
+        Self { renaming: None }
+    }
+
+    fn select(config: &mut Config, index: usize) {
+        config.profile = Some(config.profiles[index].name.clone());
+        config.measurement = config.profiles[index].measurement.clone();
+    }
+
+    fn add(config: &mut Config, name: &str, measurement: MeasurementConfig) {
+        config.profiles.push(Profile {
+            name: config.unique_profile_name(name),
+            measurement,
+        });
+
+        Self::select(config, config.profiles.len() - 1);
+    }
+
+    /// Returns `true` if the measurement settings of the config have been replaced by a profile,
+    /// they then have to be pushed into the pipeline.
+    pub fn ui(&mut self, ui: &mut egui::Ui, config: &mut Config) -> bool {
+        let active = config.active_profile();
+
+        if let (Some(index), Some(name)) = (active, &mut self.renaming) {
+            let response = ui.text_edit_singleline(name);
+
+            let name = name.trim().to_string();
+            let valid = !name.is_empty()
+                && config
+                    .profiles
+                    .iter()
+                    .enumerate()
+                    .all(|(other, profile)| other == index || profile.name != name);
+
+            let confirmed = ui.add_enabled(valid, egui::Button::new("OK")).clicked()
+                || (valid && response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)));
+
+            if confirmed {
+                config.profiles[index].name = name.clone();
+                config.profile = Some(name);
+                self.renaming = None;
+            }
+
+            if ui.button("Cancel").clicked() {
+                self.renaming = None;
+            }
+
+            return false;
+        }
+
+        self.renaming = None;
+
+        let mut selected = active;
+
+        egui::ComboBox::from_id_salt("profile")
+            .selected_text(
+                active.map_or(PROFILE_NONE, |index| config.profiles[index].name.as_str()),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut selected, None, PROFILE_NONE)
+                    .on_hover_text("Keeps the current settings without a profile");
+
+                for (index, profile) in config.profiles.iter().enumerate() {
+                    ui.selectable_value(&mut selected, Some(index), &profile.name);
+                }
+            });
+
+        let mut changed = false;
+
+        if selected != active {
+            match selected {
+                Some(index) => {
+                    Self::select(config, index);
+                    changed = true;
+                }
+                None => config.profile = None,
+            }
+        }
+
+        if ui
+            .button("New")
+            .on_hover_text("Creates a profile with the default settings")
+            .clicked()
+        {
+            Self::add(config, "Profile", MeasurementConfig::default());
+            changed = true;
+        }
+
+        if ui
+            .button("Duplicate")
+            .on_hover_text("Creates a profile with the current settings")
+            .clicked()
+        {
+            let name = match active {
+                Some(index) => format!("{} Copy", config.profiles[index].name),
+                None => "Profile".to_string(),
+            };
+
+            Self::add(config, &name, config.measurement.clone());
+        }
+
+        if let Some(index) = active {
+            if ui.button("Rename").clicked() {
+                self.renaming = Some(config.profiles[index].name.clone());
+            }
+
+            if ui
+                .button("Delete")
+                .on_hover_text("Deletes the profile, the current settings are kept")
+                .clicked()
+            {
+                config.profiles.remove(index);
+                config.profile = None;
+            }
+        }
+
+        changed
+    }
+}
@@ -0,0 +1,266 @@
+mod tap;
+
+use crate::{
+    capture::{CaptureHeader, CaptureWriter, CAPTURE_EXTENSION},
+    config::{MeasurementConfig, RecorderConfig},
+};
+use chrono::Local;
+use egui::{Color32, RichText};
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+};
+pub use tap::RecorderTap;
+
+const FILE_PREFIX: &str = "capture-";
+const MIB: u64 = 1024 * 1024;
+
+struct Capture {
+    writer: CaptureWriter,
+    path: PathBuf,
+}
+
+#[derive(Default)]
+struct RecorderState {
+    capture: Option<Capture>,
+    // files of the current or last recording
+    files: usize,
+    error: Option<String>,
+}
+
+/// State of the recorder, as reported to the user interface and the remote interfaces.
+#[derive(Clone, Serialize)]
+pub struct RecorderStatus {
+    pub recording: bool,
+    // file currently written
+    pub path: Option<PathBuf>,
+    pub size: u64,
+    pub files: usize,
+    // failure of the last recording
+    pub error: Option<String>,
+}
+
+/// Writes the samples of all channels to capture files while recording, shared between the
+/// pipeline, the user interface and the remote interfaces.
+#[derive(Clone)]
+pub struct Recorder {
+    config: RecorderConfig,
+    channels: usize,
+    // measurement settings the pipeline runs with, described in the header of every file
+    measurement: Arc<RwLock<MeasurementConfig>>,
+
+    // checked for every frame, without taking the lock
+    recording: Arc<AtomicBool>,
+    state: Arc<Mutex<RecorderState>>,
+}
+
+impl Recorder {
+    pub fn new(
+        config: RecorderConfig,
+        channels: usize,
+        measurement: Arc<RwLock<MeasurementConfig>>,
+    ) -> Self {
+        Self {
+            config,
+            channels,
+            measurement,
+            recording: Arc::default(),
+            state: Arc::default(),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> RecorderStatus {
+        let state = self.state.lock().unwrap();
+
+        RecorderStatus {
+            recording: state.capture.is_some(),
+            path: state.capture.as_ref().map(|capture| capture.path.clone()),
+            size: state
+                .capture
+                .as_ref()
+                .map_or(0, |capture| capture.writer.size()),
+            files: state.files,
+            error: state.error.clone(),
+        }
+    }
+
+    /// Starts a recording in a new file, a running recording is continued. Failures are kept in
+    /// the status as well.
+    pub fn start(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.capture.is_some() {
+            return Ok(());
+        }
+
+        state.files = 0;
+        state.error = None;
+
+        if let Err(e) = self.open(&mut state) {
+            state.error = Some(format!("Failed to start the recording: {}", e));
+            return Err(e);
+        }
+
+        self.recording.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// Stops the recording, the samples received so far are written.
+    pub fn stop(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        self.recording.store(false, Ordering::Relaxed);
+
+        let Some(capture) = state.capture.take() else {
+            return Ok(());
+        };
+
+        capture.writer.finish().inspect_err(|e| {
+            state.error = Some(format!("Failed to finish the recording: {}", e));
+        })
+    }
+
+    /// Opens the next file of the recording, named after its start time.
+    fn open(&self, state: &mut RecorderState) -> io::Result<()> {
+        fs::create_dir_all(&self.config.directory)?;
+
+        let start_time = Local::now();
+
+        let path = self.config.directory.join(format!(
+            "{}{}.{}",
+            FILE_PREFIX,
+            start_time.format("%Y%m%d-%H%M%S%.3f"),
+            CAPTURE_EXTENSION
+        ));
+
+        let header =
+            CaptureHeader::new(&self.measurement.read().unwrap(), self.channels, start_time);
+
+        state.capture = Some(Capture {
+            writer: CaptureWriter::create(&path, header)?,
+            path,
+        });
+        state.files += 1;
+
+        self.remove_old_files()
+    }
+
+    /// Deletes the oldest captures beyond the maximum number of files, other files in the
+    /// directory are left alone.
+    fn remove_old_files(&self) -> io::Result<()> {
+        if self.config.max_files == 0 {
+            return Ok(());
+        }
+
+        let mut captures = fs::read_dir(&self.config.directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == CAPTURE_EXTENSION)
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(FILE_PREFIX))
+            })
+            .collect::<Vec<_>>();
+
+        // the names start with the date, so they sort from the oldest to the newest
+        captures.sort();
+
+        let excess = captures.len().saturating_sub(self.config.max_files);
+
+        captures[..excess].iter().try_for_each(fs::remove_file)
+    }
+
+    /// Writes the samples of one frame, an error stops the recording.
+    pub fn record(&self, raw: &[f32], calibrated: &[f32]) {
+        let mut state = self.state.lock().unwrap();
+
+        if let Err(e) = self.write(&mut state, raw, calibrated) {
+            state.capture = None;
+            state.error = Some(format!("Recording stopped: {}", e));
+
+            self.recording.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// A full file, or a change of the sample rate or a calibration factor, continues the
+    /// recording in a new file with the next chunk, so the header of every file describes all of
+    /// its samples.
+    fn write(&self, state: &mut RecorderState, raw: &[f32], calibrated: &[f32]) -> io::Result<()> {
+        let Some(capture) = &mut state.capture else {
+            return Ok(());
+        };
+
+        if !capture.writer.push(raw, calibrated)? {
+            return Ok(());
+        }
+
+        let full = capture.writer.size() >= self.config.max_file_size * MIB;
+
+        if !full
+            && capture
+                .writer
+                .header()
+                .matches(&self.measurement.read().unwrap())
+        {
+            return Ok(());
+        }
+
+        if let Some(capture) = state.capture.take() {
+            capture.writer.finish()?;
+        }
+
+        self.open(state)
+    }
+
+    pub fn ui(&self, ui: &mut egui::Ui) {
+        let status = self.status();
+
+        if status.recording {
+            // failures are shown with the status
+            if ui.button("Stop Recording").clicked() {
+                let _ = self.stop();
+            }
+
+            let name = status
+                .path
+                .as_ref()
+                .and_then(|path| path.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            ui.label(
+                RichText::new(format!(
+                    "● {} ({:.1} MiB)",
+                    name,
+                    status.size as f64 / MIB as f64
+                ))
+                .color(Color32::RED),
+            )
+            .on_hover_text(format!(
+                "{} files in {}",
+                status.files,
+                self.config.directory.display()
+            ));
+        } else {
+            if ui.button("Start Recording").clicked() {
+                let _ = self.start();
+            }
+
+            if let Some(error) = &status.error {
+                ui.colored_label(Color32::RED, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use super::Recorder;
+use conductor::prelude::*;
+
+struct RecorderTapRunner {
+    recorder: Recorder,
+
+    raw: Vec<NodeRunnerInputPort<f32>>,
+    calibrated: Vec<NodeRunnerInputPort<f32>>,
+}
+
+impl NodeRunner for RecorderTapRunner {
+    fn run(self: Box<Self>) {
+        let mut raw = vec![0.0; self.raw.len()];
+        let mut calibrated = vec![0.0; self.calibrated.len()];
+
+        loop {
+            // every channel receives one sample per frame of the demultiplexer, so the ports are
+            // read in turn to assemble the frames again
+            for (sample, input) in raw.iter_mut().zip(&self.raw) {
+                *sample = input.recv();
+            }
+            for (sample, input) in calibrated.iter_mut().zip(&self.calibrated) {
+                *sample = input.recv();
+            }
+
+            if self.recorder.is_recording() {
+                self.recorder.record(&raw, &calibrated);
+            }
+        }
+    }
+}
+
+/// Passes the raw and calibrated samples of all channels to the recorder.
+pub struct RecorderTap {
+    recorder: Recorder,
+
+    // one per channel
+    pub raw: Vec<NodeConfigInputPort<f32>>,
+    pub calibrated: Vec<NodeConfigInputPort<f32>>,
+}
+
+impl RecorderTap {
+    pub fn new(recorder: Recorder, channels: usize) -> Self {
+        Self {
+            recorder,
+
+            raw: (0..channels).map(|_| NodeConfigInputPort::new()).collect(),
+            calibrated: (0..channels).map(|_| NodeConfigInputPort::new()).collect(),
+        }
+    }
+}
+
+impl NodeConfig for RecorderTap {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(RecorderTapRunner {
+            recorder: self.recorder,
+
+            raw: self.raw.into_iter().map(Into::into).collect(),
+            calibrated: self.calibrated.into_iter().map(Into::into).collect(),
+        })
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    config::MeasurementConfig,
+    cycle_measurement::MeasurementMode,
+    frequency_widget::FrequencyEstimator,
+    harmonics::FftWindow,
+    measurements::{Measurements, Trace},
+    settings::{
+        CalibrationFactor, ChartSize, Cycles, FftSize, RatedVoltage, RefreshPeriod, RmsWindow,
+        SettingsPacket, TimeChartPeriods,
+    },
+    Buffers,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+    net::SocketAddr,
+    sync::mpsc::Sender,
+    thread,
+};
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+
+type JsonResponse = Response<Cursor<Vec<u8>>>;
+
+/// Measurement settings of a `PUT /settings` request, missing settings are left unchanged.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsUpdate {
+    sample_rate: Option<usize>,
+    // one per channel, starting with the first
+    calibration_factors: Option<Vec<CalibrationFactor>>,
+    time_chart_periods: Option<TimeChartPeriods>,
+    fft_size: Option<FftSize>,
+    fft_window: Option<FftWindow>,
+    harmonics_refresh_period: Option<RefreshPeriod>,
+    rated_voltage: Option<RatedVoltage>,
+    frequency_estimator: Option<FrequencyEstimator>,
+    rms_window: Option<RmsWindow>,
+    measurement_mode: Option<MeasurementMode>,
+    cycles: Option<Cycles>,
+    chart_size: Option<ChartSize>,
+    rms_refresh_period: Option<RefreshPeriod>,
+}
+
+impl SettingsUpdate {
+    fn packets(self) -> Vec<SettingsPacket> {
+        let mut packets = Vec::new();
+
+        if let Some(sample_rate) = self.sample_rate {
+            packets.push(SettingsPacket::SampleRate(sample_rate as f32));
+        }
+
+        packets.extend(
+            self.calibration_factors
+                .into_iter()
+                .flatten()
+                .enumerate()
+                .map(|(channel, factor)| SettingsPacket::CalibrationFactor(channel, factor)),
+        );
+
+        packets.extend(
+            [
+                self.time_chart_periods
+                    .map(SettingsPacket::TimeChartPeriods),
+                self.fft_size.map(SettingsPacket::FftSize),
+                self.fft_window.map(SettingsPacket::FftWindow),
+                self.harmonics_refresh_period
+                    .map(SettingsPacket::HarmonicsRefreshPeriod),
+                self.rated_voltage.map(SettingsPacket::RatedVoltage),
+                self.frequency_estimator
+                    .map(SettingsPacket::FrequencyEstimator),
+                self.rms_window.map(SettingsPacket::Window),
+                self.measurement_mode.map(SettingsPacket::MeasurementMode),
+                self.cycles.map(SettingsPacket::Cycles),
+                self.chart_size.map(SettingsPacket::ChartSize),
+                self.rms_refresh_period
+                    .map(SettingsPacket::RmsRefreshPeriod),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+
+        packets
+    }
+}
+
+/// Body of a `PUT /recording` request.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RecordingUpdate {
+    recording: bool,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+fn json_response(status: u16, body: &impl Serialize) -> JsonResponse {
+    let body = serde_json::to_vec(body).expect("responses are always serializable");
+
+    Response::from_data(body)
+        .with_status_code(StatusCode(status))
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
+
+fn error_response(status: u16, error: impl Into<String>) -> JsonResponse {
+    json_response(
+        status,
+        &ErrorBody {
+            error: error.into(),
+        },
+    )
+}
+
+fn update_settings(
+    request: &mut Request,
+    channels: usize,
+    remote_settings: &Sender<SettingsPacket>,
+) -> JsonResponse {
+    let mut body = String::new();
+
+    if let Err(e) = request
+        .as_reader()
+        .take(MAX_BODY_SIZE)
+        .read_to_string(&mut body)
+    {
+        return error_response(400, e.to_string());
+    }
+
+    let update = match serde_json::from_str::<SettingsUpdate>(&body) {
+        Ok(update) => update,
+        Err(e) => return error_response(400, e.to_string()),
+    };
+
+    // all settings of a request are applied together, or none if one of them is invalid
+    let packet = SettingsPacket::Batch(update.packets());
+
+    if let Err(e) = MeasurementConfig::check(&packet, channels) {
+        return error_response(422, e);
+    }
+
+    // the user interface takes over the settings and forwards them to the pipeline
+    if remote_settings.send(packet).is_err() {
+        return error_response(503, "settings are not accepted anymore");
+    }
+
+    Response::from_data(Vec::new()).with_status_code(StatusCode(204))
+}
+
+fn update_recording(request: &mut Request, buffers: &Buffers) -> JsonResponse {
+    let mut body = String::new();
+
+    if let Err(e) = request
+        .as_reader()
+        .take(MAX_BODY_SIZE)
+        .read_to_string(&mut body)
+    {
+        return error_response(400, e.to_string());
+    }
+
+    let update = match serde_json::from_str::<RecordingUpdate>(&body) {
+        Ok(update) => update,
+        Err(e) => return error_response(400, e.to_string()),
+    };
+
+    let result = if update.recording {
+        buffers.recorder.start()
+    } else {
+        buffers.recorder.stop()
+    };
+
+    match result {
+        Ok(()) => json_response(200, &buffers.recorder.status()),
+        Err(e) => error_response(500, e.to_string()),
+    }
+}
+
+fn handle(
+    request: &mut Request,
+    buffers: &Buffers,
+    remote_settings: &Sender<SettingsPacket>,
+) -> JsonResponse {
+    let path = request.url().split('?').next().unwrap_or_default();
+
+    match (request.method(), path) {
+        (Method::Get, "/measurements") => json_response(200, &Measurements::read(buffers)),
+        (Method::Get, "/waveform") => {
+            json_response(200, &Trace::read(buffers, |channel| &channel.time_chart))
+        }
+        (Method::Get, "/spectrum") => {
+            json_response(200, &Trace::read(buffers, |channel| &channel.harmonics))
+        }
+        (Method::Put, "/settings") => {
+            update_settings(request, buffers.channels.len(), remote_settings)
+        }
+        (Method::Get, "/recording") => json_response(200, &buffers.recorder.status()),
+        (Method::Put, "/recording") => update_recording(request, buffers),
+        (_, "/measurements" | "/waveform" | "/spectrum" | "/settings" | "/recording") => {
+            error_response(405, "method not allowed")
+        }
+        _ => error_response(404, "not found"),
+    }
+}
+
+/// Serves the measurements, the chart data, the measurement settings and the recorder as JSON.
+pub fn serve(
+    address: SocketAddr,
+    buffers: Buffers,
+    remote_settings: Sender<SettingsPacket>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(address)?;
+
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let response = handle(&mut request, &buffers, &remote_settings);
+
+            // the client may be gone already, nothing to do about it
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok(())
+}
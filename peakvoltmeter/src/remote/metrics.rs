@@ -0,0 +1,190 @@
+use crate::{
+    measurements::{ChannelMeasurements, Measurements},
+    Buffers,
+};
+use std::{error::Error, fmt::Write, net::SocketAddr, thread};
+use tiny_http::{Header, Method, Response, Server, StatusCode};
+
+// version of the Prometheus text exposition format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Formats a sample value, Prometheus spells the non-finite ones `+Inf`, `-Inf` and `NaN`.
+fn sample_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_owned()
+    } else if value == f64::INFINITY {
+        "+Inf".to_owned()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_owned()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes a metric in the Prometheus text format, one sample per labelled value.
+fn metric(
+    output: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, f64)>,
+) {
+    let _ = writeln!(output, "# HELP {} {}", name, help);
+    let _ = writeln!(output, "# TYPE {} {}", name, kind);
+
+    for (labels, value) in samples {
+        let _ = writeln!(output, "{}{} {}", name, labels, sample_value(value));
+    }
+}
+
+/// One gauge sample per channel with a value, channels without value yet are left out.
+fn channel_gauge(
+    output: &mut String,
+    measurements: &Measurements,
+    name: &str,
+    help: &str,
+    value: impl Fn(&ChannelMeasurements) -> Option<f64>,
+) {
+    metric(
+        output,
+        name,
+        "gauge",
+        help,
+        measurements.channels.iter().filter_map(|channel| {
+            value(channel).map(|value| (format!("{{channel=\"{}\"}}", channel.channel), value))
+        }),
+    );
+}
+
+fn render(measurements: &Measurements) -> String {
+    let mut output = String::new();
+
+    channel_gauge(
+        &mut output,
+        measurements,
+        "peakvoltmeter_rms_volts",
+        "RMS voltage over the RMS window.",
+        |channel| channel.rms,
+    );
+    channel_gauge(
+        &mut output,
+        measurements,
+        "peakvoltmeter_peak_sqrt_volts",
+        "Peak voltage divided by the square root of 2.",
+        |channel| channel.peak_sqrt,
+    );
+    channel_gauge(
+        &mut output,
+        measurements,
+        "peakvoltmeter_frequency_hertz",
+        "Dominant frequency of the spectrum.",
+        |channel| channel.frequency,
+    );
+    channel_gauge(
+        &mut output,
+        measurements,
+        "peakvoltmeter_thd_percent",
+        "Total harmonic distortion in percent of the fundamental.",
+        |channel| channel.thd,
+    );
+    channel_gauge(
+        &mut output,
+        measurements,
+        "peakvoltmeter_tdd_percent",
+        "Total demand distortion in percent of the rated voltage.",
+        |channel| channel.tdd,
+    );
+
+    let link = &measurements.link;
+
+    metric(
+        &mut output,
+        "peakvoltmeter_samples_received_total",
+        "counter",
+        "Samples of all channels received from the sender.",
+        [(String::new(), link.samples as f64)],
+    );
+    metric(
+        &mut output,
+        "peakvoltmeter_samples_filled_total",
+        "counter",
+        "Samples of all channels synthesized for lost packets.",
+        [(String::new(), link.filled_samples as f64)],
+    );
+    metric(
+        &mut output,
+        "peakvoltmeter_packets_received_total",
+        "counter",
+        "Packets received from the sender.",
+        [(String::new(), link.received as f64)],
+    );
+    metric(
+        &mut output,
+        "peakvoltmeter_packets_dropped_total",
+        "counter",
+        "Packets that did not make it into the pipeline, by reason.",
+        [
+            ("lost", link.lost),
+            ("duplicated", link.duplicated),
+            ("malformed", link.malformed),
+        ]
+        .map(|(reason, count)| (format!("{{reason=\"{}\"}}", reason), count as f64)),
+    );
+    metric(
+        &mut output,
+        "peakvoltmeter_pipeline_lag_samples",
+        "gauge",
+        "Samples of all channels received but not yet processed by the pipeline.",
+        [(String::new(), link.pipeline_lag as f64)],
+    );
+
+    output
+}
+
+/// Serves the measurements and link statistics in the Prometheus text format on `/metrics`.
+pub fn serve(address: SocketAddr, buffers: Buffers) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let server = Server::http(address)?;
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let path = request.url().split('?').next().unwrap_or_default();
+
+            let response = match (request.method(), path) {
+                (Method::Get, "/metrics") => {
+                    Response::from_string(render(&Measurements::read(&buffers)))
+                        .with_header(Header::from_bytes("Content-Type", CONTENT_TYPE).unwrap())
+                }
+                (_, "/metrics") => {
+                    Response::from_string("method not allowed").with_status_code(StatusCode(405))
+                }
+                _ => Response::from_string("not found").with_status_code(StatusCode(404)),
+            };
+
+            // the scraper may be gone already, nothing to do about it
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_non_finite_values_in_the_exposition_format() {
+        let mut output = String::new();
+
+        metric(
+            &mut output,
+            "test",
+            "gauge",
+            "Test values.",
+            [1.5, f64::INFINITY, f64::NEG_INFINITY, f64::NAN].map(|value| (String::new(), value)),
+        );
+
+        let samples: Vec<_> = output.lines().skip(2).collect();
+        assert_eq!(samples, ["test 1.5", "test +Inf", "test -Inf", "test NaN"]);
+    }
+}
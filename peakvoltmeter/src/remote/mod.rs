@@ -0,0 +1,88 @@
+mod http;
+mod metrics;
+mod modbus;
+mod mqtt;
+mod scpi;
+mod websocket;
+
+use crate::{config::Config, settings::SettingsPacket, Buffers};
+use std::sync::mpsc::Sender;
+
+/// Starts the enabled remote interfaces. Settings they receive are sent to `remote_settings`, a
+/// failing interface is reported in the returned warnings without affecting the others.
+pub fn start(
+    config: &Config,
+    buffers: &Buffers,
+    remote_settings: &Sender<SettingsPacket>,
+) -> Vec<String> {
+    let mut warnings = Vec::new();
+
+    if config.scpi.enabled {
+        if let Err(e) = scpi::serve(
+            config.scpi.listen_address,
+            buffers.clone(),
+            remote_settings.clone(),
+        ) {
+            warnings.push(format!(
+                "Failed to start the SCPI server on {}: {}",
+                config.scpi.listen_address, e
+            ));
+        }
+    }
+
+    if config.http.enabled {
+        if let Err(e) = http::serve(
+            config.http.listen_address,
+            buffers.clone(),
+            remote_settings.clone(),
+        ) {
+            warnings.push(format!(
+                "Failed to start the HTTP server on {}: {}",
+                config.http.listen_address, e
+            ));
+        }
+    }
+
+    if config.websocket.enabled {
+        if let Err(e) = websocket::serve(config.websocket.listen_address, buffers.clone()) {
+            warnings.push(format!(
+                "Failed to start the WebSocket server on {}: {}",
+                config.websocket.listen_address, e
+            ));
+        }
+    }
+
+    if config.metrics.enabled {
+        if let Err(e) = metrics::serve(config.metrics.listen_address, buffers.clone()) {
+            warnings.push(format!(
+                "Failed to start the metrics server on {}: {}",
+                config.metrics.listen_address, e
+            ));
+        }
+    }
+
+    if config.mqtt.enabled {
+        if let Err(e) = mqtt::start(buffers.clone(), config.mqtt.clone(), config.alarms) {
+            warnings.push(format!(
+                "Failed to start the MQTT publisher for {}: {}",
+                config.mqtt.broker, e
+            ));
+        }
+    }
+
+    if config.modbus.enabled {
+        if let Err(e) = modbus::serve(
+            config.modbus.listen_address,
+            buffers.clone(),
+            config.alarms,
+            remote_settings.clone(),
+        ) {
+            warnings.push(format!(
+                "Failed to start the Modbus server on {}: {}",
+                config.modbus.listen_address, e
+            ));
+        }
+    }
+
+    warnings
+}
@@ -0,0 +1,477 @@
+use crate::{
+    alarms::AlarmState,
+    config::{AlarmConfig, MeasurementConfig},
+    measurements::Measurements,
+    settings::SettingsPacket,
+    Buffers,
+};
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::Sender,
+    thread,
+};
+
+// Version of the register map, read from register 0. It is increased whenever registers are
+// added, registers of earlier versions never move or change their meaning.
+const MAP_VERSION: u16 = 1;
+
+const VERSION_REGISTER: u16 = 0;
+const CHANNELS_REGISTER: u16 = 1;
+// writable settings, float32
+const SAMPLE_RATE_REGISTER: u16 = 10;
+const FFT_SIZE_REGISTER: u16 = 12;
+const RMS_WINDOW_REGISTER: u16 = 14;
+
+// every channel has a block of registers, the first channel starts at 100, the second at 200
+const CHANNEL_BLOCK_SIZE: u16 = 100;
+// offsets within the block of a channel, float32 unless noted otherwise
+const RMS_OFFSET: u16 = 0;
+const PEAK_SQRT_OFFSET: u16 = 2;
+const FREQUENCY_OFFSET: u16 = 4;
+const THD_OFFSET: u16 = 6;
+const RMS_MIN_OFFSET: u16 = 8;
+const RMS_MAX_OFFSET: u16 = 10;
+const PEAK_SQRT_MIN_OFFSET: u16 = 12;
+const PEAK_SQRT_MAX_OFFSET: u16 = 14;
+const FREQUENCY_MIN_OFFSET: u16 = 16;
+const FREQUENCY_MAX_OFFSET: u16 = 18;
+// u16, one bit per alarm
+const ALARMS_OFFSET: u16 = 20;
+// writable
+const CALIBRATION_FACTOR_OFFSET: u16 = 30;
+
+const READ_HOLDING_REGISTERS: u8 = 0x03;
+const READ_INPUT_REGISTERS: u8 = 0x04;
+const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+// limits of the Modbus specification, so a response fits into a single frame
+const MAX_READ_COUNT: u16 = 125;
+const MAX_WRITE_COUNT: u16 = 123;
+
+const MBAP_HEADER_SIZE: usize = 7;
+
+// exception codes of the Modbus specification, illegal function, data address and data value
+#[derive(Clone, Copy)]
+enum Exception {
+    UnsupportedFunction = 0x01,
+    InvalidAddress = 0x02,
+    InvalidValue = 0x03,
+}
+
+fn channel_block(channel: usize) -> u16 {
+    CHANNEL_BLOCK_SIZE * (channel as u16 + 1)
+}
+
+fn set_float(registers: &mut [u16], address: u16, value: f64) {
+    // the high word comes first, like the bytes of every register
+    let bits = (value as f32).to_bits();
+
+    registers[address as usize] = (bits >> 16) as u16;
+    registers[address as usize + 1] = bits as u16;
+}
+
+/// Current content of all registers. Values that are not available yet read as NaN, registers
+/// without meaning as 0.
+fn registers(buffers: &Buffers, alarms: &AlarmConfig) -> Vec<u16> {
+    let measurements = Measurements::read(buffers);
+    let settings = buffers.measurement.read().unwrap().clone();
+
+    let mut registers = vec![0; channel_block(measurements.channels.len()) as usize];
+
+    registers[VERSION_REGISTER as usize] = MAP_VERSION;
+    registers[CHANNELS_REGISTER as usize] = measurements.channels.len() as u16;
+    set_float(
+        &mut registers,
+        SAMPLE_RATE_REGISTER,
+        settings.sample_rate as f64,
+    );
+    set_float(&mut registers, FFT_SIZE_REGISTER, settings.fft_size as f64);
+    set_float(
+        &mut registers,
+        RMS_WINDOW_REGISTER,
+        settings.rms_window as f64,
+    );
+
+    for channel in &measurements.channels {
+        let block = channel_block(channel.channel);
+
+        let values = [
+            (RMS_OFFSET, channel.rms),
+            (PEAK_SQRT_OFFSET, channel.peak_sqrt),
+            (FREQUENCY_OFFSET, channel.frequency),
+            (THD_OFFSET, channel.thd),
+            (RMS_MIN_OFFSET, channel.rms_range.map(|range| range[0])),
+            (RMS_MAX_OFFSET, channel.rms_range.map(|range| range[1])),
+            (
+                PEAK_SQRT_MIN_OFFSET,
+                channel.peak_sqrt_range.map(|range| range[0]),
+            ),
+            (
+                PEAK_SQRT_MAX_OFFSET,
+                channel.peak_sqrt_range.map(|range| range[1]),
+            ),
+            (
+                FREQUENCY_MIN_OFFSET,
+                channel.frequency_range.map(|range| range[0]),
+            ),
+            (
+                FREQUENCY_MAX_OFFSET,
+                channel.frequency_range.map(|range| range[1]),
+            ),
+            (
+                CALIBRATION_FACTOR_OFFSET,
+                Some(settings.calibration_factor(channel.channel) as f64),
+            ),
+        ];
+
+        for (offset, value) in values {
+            set_float(&mut registers, block + offset, value.unwrap_or(f64::NAN));
+        }
+
+        registers[(block + ALARMS_OFFSET) as usize] = AlarmState::evaluate(alarms, channel).bits();
+    }
+
+    registers
+}
+
+/// Setting of the writable float at `address`.
+fn setting(address: u16, value: f32, channels: usize) -> Option<SettingsPacket> {
+    match address {
+        SAMPLE_RATE_REGISTER => Some(SettingsPacket::SampleRate(value.round())),
+        FFT_SIZE_REGISTER => Some(SettingsPacket::FftSize(value.round() as usize)),
+        RMS_WINDOW_REGISTER => Some(SettingsPacket::Window(value)),
+        _ => {
+            let channel = (address / CHANNEL_BLOCK_SIZE) as usize;
+
+            (channel >= 1
+                && channel <= channels
+                && address % CHANNEL_BLOCK_SIZE == CALIBRATION_FACTOR_OFFSET)
+                .then(|| SettingsPacket::CalibrationFactor(channel - 1, value))
+        }
+    }
+}
+
+struct Session {
+    buffers: Buffers,
+    alarms: AlarmConfig,
+    remote_settings: Sender<SettingsPacket>,
+}
+
+impl Session {
+    fn read(&self, function: u8, data: &[u8]) -> Result<Vec<u8>, Exception> {
+        let [start_high, start_low, count_high, count_low] = data else {
+            return Err(Exception::InvalidValue);
+        };
+
+        let start = u16::from_be_bytes([*start_high, *start_low]) as usize;
+        let count = u16::from_be_bytes([*count_high, *count_low]);
+
+        if !(1..=MAX_READ_COUNT).contains(&count) {
+            return Err(Exception::InvalidValue);
+        }
+
+        let registers = registers(&self.buffers, &self.alarms);
+
+        let registers = registers
+            .get(start..start + count as usize)
+            .ok_or(Exception::InvalidAddress)?;
+
+        let mut response = vec![function, (count * 2) as u8];
+        response.extend(registers.iter().flat_map(|register| register.to_be_bytes()));
+
+        Ok(response)
+    }
+
+    /// Writes settings, all values of a request have to be valid floats of writable registers.
+    fn write(&self, data: &[u8]) -> Result<Vec<u8>, Exception> {
+        if data.len() < 5 {
+            return Err(Exception::InvalidValue);
+        }
+
+        let start = u16::from_be_bytes([data[0], data[1]]);
+        let count = u16::from_be_bytes([data[2], data[3]]);
+        let values = &data[5..];
+
+        if !(1..=MAX_WRITE_COUNT).contains(&count)
+            || data[4] as usize != count as usize * 2
+            || values.len() != count as usize * 2
+        {
+            return Err(Exception::InvalidValue);
+        }
+
+        // a float spans two registers, halves of it cannot be written
+        if !count.is_multiple_of(2) {
+            return Err(Exception::InvalidAddress);
+        }
+
+        let channels = self.buffers.channels.len();
+
+        let packets = values
+            .chunks_exact(4)
+            .enumerate()
+            .map(|(index, bytes)| {
+                let value = f32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+                // the registers of a request end at the last address
+                start
+                    .checked_add(index as u16 * 2)
+                    .and_then(|address| setting(address, value, channels))
+                    .ok_or(Exception::InvalidAddress)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // all settings of a request are applied together, or none if one of them is invalid
+        let packet = SettingsPacket::Batch(packets);
+
+        if MeasurementConfig::check(&packet, channels).is_err() {
+            return Err(Exception::InvalidValue);
+        }
+
+        // the settings are forwarded to the pipeline and taken over by the user interface
+        let _ = self.remote_settings.send(packet);
+
+        Ok([&[WRITE_MULTIPLE_REGISTERS], &data[..4]].concat())
+    }
+
+    fn handle(&self, pdu: &[u8]) -> Vec<u8> {
+        let Some((&function, data)) = pdu.split_first() else {
+            return vec![0x80, Exception::UnsupportedFunction as u8];
+        };
+
+        let response = match function {
+            READ_HOLDING_REGISTERS | READ_INPUT_REGISTERS => self.read(function, data),
+            WRITE_MULTIPLE_REGISTERS => self.write(data),
+            _ => Err(Exception::UnsupportedFunction),
+        };
+
+        response.unwrap_or_else(|exception| vec![function | 0x80, exception as u8])
+    }
+}
+
+fn handle_client(mut stream: TcpStream, session: Session) -> io::Result<()> {
+    loop {
+        let mut header = [0; MBAP_HEADER_SIZE];
+        stream.read_exact(&mut header)?;
+
+        let transaction = [header[0], header[1]];
+        let protocol = u16::from_be_bytes([header[2], header[3]]);
+        // the unit identifier is counted in
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        let unit = header[6];
+
+        // not a Modbus client, the stream cannot be resynchronized
+        if protocol != 0 || !(2..=254).contains(&length) {
+            return Ok(());
+        }
+
+        let mut pdu = vec![0; length - 1];
+        stream.read_exact(&mut pdu)?;
+
+        let response = session.handle(&pdu);
+
+        let mut frame = Vec::with_capacity(MBAP_HEADER_SIZE + response.len());
+        frame.extend_from_slice(&transaction);
+        frame.extend_from_slice(&0u16.to_be_bytes());
+        frame.extend_from_slice(&(response.len() as u16 + 1).to_be_bytes());
+        frame.push(unit);
+        frame.extend_from_slice(&response);
+
+        stream.write_all(&frame)?;
+    }
+}
+
+/// Serves the measurements as Modbus TCP registers to any number of clients, every unit
+/// identifier is answered. Returns the address the server is bound to, which tells the port when
+/// port 0 is given.
+pub fn serve(
+    address: SocketAddr,
+    buffers: Buffers,
+    alarms: AlarmConfig,
+    remote_settings: Sender<SettingsPacket>,
+) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let session = Session {
+                buffers: buffers.clone(),
+                alarms,
+                remote_settings: remote_settings.clone(),
+            };
+
+            // the connection ends with an error when the client is gone, nothing to do about it
+            thread::spawn(move || handle_client(stream, session));
+        }
+    });
+
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecorderConfig;
+    use std::{sync::mpsc, time::Duration};
+
+    struct Client {
+        stream: TcpStream,
+        transaction: u16,
+    }
+
+    impl Client {
+        fn connect(address: SocketAddr) -> Self {
+            let stream = TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+
+            Self {
+                stream,
+                transaction: 0,
+            }
+        }
+
+        /// Sends a PDU to unit 1 and returns the PDU of the response.
+        fn request(&mut self, pdu: &[u8]) -> Vec<u8> {
+            self.transaction += 1;
+
+            let mut frame = self.transaction.to_be_bytes().to_vec();
+            frame.extend_from_slice(&0u16.to_be_bytes());
+            frame.extend_from_slice(&(pdu.len() as u16 + 1).to_be_bytes());
+            frame.push(1);
+            frame.extend_from_slice(pdu);
+            self.stream.write_all(&frame).unwrap();
+
+            let mut header = [0; MBAP_HEADER_SIZE];
+            self.stream.read_exact(&mut header).unwrap();
+
+            assert_eq!(header[..2], self.transaction.to_be_bytes());
+            assert_eq!(header[2..4], [0, 0]);
+            assert_eq!(header[6], 1);
+
+            let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+            let mut response = vec![0; length - 1];
+            self.stream.read_exact(&mut response).unwrap();
+
+            response
+        }
+
+        fn read(&mut self, function: u8, start: u16, count: u16) -> Vec<u8> {
+            let mut pdu = vec![function];
+            pdu.extend_from_slice(&start.to_be_bytes());
+            pdu.extend_from_slice(&count.to_be_bytes());
+
+            self.request(&pdu)
+        }
+
+        fn write_floats(&mut self, start: u16, values: &[f32]) -> Vec<u8> {
+            let mut pdu = vec![WRITE_MULTIPLE_REGISTERS];
+            pdu.extend_from_slice(&start.to_be_bytes());
+            pdu.extend_from_slice(&(values.len() as u16 * 2).to_be_bytes());
+            pdu.push(values.len() as u8 * 4);
+            pdu.extend(values.iter().flat_map(|value| value.to_be_bytes()));
+
+            self.request(&pdu)
+        }
+    }
+
+    fn start() -> (Client, mpsc::Receiver<SettingsPacket>) {
+        let buffers = Buffers::new(1, RecorderConfig::default(), None);
+        *buffers.channels[0].rms_trend.write().unwrap() = vec![[0.0, 229.0], [1.0, 230.5]];
+
+        let (sender, receiver) = mpsc::channel();
+        let address = serve(
+            "127.0.0.1:0".parse().unwrap(),
+            buffers,
+            AlarmConfig::default(),
+            sender,
+        )
+        .unwrap();
+
+        (Client::connect(address), receiver)
+    }
+
+    #[test]
+    fn reads_registers_with_the_high_word_first() {
+        let (mut client, _receiver) = start();
+
+        assert_eq!(
+            client.read(READ_HOLDING_REGISTERS, VERSION_REGISTER, 2),
+            [READ_HOLDING_REGISTERS, 4, 0, MAP_VERSION as u8, 0, 1]
+        );
+
+        let response = client.read(READ_INPUT_REGISTERS, channel_block(0) + RMS_OFFSET, 4);
+        assert_eq!(response[..2], [READ_INPUT_REGISTERS, 8]);
+
+        // 230.5 is 0x43668000, the frequency is not known yet
+        assert_eq!(response[2..6], [0x43, 0x66, 0x80, 0x00]);
+        assert!(f32::from_be_bytes([response[6], response[7], response[8], response[9]]).is_nan());
+    }
+
+    #[test]
+    fn writes_settings_together() {
+        let (mut client, receiver) = start();
+
+        assert_eq!(
+            client.write_floats(SAMPLE_RATE_REGISTER, &[3125.0, 4096.0]),
+            [WRITE_MULTIPLE_REGISTERS, 0, 10, 0, 4]
+        );
+
+        let Ok(SettingsPacket::Batch(packets)) = receiver.recv_timeout(Duration::from_secs(5))
+        else {
+            panic!("no batch of settings received");
+        };
+        assert!(matches!(
+            packets[..],
+            [
+                SettingsPacket::SampleRate(3125.0),
+                SettingsPacket::FftSize(4096)
+            ]
+        ));
+    }
+
+    #[test]
+    fn answers_invalid_requests_with_exceptions() {
+        let (mut client, receiver) = start();
+
+        // write single register is not supported
+        assert_eq!(client.request(&[0x06, 0, 10, 0, 0]), [0x86, 0x01]);
+        // past the block of the only channel
+        assert_eq!(client.read(READ_HOLDING_REGISTERS, 199, 2), [0x83, 0x02]);
+        assert_eq!(
+            client.read(READ_HOLDING_REGISTERS, 0, MAX_READ_COUNT + 1),
+            [0x83, 0x03]
+        );
+        assert_eq!(client.request(&[READ_HOLDING_REGISTERS, 0]), [0x83, 0x03]);
+        // measurements are read only, the registers of a request cannot wrap around
+        assert_eq!(
+            client.write_floats(channel_block(0) + RMS_OFFSET, &[1.0]),
+            [0x90, 0x02]
+        );
+        assert_eq!(client.write_floats(u16::MAX - 1, &[1.0, 1.0]), [0x90, 0x02]);
+        // a single register is half a float
+        assert_eq!(
+            client.request(&[WRITE_MULTIPLE_REGISTERS, 0, 10, 0, 1, 2, 0, 0]),
+            [0x90, 0x02]
+        );
+        assert_eq!(
+            client.write_floats(FFT_SIZE_REGISTER, &[100_000.0]),
+            [0x90, 0x03]
+        );
+
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn closes_the_connection_of_other_protocols() {
+        let (mut client, _receiver) = start();
+
+        // header of protocol 1, the connection ends without reading further
+        client.stream.write_all(&[0, 1, 0, 1, 0, 6, 1]).unwrap();
+
+        let mut response = [0; 1];
+        assert_eq!(client.stream.read(&mut response).unwrap(), 0);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::{
+    alarms::{Alarm, AlarmState},
+    config::{AlarmConfig, MqttConfig},
+    measurements::Measurements,
+    streams::Stream,
+    Buffers,
+};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::Serialize;
+use std::{thread, time::Duration};
+
+const KEEP_ALIVE: Duration = Duration::from_secs(5);
+// delays between two connection attempts, doubled after every failed attempt
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+#[derive(Serialize)]
+struct AlarmMessage {
+    active: bool,
+    alarms: Vec<Alarm>,
+}
+
+fn topic(template: &str, channel: usize) -> String {
+    template.replace("{channel}", &channel.to_string())
+}
+
+/// Keeps the connection to the broker alive, reconnecting with backoff after failures. The
+/// messages published in the meantime are buffered by the client and sent after the reconnect.
+fn drive_connection(
+    mut connection: rumqttc::Connection,
+    client: Client,
+    buffers: Buffers,
+    config: MqttConfig,
+    qos: QoS,
+) {
+    let mut backoff = BACKOFF_MIN;
+    let mut connected = false;
+
+    for event in connection.iter() {
+        match event {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                connected = true;
+                backoff = BACKOFF_MIN;
+
+                // replaces the retained last will of a previous connection
+                let _ = client.try_publish(&config.status_topic, qos, true, ONLINE);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                if connected {
+                    buffers.warn(format!(
+                        "Lost the connection to the MQTT broker {}: {}",
+                        config.broker, e
+                    ));
+                    connected = false;
+                }
+
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(BACKOFF_MAX);
+            }
+        }
+    }
+}
+
+/// Publishes every new RMS, Vp/√2 and frequency value as it is computed, so at the refresh
+/// periods of the charts, and the alarm state of a channel whenever it changes. All messages are
+/// retained, a subscriber gets the last values right away.
+fn publish_measurements(
+    client: Client,
+    buffers: Buffers,
+    config: MqttConfig,
+    alarms: AlarmConfig,
+    qos: QoS,
+) {
+    let frames = buffers.streams.subscribe();
+
+    let mut states: Vec<Option<AlarmState>> = vec![None; buffers.channels.len()];
+
+    for frame in frames {
+        let template = match frame.stream {
+            Stream::Rms => &config.rms_topic,
+            Stream::PeakSqrt => &config.peak_sqrt_topic,
+            Stream::Frequency => &config.frequency_topic,
+            Stream::Waveform | Stream::Spectrum => continue,
+        };
+
+        let Some(value) = frame.values.first() else {
+            continue;
+        };
+
+        // a full buffer drops the value, the next one follows within a refresh period
+        let _ = client.try_publish(topic(template, frame.channel), qos, true, value.to_string());
+
+        let measurements = Measurements::read(&buffers);
+        let state = AlarmState::evaluate(&alarms, &measurements.channels[frame.channel]);
+
+        if states[frame.channel] != Some(state) {
+            let message = AlarmMessage {
+                active: state.is_active(),
+                alarms: state.alarms(),
+            };
+
+            let published = client.try_publish(
+                topic(&config.alarm_topic, frame.channel),
+                qos,
+                true,
+                serde_json::to_vec(&message).expect("alarms are always serializable"),
+            );
+
+            // an alarm must not get lost, it is published again with the next value
+            if published.is_ok() {
+                states[frame.channel] = Some(state);
+            }
+        }
+    }
+}
+
+/// Publishes the measurements and alarms to the MQTT broker of the config.
+pub fn start(buffers: Buffers, config: MqttConfig, alarms: AlarmConfig) -> Result<(), String> {
+    let (host, port) = config
+        .broker_address()
+        .ok_or_else(|| format!("{} is not a `host:port` address", config.broker))?;
+
+    let qos = rumqttc::qos(config.qos).map_err(|e| e.to_string())?;
+
+    let mut options = MqttOptions::new(&config.client_id, host, port);
+    options.set_keep_alive(KEEP_ALIVE);
+    options.set_last_will(LastWill::new(&config.status_topic, OFFLINE, qos, true));
+
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+
+    let (client, connection) = Client::new(options, config.buffer_size);
+
+    {
+        let client = client.clone();
+        let buffers = buffers.clone();
+        let config = config.clone();
+        thread::spawn(move || drive_connection(connection, client, buffers, config, qos));
+    }
+
+    thread::spawn(move || publish_measurements(client, buffers, config, alarms, qos));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::RecorderConfig, streams::Stream};
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        time::Instant,
+    };
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    struct Will {
+        topic: String,
+        message: String,
+        retain: bool,
+    }
+
+    struct Publish {
+        topic: String,
+        payload: String,
+        retain: bool,
+    }
+
+    /// Accepts a single client and speaks just enough MQTT 3.1.1 to receive its publishes.
+    struct Broker {
+        stream: TcpStream,
+        received: Vec<Publish>,
+    }
+
+    fn string(body: &[u8], offset: &mut usize) -> String {
+        let length = u16::from_be_bytes([body[*offset], body[*offset + 1]]) as usize;
+        let string = String::from_utf8_lossy(&body[*offset + 2..*offset + 2 + length]);
+        *offset += 2 + length;
+        string.into_owned()
+    }
+
+    impl Broker {
+        /// Accepts the connection of the client and returns the last will it connects with.
+        fn accept(listener: &TcpListener) -> (Self, Option<Will>) {
+            let (stream, _) = listener.accept().unwrap();
+            stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+
+            let mut broker = Self {
+                stream,
+                received: Vec::new(),
+            };
+
+            let (header, body) = broker.packet();
+            assert_eq!(header >> 4, 1, "the client must connect first");
+
+            // protocol name, level, flags and keep alive precede the client id
+            let mut offset = 2 + u16::from_be_bytes([body[0], body[1]]) as usize;
+            let flags = body[offset + 1];
+            offset += 4;
+            string(&body, &mut offset);
+
+            let will = (flags & 0x04 != 0).then(|| Will {
+                topic: string(&body, &mut offset),
+                message: string(&body, &mut offset),
+                retain: flags & 0x20 != 0,
+            });
+
+            broker.stream.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+
+            (broker, will)
+        }
+
+        fn packet(&mut self) -> (u8, Vec<u8>) {
+            let mut header = [0];
+            self.stream.read_exact(&mut header).unwrap();
+
+            let mut length = 0;
+            for shift in (0..28).step_by(7) {
+                let mut byte = [0];
+                self.stream.read_exact(&mut byte).unwrap();
+
+                length |= ((byte[0] & 0x7F) as usize) << shift;
+                if byte[0] & 0x80 == 0 {
+                    break;
+                }
+            }
+
+            let mut body = vec![0; length];
+            self.stream.read_exact(&mut body).unwrap();
+
+            (header[0], body)
+        }
+
+        /// Waits for a publish of `payload` on `topic`, publishes received before are kept.
+        fn published(&mut self, topic: &str, payload: &str) -> &Publish {
+            while !self
+                .received
+                .iter()
+                .any(|publish| publish.topic == topic && publish.payload == payload)
+            {
+                let (header, body) = self.packet();
+
+                match header >> 4 {
+                    3 => {
+                        let mut offset = 0;
+                        let topic = string(&body, &mut offset);
+
+                        if (header >> 1) & 0x03 > 0 {
+                            let id = [body[offset], body[offset + 1]];
+                            offset += 2;
+                            self.stream.write_all(&[0x40, 0x02, id[0], id[1]]).unwrap();
+                        }
+
+                        self.received.push(Publish {
+                            topic,
+                            payload: String::from_utf8_lossy(&body[offset..]).into_owned(),
+                            retain: header & 0x01 != 0,
+                        });
+                    }
+                    12 => self.stream.write_all(&[0xD0, 0x00]).unwrap(),
+                    _ => {}
+                }
+            }
+
+            self.received
+                .iter()
+                .find(|publish| publish.topic == topic && publish.payload == payload)
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn publishes_to_a_broker() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+
+        let buffers = Buffers::new(1, RecorderConfig::default(), None);
+        let config = MqttConfig {
+            enabled: true,
+            broker: listener.local_addr().unwrap().to_string(),
+            ..MqttConfig::default()
+        };
+
+        start(buffers.clone(), config.clone(), AlarmConfig::default()).unwrap();
+
+        let (mut broker, will) = Broker::accept(&listener);
+
+        let will = will.expect("the client has a last will");
+        assert_eq!(will.topic, config.status_topic);
+        assert_eq!(will.message, OFFLINE);
+        assert!(will.retain);
+
+        assert!(broker.published(&config.status_topic, ONLINE).retain);
+
+        let rms = buffers.streams.publisher(Stream::Rms, 0);
+        let rms_topic = topic(&config.rms_topic, 0);
+
+        rms.publish(0.0, || vec![230.5]);
+        assert!(broker.published(&rms_topic, "230.5").retain);
+
+        // the broker goes away, the lost connection is reported as warning
+        drop(broker);
+
+        let lost = Instant::now();
+        while buffers.warnings.read().unwrap().is_empty() {
+            assert!(
+                lost.elapsed() < TIMEOUT,
+                "the lost connection is not reported"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(buffers.warnings.read().unwrap()[0].contains(&config.broker));
+
+        // published while disconnected, sent after the reconnect
+        rms.publish(0.0, || vec![231.0]);
+
+        let (mut broker, _) = Broker::accept(&listener);
+
+        assert!(broker.published(&rms_topic, "231").retain);
+        assert!(broker.published(&config.status_topic, ONLINE).retain);
+    }
+}
@@ -0,0 +1,531 @@
+use crate::{
+    config::MeasurementConfig,
+    cycle_measurement::MeasurementMode,
+    frequency_widget::FrequencyEstimator,
+    harmonics::FftWindow,
+    measurements::{ChannelMeasurements, Measurements},
+    settings::{Channel, SettingsPacket},
+    Buffers,
+};
+use std::{
+    collections::VecDeque,
+    f64::consts::SQRT_2,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::Sender,
+    thread,
+};
+
+// SCPI's representation of a value that is not available
+const NOT_A_NUMBER: f64 = 9.91e37;
+const MAX_ERRORS: usize = 32;
+
+const IDENTIFICATION: &str = concat!("conductor,peakvoltmeter,0,", env!("CARGO_PKG_VERSION"));
+
+#[derive(Clone, Copy)]
+enum ScpiError {
+    Command,
+    Parameter,
+    MissingParameter,
+    OutOfRange,
+    Stale,
+    MassStorage,
+    QueueOverflow,
+}
+
+impl ScpiError {
+    fn code(&self) -> i32 {
+        match self {
+            ScpiError::Command => -113,
+            ScpiError::Parameter => -224,
+            ScpiError::MissingParameter => -109,
+            ScpiError::OutOfRange => -222,
+            ScpiError::Stale => -230,
+            ScpiError::MassStorage => -250,
+            ScpiError::QueueOverflow => -350,
+        }
+    }
+
+    fn message(&self) -> &'static str {
+        match self {
+            ScpiError::Command => "Undefined header",
+            ScpiError::Parameter => "Illegal parameter value",
+            ScpiError::MissingParameter => "Missing parameter",
+            ScpiError::OutOfRange => "Data out of range",
+            ScpiError::Stale => "Data stale",
+            ScpiError::MassStorage => "Mass storage error",
+            ScpiError::QueueOverflow => "Queue overflow",
+        }
+    }
+}
+
+/// Whether `header` is `pattern` in its long or short form. The short form of every mnemonic is
+/// made of its upper case letters, `MEASure` matches `MEAS` and `measure`.
+fn matches(header: &str, pattern: &str) -> bool {
+    let header = header.trim_start_matches(':');
+
+    let mnemonics = header.split(':').collect::<Vec<_>>();
+    let patterns = pattern.split(':').collect::<Vec<_>>();
+
+    mnemonics.len() == patterns.len()
+        && mnemonics.iter().zip(&patterns).all(|(mnemonic, pattern)| {
+            let short = pattern
+                .chars()
+                .filter(|c| !c.is_ascii_lowercase())
+                .collect::<String>();
+
+            mnemonic.eq_ignore_ascii_case(&short) || mnemonic.eq_ignore_ascii_case(pattern)
+        })
+}
+
+fn format_number(value: Option<f64>) -> String {
+    format!("{:.6E}", value.unwrap_or(NOT_A_NUMBER))
+}
+
+struct Session {
+    buffers: Buffers,
+    remote_settings: Sender<SettingsPacket>,
+    errors: VecDeque<ScpiError>,
+}
+
+impl Session {
+    fn push_error(&mut self, error: ScpiError) {
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push_back(error);
+        } else if let Some(last) = self.errors.back_mut() {
+            *last = ScpiError::QueueOverflow;
+        }
+    }
+
+    /// Channel selected by the parameters of a query, the first one without parameters.
+    /// Channels are counted from 1, `(@2)` is accepted as well as `2`.
+    fn channel(&mut self, parameters: &[&str]) -> Option<Channel> {
+        let Some(parameter) = parameters.first() else {
+            return Some(0);
+        };
+
+        let parameter = parameter
+            .trim_start_matches("(@")
+            .trim_end_matches(')')
+            .trim();
+
+        match parameter.parse::<Channel>() {
+            Ok(channel) if (1..=self.buffers.channels.len()).contains(&channel) => {
+                Some(channel - 1)
+            }
+            Ok(_) => {
+                self.push_error(ScpiError::OutOfRange);
+                None
+            }
+            Err(_) => {
+                self.push_error(ScpiError::Parameter);
+                None
+            }
+        }
+    }
+
+    /// Latest value of a measurement of the selected channel, stale values are reported as
+    /// error but still answered, with SCPI's not a number.
+    fn measurement(
+        &mut self,
+        parameters: &[&str],
+        value: impl Fn(&ChannelMeasurements) -> Option<f64>,
+    ) -> Option<String> {
+        let channel = self.channel(parameters)?;
+
+        let value = value(&Measurements::read(&self.buffers).channels[channel]);
+
+        if value.is_none() {
+            self.push_error(ScpiError::Stale);
+        }
+
+        Some(format_number(value))
+    }
+
+    fn number(&mut self, parameter: Option<&&str>) -> Option<f64> {
+        let Some(parameter) = parameter else {
+            self.push_error(ScpiError::MissingParameter);
+            return None;
+        };
+
+        match parameter.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() => Some(value),
+            _ => {
+                self.push_error(ScpiError::Parameter);
+                None
+            }
+        }
+    }
+
+    /// Parses a parameter given by one of the mnemonics of `choices`.
+    fn choice<T: Copy>(&mut self, parameter: Option<&&str>, choices: &[(&str, T)]) -> Option<T> {
+        let Some(parameter) = parameter else {
+            self.push_error(ScpiError::MissingParameter);
+            return None;
+        };
+
+        let choice = choices
+            .iter()
+            .find(|(pattern, _)| matches(parameter, pattern))
+            .map(|&(_, choice)| choice);
+
+        if choice.is_none() {
+            self.push_error(ScpiError::Parameter);
+        }
+
+        choice
+    }
+
+    /// Parses the parameters of a `CONFigure` command into the setting it changes.
+    fn setting(&mut self, header: &str, parameters: &[&str]) -> Option<SettingsPacket> {
+        // settings that are not a number
+        if matches(header, "CONFigure:FFT:WINDow") {
+            return self
+                .choice(
+                    parameters.first(),
+                    &[
+                        ("RECTangular", FftWindow::Rectangular),
+                        ("HANN", FftWindow::Hann),
+                        ("BHARris", FftWindow::BlackmanHarris),
+                        ("FLATtop", FftWindow::FlatTop),
+                    ],
+                )
+                .map(SettingsPacket::FftWindow);
+        }
+        if matches(header, "CONFigure:FREQuency:ESTimator") {
+            return self
+                .choice(
+                    parameters.first(),
+                    &[
+                        ("QUADratic", FrequencyEstimator::Quadratic),
+                        ("GAUSsian", FrequencyEstimator::Gaussian),
+                        ("JACobsen", FrequencyEstimator::Jacobsen),
+                        ("ZCRossing", FrequencyEstimator::ZeroCrossing),
+                        ("PLL", FrequencyEstimator::Pll),
+                    ],
+                )
+                .map(SettingsPacket::FrequencyEstimator);
+        }
+        if matches(header, "CONFigure:RMS:MODE") {
+            return self
+                .choice(
+                    parameters.first(),
+                    &[
+                        ("WINDow", MeasurementMode::Window),
+                        ("CYCLes", MeasurementMode::Cycles),
+                    ],
+                )
+                .map(SettingsPacket::MeasurementMode);
+        }
+
+        let value = self.number(parameters.first())?;
+
+        let packet = if matches(header, "CONFigure:SRATe") {
+            SettingsPacket::SampleRate(value.round() as f32)
+        } else if matches(header, "CONFigure:CALibration") {
+            // the channel is optional, the first one by default
+            let channel = self.channel(&parameters[1..])?;
+            SettingsPacket::CalibrationFactor(channel, value as f32)
+        } else if matches(header, "CONFigure:PERiods") {
+            SettingsPacket::TimeChartPeriods(value.round() as usize)
+        } else if matches(header, "CONFigure:FFT:SIZE") {
+            SettingsPacket::FftSize(value.round() as usize)
+        } else if matches(header, "CONFigure:HARMonics:REFResh") {
+            SettingsPacket::HarmonicsRefreshPeriod(value as f32)
+        } else if matches(header, "CONFigure:HARMonics:RATed") {
+            SettingsPacket::RatedVoltage(value as f32)
+        } else if matches(header, "CONFigure:RMS:WINDow") {
+            SettingsPacket::Window(value as f32)
+        } else if matches(header, "CONFigure:RMS:CYCLes") {
+            SettingsPacket::Cycles(value.round() as usize)
+        } else if matches(header, "CONFigure:RMS:REFResh") {
+            SettingsPacket::RmsRefreshPeriod(value as f32)
+        } else if matches(header, "CONFigure:CHARt:SIZE") {
+            SettingsPacket::ChartSize(value.round() as usize)
+        } else {
+            self.push_error(ScpiError::Command);
+            return None;
+        };
+
+        if MeasurementConfig::check(&packet, self.buffers.channels.len()).is_err() {
+            self.push_error(ScpiError::OutOfRange);
+            return None;
+        }
+
+        Some(packet)
+    }
+
+    /// Starts or stops the recorder, `ON` and `1` start it, `OFF` and `0` stop it.
+    fn recording(&mut self, parameters: &[&str]) {
+        let Some(parameter) = parameters.first() else {
+            self.push_error(ScpiError::MissingParameter);
+            return;
+        };
+
+        let result = if parameter.eq_ignore_ascii_case("ON") || *parameter == "1" {
+            self.buffers.recorder.start()
+        } else if parameter.eq_ignore_ascii_case("OFF") || *parameter == "0" {
+            self.buffers.recorder.stop()
+        } else {
+            self.push_error(ScpiError::Parameter);
+            return;
+        };
+
+        if result.is_err() {
+            self.push_error(ScpiError::MassStorage);
+        }
+    }
+
+    fn query(&mut self, header: &str, parameters: &[&str]) -> Option<String> {
+        if matches(header, "*IDN") {
+            Some(IDENTIFICATION.to_string())
+        } else if matches(header, "MEASure:VOLTage:RMS") {
+            self.measurement(parameters, |channel| channel.rms)
+        } else if matches(header, "MEASure:VOLTage:PEAK") {
+            self.measurement(parameters, |channel| {
+                channel.peak_sqrt.map(|peak_sqrt| peak_sqrt * SQRT_2)
+            })
+        } else if matches(header, "MEASure:FREQuency") {
+            self.measurement(parameters, |channel| channel.frequency)
+        } else if matches(header, "FETCh:HARMonics") {
+            let channel = self.channel(parameters)?;
+
+            let harmonics = Measurements::read(&self.buffers).channels[channel]
+                .harmonics
+                .clone();
+
+            if harmonics.is_empty() {
+                self.push_error(ScpiError::Stale);
+                return Some(format_number(None));
+            }
+
+            Some(
+                harmonics
+                    .into_iter()
+                    .map(|level| format_number(Some(level)))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            )
+        } else if matches(header, "RECord:STATe") {
+            Some(u8::from(self.buffers.recorder.is_recording()).to_string())
+        } else if matches(header, "SYSTem:ERRor") || matches(header, "SYSTem:ERRor:NEXT") {
+            Some(match self.errors.pop_front() {
+                Some(error) => format!("{},\"{}\"", error.code(), error.message()),
+                None => "0,\"No error\"".to_string(),
+            })
+        } else {
+            self.push_error(ScpiError::Command);
+            None
+        }
+    }
+
+    fn command(&mut self, header: &str, parameters: &[&str]) {
+        if matches(header, "*CLS") {
+            self.errors.clear();
+        } else if header
+            .trim_start_matches(':')
+            .split(':')
+            .next()
+            .is_some_and(|root| matches(root, "CONFigure"))
+        {
+            if let Some(packet) = self.setting(header, parameters) {
+                // the user interface takes over the setting and forwards it to the pipeline
+                let _ = self.remote_settings.send(packet);
+            }
+        } else if matches(header, "RECord:STATe") {
+            self.recording(parameters);
+        } else {
+            self.push_error(ScpiError::Command);
+        }
+    }
+
+    /// Handles a line of `;` separated commands, the answers of all queries are returned together.
+    fn line(&mut self, line: &str) -> Option<String> {
+        let responses = line
+            .split(';')
+            .map(str::trim)
+            .filter(|message| !message.is_empty())
+            .filter_map(|message| {
+                let (header, parameters) = message
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((message, ""));
+
+                let parameters = parameters
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|parameter| !parameter.is_empty())
+                    .collect::<Vec<_>>();
+
+                match header.strip_suffix('?') {
+                    Some(header) => self.query(header, &parameters),
+                    None => {
+                        self.command(header, &parameters);
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<_>>();
+
+        (!responses.is_empty()).then(|| responses.join(";"))
+    }
+}
+
+fn handle_client(stream: TcpStream, buffers: Buffers, remote_settings: Sender<SettingsPacket>) {
+    let mut session = Session {
+        buffers,
+        remote_settings,
+        errors: VecDeque::new(),
+    };
+
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+
+        if let Some(response) = session.line(&line) {
+            if writeln!(writer, "{}", response).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Answers SCPI queries and commands of any number of clients, one line per message. Returns the
+/// address the server is bound to, which tells the port when port 0 is given.
+pub fn serve(
+    address: SocketAddr,
+    buffers: Buffers,
+    remote_settings: Sender<SettingsPacket>,
+) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(address)?;
+    let address = listener.local_addr()?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let buffers = buffers.clone();
+            let remote_settings = remote_settings.clone();
+
+            thread::spawn(move || handle_client(stream, buffers, remote_settings));
+        }
+    });
+
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RecorderConfig;
+    use std::{sync::mpsc, time::Duration};
+
+    struct Client {
+        writer: TcpStream,
+        reader: BufReader<TcpStream>,
+    }
+
+    impl Client {
+        fn connect(address: SocketAddr) -> Self {
+            let stream = TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+
+            Self {
+                writer: stream.try_clone().unwrap(),
+                reader: BufReader::new(stream),
+            }
+        }
+
+        fn send(&mut self, line: &str) {
+            writeln!(self.writer, "{}", line).unwrap();
+        }
+
+        fn query(&mut self, line: &str) -> String {
+            self.send(line);
+
+            let mut response = String::new();
+            self.reader.read_line(&mut response).unwrap();
+            response.trim_end().to_string()
+        }
+    }
+
+    fn numbers(response: &str) -> Vec<f64> {
+        response
+            .split(',')
+            .map(|number| number.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn answers_a_local_client() {
+        let buffers = Buffers::new(1, RecorderConfig::default(), None);
+        let channel = &buffers.channels[0];
+
+        *channel.rms_trend.write().unwrap() = vec![[0.0, 229.0], [1.0, 230.5]];
+        *channel.frequency_widget.write().unwrap() = vec![[1.0, 50.0]];
+        // lines 1 Hz apart up to 160 Hz, so the 3rd harmonic is the last one
+        *channel.harmonics.write().unwrap() = (0..=160)
+            .map(|line| {
+                let level = match line {
+                    50 => 44.0,
+                    100 => 10.0,
+                    150 => 20.0,
+                    _ => -80.0,
+                };
+                [line as f64, level]
+            })
+            .collect();
+
+        let (sender, receiver) = mpsc::channel();
+        let address = serve("127.0.0.1:0".parse().unwrap(), buffers, sender).unwrap();
+
+        let mut client = Client::connect(address);
+
+        assert!(client
+            .query("*IDN?")
+            .starts_with("conductor,peakvoltmeter,0,"));
+        assert_eq!(numbers(&client.query("MEAS:VOLT:RMS?")), [230.5]);
+        assert_eq!(
+            numbers(&client.query("FETC:HARM? (@1)")),
+            [44.0, 10.0, 20.0]
+        );
+        assert_eq!(client.query("SYST:ERR?"), "0,\"No error\"");
+
+        client.send("CONF:FFT:SIZE 4096");
+        assert!(matches!(
+            receiver.recv_timeout(Duration::from_secs(5)),
+            Ok(SettingsPacket::FftSize(4096))
+        ));
+
+        client.send("CONF:FFT:WIND HANN");
+        assert!(matches!(
+            receiver.recv_timeout(Duration::from_secs(5)),
+            Ok(SettingsPacket::FftWindow(FftWindow::Hann))
+        ));
+    }
+
+    #[test]
+    fn reports_unknown_commands() {
+        let buffers = Buffers::new(1, RecorderConfig::default(), None);
+
+        let (sender, receiver) = mpsc::channel();
+        let address = serve("127.0.0.1:0".parse().unwrap(), buffers, sender).unwrap();
+
+        let mut client = Client::connect(address);
+
+        client.send("FOO:BAR 1");
+        client.send("CONF:FOO 1");
+        assert_eq!(client.query("SYST:ERR?"), "-113,\"Undefined header\"");
+        assert_eq!(client.query("SYST:ERR?"), "-113,\"Undefined header\"");
+        assert_eq!(client.query("SYST:ERR?"), "0,\"No error\"");
+
+        // a query that is not known has no answer, only an error
+        assert_eq!(client.query("FOO?;SYST:ERR?"), "-113,\"Undefined header\"");
+
+        assert!(receiver.try_recv().is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{
+    streams::{Frame, Stream},
+    Buffers,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    io::{self, ErrorKind},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{
+        mpsc::{Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use tungstenite::{
+    handshake::server::{Callback, ErrorResponse, Request, Response},
+    http::StatusCode,
+    Error, Message, WebSocket,
+};
+
+// longest delay of a frame while waiting for messages of the client
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+// a client that does not take a frame within this time is disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Format {
+    Json,
+    Binary,
+}
+
+/// Message of a client changing its subscription, missing fields are left unchanged.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Control {
+    subscribe: Option<Vec<Stream>>,
+    unsubscribe: Option<Vec<Stream>>,
+    format: Option<Format>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+struct Subscription {
+    streams: HashSet<Stream>,
+    format: Format,
+}
+
+impl Subscription {
+    /// Subscription requested by the query of the url, `?streams=waveform,rms&format=binary`.
+    /// Without query all streams are sent as JSON.
+    fn from_query(query: Option<&str>) -> Result<Self, String> {
+        let mut subscription = Subscription {
+            streams: Stream::ALL.into_iter().collect(),
+            format: Format::Json,
+        };
+
+        for parameter in query.unwrap_or_default().split('&') {
+            match parameter.split_once('=') {
+                Some(("streams", streams)) => {
+                    subscription.streams = streams
+                        .split(',')
+                        .filter(|stream| !stream.is_empty())
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()?;
+                }
+                Some(("format", "json")) => subscription.format = Format::Json,
+                Some(("format", "binary")) => subscription.format = Format::Binary,
+                Some(("format", format)) => return Err(format!("unknown format `{}`", format)),
+                _ if parameter.is_empty() => {}
+                _ => return Err(format!("unknown parameter `{}`", parameter)),
+            }
+        }
+
+        Ok(subscription)
+    }
+
+    fn apply(&mut self, control: Control) {
+        self.streams.extend(control.subscribe.into_iter().flatten());
+
+        for stream in control.unsubscribe.into_iter().flatten() {
+            self.streams.remove(&stream);
+        }
+
+        if let Some(format) = control.format {
+            self.format = format;
+        }
+    }
+
+    fn message(&self, frame: &Frame) -> Message {
+        match self.format {
+            Format::Json => {
+                Message::Text(serde_json::to_string(frame).expect("frames are always serializable"))
+            }
+            Format::Binary => Message::Binary(encode(frame)),
+        }
+    }
+}
+
+fn stream_id(stream: Stream) -> u8 {
+    match stream {
+        Stream::Waveform => 0,
+        Stream::Spectrum => 1,
+        Stream::Rms => 2,
+        Stream::PeakSqrt => 3,
+        Stream::Frequency => 4,
+    }
+}
+
+/// Binary frame, little endian: stream `u8`, channel `u8`, 2 reserved bytes, value count `u32`,
+/// time `f64`, step `f64`, then the values as `f32`.
+fn encode(frame: &Frame) -> Vec<u8> {
+    let mut message = Vec::with_capacity(24 + frame.values.len() * 4);
+
+    message.push(stream_id(frame.stream));
+    message.push(frame.channel as u8);
+    message.extend_from_slice(&[0; 2]);
+    message.extend_from_slice(&(frame.values.len() as u32).to_le_bytes());
+    message.extend_from_slice(&frame.time.to_le_bytes());
+    message.extend_from_slice(&frame.step.to_le_bytes());
+
+    for value in &frame.values {
+        message.extend_from_slice(&(*value as f32).to_le_bytes());
+    }
+
+    message
+}
+
+fn error_message(error: impl Into<String>) -> Message {
+    Message::Text(
+        serde_json::to_string(&ErrorBody {
+            error: error.into(),
+        })
+        .expect("errors are always serializable"),
+    )
+}
+
+fn is_timeout(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Io(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+    )
+}
+
+/// Sends the subscribed frames until the client is gone.
+fn stream_frames(
+    mut socket: WebSocket<TcpStream>,
+    mut subscription: Subscription,
+    frames: Receiver<Arc<Frame>>,
+) {
+    loop {
+        // messages of the client are checked between the frames
+        let sent = match socket.read() {
+            Ok(Message::Text(text)) => match serde_json::from_str::<Control>(&text) {
+                Ok(control) => {
+                    subscription.apply(control);
+                    Ok(())
+                }
+                Err(e) => socket.send(error_message(e.to_string())),
+            },
+            Ok(_) => Ok(()),
+            Err(e) if is_timeout(&e) => Ok(()),
+            Err(e) => Err(e),
+        };
+
+        if sent.is_err() {
+            return;
+        }
+
+        loop {
+            let frame = match frames.try_recv() {
+                Ok(frame) => frame,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            };
+
+            if subscription.streams.contains(&frame.stream)
+                && socket.send(subscription.message(&frame)).is_err()
+            {
+                return;
+            }
+        }
+    }
+}
+
+/// Takes the subscription from the url of the handshake, an invalid one is rejected before the
+/// connection is upgraded.
+struct Handshake<'a> {
+    subscription: &'a mut Option<Subscription>,
+}
+
+impl Callback for Handshake<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        match Subscription::from_query(request.uri().query()) {
+            Ok(subscription) => {
+                *self.subscription = Some(subscription);
+                Ok(response)
+            }
+            Err(e) => {
+                let mut response = ErrorResponse::new(Some(e));
+                *response.status_mut() = StatusCode::BAD_REQUEST;
+                Err(response)
+            }
+        }
+    }
+}
+
+fn handle_client(stream: TcpStream, buffers: Buffers) {
+    let mut subscription = None;
+
+    let socket = tungstenite::accept_hdr(
+        stream,
+        Handshake {
+            subscription: &mut subscription,
+        },
+    );
+
+    let Ok(socket) = socket else {
+        return;
+    };
+
+    let Some(subscription) = subscription else {
+        return;
+    };
+
+    if socket
+        .get_ref()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .and_then(|_| socket.get_ref().set_write_timeout(Some(WRITE_TIMEOUT)))
+        .is_err()
+    {
+        return;
+    }
+
+    stream_frames(socket, subscription, buffers.streams.subscribe());
+}
+
+/// Streams the frames of the charts to any number of clients, each one receives the streams it
+/// subscribed to.
+pub fn serve(address: SocketAddr, buffers: Buffers) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let buffers = buffers.clone();
+
+            thread::spawn(move || handle_client(stream, buffers));
+        }
+    });
+
+    Ok(())
+}
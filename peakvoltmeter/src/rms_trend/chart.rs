@@ -1,12 +1,19 @@
-use crate::settings::{ChartSize, RefreshPeriod};
+use crate::{
+    cycle_measurement::{CycleValues, MeasurementMode},
+    settings::{ChartSize, RefreshPeriod},
+    streams::Publisher,
+};
 use conductor::prelude::*;
 use std::sync::{Arc, RwLock};
 
 struct ChartRunner {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 
     input: NodeRunnerInputPort<Vec<f32>>,
+    cycle_values: NodeRunnerInputPort<Option<CycleValues>>,
 
+    mode: NodeRunnerInputPort<MeasurementMode>,
     chart_size: NodeRunnerInputPort<ChartSize>,
     refresh_period: NodeRunnerInputPort<RefreshPeriod>,
 }
@@ -21,21 +28,35 @@ impl NodeRunner for ChartRunner {
             (chart_size as f32 / refresh_period) as usize + 1
         }
 
+        let mut mode = self.mode.recv();
         let mut chart_size = self.chart_size.recv();
         let mut refresh_period = self.refresh_period.recv();
 
         let mut rms_data = CircularBuffer::new(calculate_buffer_size(chart_size, refresh_period));
+        // latest values of the whole cycles, taken over at the pace of the windows
+        let mut cycle_values = None;
 
         loop {
             receive! {
                 (self.input): buffer => {
-                    let rms = (buffer
-                        .iter()
-                        .fold(0.0, |acc, &v| acc + (v as f64 * v as f64)) / buffer.len() as f64)
-                        .sqrt();
+                    let rms = match mode {
+                        MeasurementMode::Window => (buffer
+                            .iter()
+                            .fold(0.0, |acc, &v| acc + (v as f64 * v as f64)) / buffer.len() as f64)
+                            .sqrt(),
+                        MeasurementMode::Cycles => {
+                            let Some(CycleValues { rms, .. }) = cycle_values else {
+                                continue;
+                            };
+
+                            rms
+                        }
+                    };
 
                     rms_data.push(rms);
 
+                    self.publisher.publish(0.0, || vec![rms]);
+
                     *self.data.write().unwrap() = rms_data
                         .clone()
                         .into_iter()
@@ -43,6 +64,15 @@ impl NodeRunner for ChartRunner {
                         .map(|(i, v)| [index_to_time(i, rms_data.len(), refresh_period), v])
                         .collect();
                 },
+                (self.cycle_values): new_cycle_values => {
+                    cycle_values = new_cycle_values;
+                },
+                (self.mode): new_mode => {
+                    mode = new_mode;
+
+                    // previous data is invalidated so new buffer must be created
+                    rms_data = CircularBuffer::new(calculate_buffer_size(chart_size, refresh_period));
+                },
                 (self.chart_size): new_chart_size => {
                     chart_size = new_chart_size;
 
@@ -61,20 +91,26 @@ impl NodeRunner for ChartRunner {
 
 pub struct Chart {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 
     pub input: NodeConfigInputPort<Vec<f32>>,
+    pub cycle_values: NodeConfigInputPort<Option<CycleValues>>,
 
+    pub mode: NodeConfigInputPort<MeasurementMode>,
     pub chart_size: NodeConfigInputPort<ChartSize>,
     pub refresh_period: NodeConfigInputPort<RefreshPeriod>,
 }
 
 impl Chart {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>, publisher: Publisher) -> Self {
         Self {
             data,
+            publisher,
 
             input: NodeConfigInputPort::new(),
+            cycle_values: NodeConfigInputPort::new(),
 
+            mode: NodeConfigInputPort::new(),
             chart_size: NodeConfigInputPort::new(),
             refresh_period: NodeConfigInputPort::new(),
         }
@@ -85,9 +121,12 @@ impl NodeConfig for Chart {
     fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
         Box::new(ChartRunner {
             data: self.data,
+            publisher: self.publisher,
 
             input: self.input.into(),
+            cycle_values: self.cycle_values.into(),
 
+            mode: self.mode.into(),
             chart_size: self.chart_size.into(),
             refresh_period: self.refresh_period.into(),
         })
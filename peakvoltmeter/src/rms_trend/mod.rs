@@ -2,17 +2,23 @@ mod chart;
 
 use crate::{
     application::{calculate_precision, Precision, VoltageUnit},
+    channel::{channel_color, channel_name, channel_views, ChannelLayout},
     coordinates_formatter,
-    settings::{ChartSize, RefreshPeriod, RmsWindow, SampleRate},
+    cycle_measurement::{CycleValues, MeasurementMode},
+    settings::{Channel, ChartSize, RefreshPeriod, RmsWindow, SampleRate},
+    streams::Publisher,
 };
 use chart::Chart;
 use conductor::{core::pipeline::Pipeline, prelude::*};
-use egui::{Color32, RichText, Vec2b};
-use egui_plot::{Line, Plot, PlotPoints};
+use egui::{RichText, Vec2b};
+use egui_plot::{Legend, Line, Plot, PlotPoints};
 use std::sync::{Arc, RwLock};
 
 pub struct RmsTrendInputPorts {
     pub data: NodeConfigInputPort<f32>,
+    // values of the whole cycles, shown instead of the windows in the cycles mode
+    pub cycle_values: NodeConfigInputPort<Option<CycleValues>>,
+    pub mode: NodeConfigInputPort<MeasurementMode>,
     pub sample_rate: (
         NodeConfigInputPort<SampleRate>,
         NodeConfigInputPort<SampleRate>,
@@ -31,6 +37,7 @@ pub struct RmsTrendOutputPorts {
 
 pub fn rms_trend(
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    publisher: Publisher,
 ) -> Pipeline<RmsTrendInputPorts, RmsTrendOutputPorts> {
     let buffer_size = Multiply::new();
 
@@ -44,7 +51,7 @@ pub fn rms_trend(
 
     let refresh_period_downsampler = Downsample::new();
 
-    let chart = Chart::new(data);
+    let chart = Chart::new(data, publisher);
 
     buffer_size.output.connect(&buffer_size_to_usize.input);
 
@@ -64,6 +71,8 @@ pub fn rms_trend(
 
     let input_ports = RmsTrendInputPorts {
         data: buffer.input.clone(),
+        cycle_values: chart.cycle_values.clone(),
+        mode: chart.mode.clone(),
         sample_rate: (buffer_size.input2.clone(), refresh_factor.input2.clone()),
         window: buffer_size.input1.clone(),
         chart_size: chart.chart_size.clone(),
@@ -90,13 +99,14 @@ pub fn rms_trend(
 }
 
 pub struct RmsTrend {
-    data: Arc<RwLock<Vec<[f64; 2]>>>,
+    // one per channel
+    data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>,
 
     prev_chart_size: f64,
 }
 
 impl RmsTrend {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>) -> Self {
         Self {
             data,
             prev_chart_size: f64::NEG_INFINITY,
@@ -106,6 +116,7 @@ impl RmsTrend {
     pub fn ui(
         &mut self,
         ui: &mut egui::Ui,
+        layout: ChannelLayout,
         chart_size: ChartSize,
         unit: VoltageUnit,
         precision: Precision,
@@ -117,51 +128,61 @@ impl RmsTrend {
 
             let chart_size = chart_size as f64;
 
-            let mut plot = Plot::new("RMS Trend")
-                .auto_bounds(Vec2b::new(false, true))
-                .y_axis_label("Voltage")
-                .x_axis_label("Time")
-                .allow_boxed_zoom(false)
-                .allow_drag(false)
-                .allow_zoom(false)
-                .allow_scroll(false)
-                .label_formatter(|_, _| "".to_owned())
-                .coordinates_formatter(
-                    egui_plot::Corner::LeftTop,
-                    coordinates_formatter(unit, precision),
-                )
-                .x_axis_formatter(|grid_mark, range| {
-                    format!(
-                        "{:.precision$} s",
-                        grid_mark.value,
-                        precision = calculate_precision(range)
+            // We need to check if the chart size has changed to reset the plots, otherwise the
+            // plots will not update the chart size.
+            let reset = (self.prev_chart_size - chart_size).abs() > f64::EPSILON;
+            self.prev_chart_size = chart_size;
+
+            channel_views(ui, layout, self.data.len(), |ui, channels| {
+                let mut plot = Plot::new(("RMS Trend", channels.start))
+                    .auto_bounds(Vec2b::new(false, true))
+                    .y_axis_label("Voltage")
+                    .x_axis_label("Time")
+                    .allow_boxed_zoom(false)
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .allow_scroll(false)
+                    .label_formatter(|_, _| "".to_owned())
+                    .coordinates_formatter(
+                        egui_plot::Corner::LeftTop,
+                        coordinates_formatter(unit, precision),
                     )
-                })
-                .y_axis_formatter(|grid_mark, range| {
-                    unit.apply_unit_with_precision(grid_mark.value, calculate_precision(range))
-                })
-                .include_y(0.0)
-                .include_x(0.0)
-                .include_x(-chart_size);
-
-            // We need to check if the chart size has changed to reset the plot, otherwise the
-            // plot will not update the chart size.
-            if (self.prev_chart_size - chart_size).abs() > f64::EPSILON {
-                plot = plot.reset();
-                self.prev_chart_size = chart_size;
-            }
-
-            plot.show(ui, |plot_ui| {
-                plot_ui.line(self.signal());
+                    .x_axis_formatter(|grid_mark, range| {
+                        format!(
+                            "{:.precision$} s",
+                            grid_mark.value,
+                            precision = calculate_precision(range)
+                        )
+                    })
+                    .y_axis_formatter(|grid_mark, range| {
+                        unit.apply_unit_with_precision(grid_mark.value, calculate_precision(range))
+                    })
+                    .include_y(0.0)
+                    .include_x(0.0)
+                    .include_x(-chart_size);
+
+                if self.data.len() > 1 {
+                    plot = plot.legend(Legend::default());
+                }
+
+                if reset {
+                    plot = plot.reset();
+                }
+
+                plot.show(ui, |plot_ui| {
+                    for channel in channels {
+                        plot_ui.line(self.signal(channel));
+                    }
+                });
             });
         });
     }
 
-    fn signal(&self) -> Line {
-        let plot_points = PlotPoints::from_iter(self.data.read().unwrap().clone());
+    fn signal(&self, channel: Channel) -> Line {
+        let plot_points = PlotPoints::from_iter(self.data[channel].read().unwrap().clone());
 
         Line::new(plot_points)
-            .color(Color32::LIGHT_BLUE)
-            .name("Signal")
+            .color(channel_color(channel))
+            .name(channel_name(channel))
     }
 }
@@ -1,7 +1,8 @@
 use crate::{
     application::{calculate_precision, Precision, VoltageUnit},
+    channel::{channel_color, channel_name, channel_views, ChannelLayout},
     coordinates_formatter,
-    settings::ChartSize,
+    settings::{Channel, ChartSize},
     DARK_GRAY,
 };
 use core::f64;
@@ -11,13 +12,14 @@ use egui_plot::{Line, Plot, PlotPoints};
 use std::sync::{Arc, RwLock};
 
 pub struct RmsWidget {
-    data: Arc<RwLock<Vec<[f64; 2]>>>,
+    // one per channel
+    data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>,
 
     prev_chart_size: f64,
 }
 
 impl RmsWidget {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>) -> Self {
         Self {
             data,
             prev_chart_size: f64::NEG_INFINITY,
@@ -27,6 +29,7 @@ impl RmsWidget {
     pub fn ui(
         &mut self,
         ui: &mut egui::Ui,
+        layout: ChannelLayout,
         chart_size: ChartSize,
         unit: VoltageUnit,
         precision: Precision,
@@ -50,74 +53,91 @@ impl RmsWidget {
 
                     ui.label(RichText::new("V RMS").size(16.0));
 
-                    let last_value = self
-                        .data
-                        .read()
-                        .unwrap()
-                        .last()
-                        .map(|v| v[1])
-                        .unwrap_or(0.0);
-
-                    ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
-                        ui.label(
-                            RichText::new(unit.apply_unit_with_precision(last_value, precision))
-                                .size(30.0)
-                                .strong(),
-                        );
-                    });
-
                     let chart_size = chart_size as f64;
 
-                    let mut plot = Plot::new("RMS Widget")
-                        .auto_bounds(Vec2b::new(false, true))
-                        .y_axis_label("Voltage")
-                        .x_axis_label("Time")
-                        .allow_boxed_zoom(false)
-                        .allow_drag(false)
-                        .allow_zoom(false)
-                        .allow_scroll(false)
-                        .label_formatter(|_, _| "".to_owned())
-                        .coordinates_formatter(
-                            egui_plot::Corner::LeftTop,
-                            coordinates_formatter(unit, precision),
-                        )
-                        .x_axis_formatter(|grid_mark, range| {
-                            format!(
-                                "{:.precision$} s",
-                                grid_mark.value,
-                                precision = calculate_precision(range)
-                            )
-                        })
-                        .y_axis_formatter(|grid_mark, range| {
-                            unit.apply_unit_with_precision(
-                                grid_mark.value,
-                                calculate_precision(range),
+                    // We need to check if the chart size has changed to reset the plots, otherwise the
+                    // plots will not update the chart size.
+                    let reset = (self.prev_chart_size - chart_size).abs() > f64::EPSILON;
+                    self.prev_chart_size = chart_size;
+
+                    channel_views(ui, layout, self.data.len(), |ui, channels| {
+                        for channel in channels.clone() {
+                            let last_value = self.data[channel]
+                                .read()
+                                .unwrap()
+                                .last()
+                                .map(|v| v[1])
+                                .unwrap_or(0.0);
+
+                            ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                                ui.label(
+                                    RichText::new(
+                                        unit.apply_unit_with_precision(last_value, precision),
+                                    )
+                                    .size(30.0)
+                                    .strong(),
+                                );
+
+                                if self.data.len() > 1 {
+                                    ui.label(
+                                        RichText::new(channel_name(channel))
+                                            .size(16.0)
+                                            .color(channel_color(channel)),
+                                    );
+                                }
+                            });
+                        }
+
+                        let mut plot = Plot::new(("RMS Widget", channels.start))
+                            .auto_bounds(Vec2b::new(false, true))
+                            .y_axis_label("Voltage")
+                            .x_axis_label("Time")
+                            .allow_boxed_zoom(false)
+                            .allow_drag(false)
+                            .allow_zoom(false)
+                            .allow_scroll(false)
+                            .label_formatter(|_, _| "".to_owned())
+                            .coordinates_formatter(
+                                egui_plot::Corner::LeftTop,
+                                coordinates_formatter(unit, precision),
                             )
-                        })
-                        .include_y(0.0)
-                        .include_x(0.0)
-                        .include_x(-chart_size);
-
-                    // We need to check if the chart size has changed to reset the plot, otherwise the
-                    // plot will not update the chart size.
-                    if (self.prev_chart_size - chart_size).abs() > f64::EPSILON {
-                        plot = plot.reset();
-                        self.prev_chart_size = chart_size;
-                    }
-
-                    plot.show(ui, |plot_ui| {
-                        plot_ui.line(self.signal());
+                            .x_axis_formatter(|grid_mark, range| {
+                                format!(
+                                    "{:.precision$} s",
+                                    grid_mark.value,
+                                    precision = calculate_precision(range)
+                                )
+                            })
+                            .y_axis_formatter(|grid_mark, range| {
+                                unit.apply_unit_with_precision(
+                                    grid_mark.value,
+                                    calculate_precision(range),
+                                )
+                            })
+                            .include_y(0.0)
+                            .include_x(0.0)
+                            .include_x(-chart_size);
+
+                        if reset {
+                            plot = plot.reset();
+                        }
+
+                        plot.show(ui, |plot_ui| {
+                            for channel in channels {
+                                plot_ui.line(self.signal(channel));
+                            }
+                        });
                     });
                 });
             },
         );
     }
 
-    fn signal(&self) -> Line {
-        let plot_points = PlotPoints::from_iter(self.data.read().unwrap().clone());
+    fn signal(&self, channel: Channel) -> Line {
+        let plot_points = PlotPoints::from_iter(self.data[channel].read().unwrap().clone());
 
         Line::new(plot_points)
-            .color(Color32::LIGHT_BLUE)
-            .name("Signal")
+            .color(channel_color(channel))
+            .name(channel_name(channel))
     }
 }
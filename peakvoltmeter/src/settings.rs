@@ -1,106 +1,212 @@
+use crate::{
+    config::MeasurementConfig,
+    cycle_measurement::MeasurementMode,
+    frequency_widget::FrequencyEstimator,
+    harmonics::FftWindow,
+    ingest::{GapHandling, PacketFormat, SampleLayout, Source},
+};
 use conductor::prelude::*;
-use std::sync::mpsc::Receiver;
+use std::sync::{mpsc::Receiver, Arc, RwLock};
 
 pub type SampleRate = f32;
 pub type CalibrationFactor = f32;
+pub type Channel = usize;
 pub type TimeChartPeriods = usize;
 pub type FftSize = usize;
 pub type RmsWindow = f32;
+// whole cycles of the signal
+pub type Cycles = usize;
 pub type ChartSize = usize;
 pub type RefreshPeriod = f32;
+// rms volts
+pub type RatedVoltage = f32;
 
+#[derive(Clone)]
 pub enum SettingsPacket {
     // signal settings
     SampleRate(SampleRate),
-    CalibrationFactor(CalibrationFactor),
+    CalibrationFactor(Channel, CalibrationFactor),
+
+    // network settings
+    Source(Source),
+    PacketFormat(PacketFormat),
+    SampleLayout(SampleLayout),
+    GapHandling(GapHandling),
 
     // time chart settings
     TimeChartPeriods(TimeChartPeriods),
 
     // harmonics settings
     FftSize(FftSize),
+    FftWindow(FftWindow),
     HarmonicsRefreshPeriod(RefreshPeriod),
+    // reference of the total demand distortion
+    RatedVoltage(RatedVoltage),
+    FrequencyEstimator(FrequencyEstimator),
 
     // rms trend and peak sqrt settings
     Window(RmsWindow),
+    MeasurementMode(MeasurementMode),
+    Cycles(Cycles),
     ChartSize(ChartSize),
     RmsRefreshPeriod(RefreshPeriod),
+
+    // applied together, no other packet is handled in between
+    Batch(Vec<SettingsPacket>),
 }
 
 struct SettingsRunner {
     receiver: Receiver<SettingsPacket>,
+    measurement: Arc<RwLock<MeasurementConfig>>,
 
     sample_rate: NodeRunnerOutputPort<SampleRate>,
-    calibration_factor: NodeRunnerOutputPort<CalibrationFactor>,
+    calibration_factors: Vec<NodeRunnerOutputPort<CalibrationFactor>>,
+    source: NodeRunnerOutputPort<Source>,
+    packet_format: NodeRunnerOutputPort<PacketFormat>,
+    sample_layout: NodeRunnerOutputPort<SampleLayout>,
+    gap_handling: NodeRunnerOutputPort<GapHandling>,
     time_chartperiods: NodeRunnerOutputPort<TimeChartPeriods>,
     fft_size: NodeRunnerOutputPort<FftSize>,
+    fft_window: NodeRunnerOutputPort<FftWindow>,
     harmonics_refresh_period: NodeRunnerOutputPort<RefreshPeriod>,
+    rated_voltage: NodeRunnerOutputPort<RatedVoltage>,
+    frequency_estimator: NodeRunnerOutputPort<FrequencyEstimator>,
     window: NodeRunnerOutputPort<RmsWindow>,
+    measurement_mode: NodeRunnerOutputPort<MeasurementMode>,
+    cycles: NodeRunnerOutputPort<Cycles>,
     chart_size: NodeRunnerOutputPort<ChartSize>,
     rms_refresh_period: NodeRunnerOutputPort<RefreshPeriod>,
 }
 
+impl SettingsRunner {
+    fn apply(&self, packet: SettingsPacket) {
+        match packet {
+            SettingsPacket::SampleRate(sample_rate) => {
+                self.sample_rate.send(&sample_rate);
+            }
+            SettingsPacket::CalibrationFactor(channel, calibration_factor) => {
+                if let Some(output) = self.calibration_factors.get(channel) {
+                    output.send(&calibration_factor);
+                }
+            }
+            SettingsPacket::Source(source) => {
+                self.source.send(&source);
+            }
+            SettingsPacket::PacketFormat(packet_format) => {
+                self.packet_format.send(&packet_format);
+            }
+            SettingsPacket::SampleLayout(sample_layout) => {
+                self.sample_layout.send(&sample_layout);
+            }
+            SettingsPacket::GapHandling(gap_handling) => {
+                self.gap_handling.send(&gap_handling);
+            }
+            SettingsPacket::TimeChartPeriods(periods) => {
+                self.time_chartperiods.send(&periods);
+            }
+            SettingsPacket::FftSize(fft_size) => {
+                self.fft_size.send(&fft_size);
+            }
+            SettingsPacket::FftWindow(fft_window) => {
+                self.fft_window.send(&fft_window);
+            }
+            SettingsPacket::HarmonicsRefreshPeriod(refresh_period) => {
+                self.harmonics_refresh_period.send(&refresh_period);
+            }
+            SettingsPacket::FrequencyEstimator(frequency_estimator) => {
+                self.frequency_estimator.send(&frequency_estimator);
+            }
+            SettingsPacket::RatedVoltage(rated_voltage) => {
+                self.rated_voltage.send(&rated_voltage);
+            }
+            SettingsPacket::Window(window) => {
+                self.window.send(&window);
+            }
+            SettingsPacket::MeasurementMode(measurement_mode) => {
+                self.measurement_mode.send(&measurement_mode);
+            }
+            SettingsPacket::Cycles(cycles) => {
+                self.cycles.send(&cycles);
+            }
+            SettingsPacket::ChartSize(chart_size) => {
+                self.chart_size.send(&chart_size);
+            }
+            SettingsPacket::RmsRefreshPeriod(refresh_period) => {
+                self.rms_refresh_period.send(&refresh_period);
+            }
+            SettingsPacket::Batch(packets) => {
+                for packet in packets {
+                    self.apply(packet);
+                }
+            }
+        }
+    }
+}
+
 impl NodeRunner for SettingsRunner {
     fn run(self: Box<Self>) {
         loop {
-            let Ok(value) = self.receiver.recv() else {
+            let Ok(packet) = self.receiver.recv() else {
                 break;
             };
 
-            match value {
-                SettingsPacket::SampleRate(sample_rate) => {
-                    self.sample_rate.send(&sample_rate);
-                }
-                SettingsPacket::CalibrationFactor(calibration_factor) => {
-                    self.calibration_factor.send(&calibration_factor);
-                }
-                SettingsPacket::TimeChartPeriods(periods) => {
-                    self.time_chartperiods.send(&periods);
-                }
-                SettingsPacket::FftSize(fft_size) => {
-                    self.fft_size.send(&fft_size);
-                }
-                SettingsPacket::HarmonicsRefreshPeriod(refresh_period) => {
-                    self.harmonics_refresh_period.send(&refresh_period);
-                }
-                SettingsPacket::Window(window) => {
-                    self.window.send(&window);
-                }
-                SettingsPacket::ChartSize(chart_size) => {
-                    self.chart_size.send(&chart_size);
-                }
-                SettingsPacket::RmsRefreshPeriod(refresh_period) => {
-                    self.rms_refresh_period.send(&refresh_period);
-                }
-            }
+            self.measurement.write().unwrap().apply(&packet);
+
+            self.apply(packet);
         }
     }
 }
 
 pub struct Settings {
     receiver: Receiver<SettingsPacket>,
+    // measurement settings sent to the pipeline so far
+    measurement: Arc<RwLock<MeasurementConfig>>,
 
     pub sample_rate: NodeConfigOutputPort<SampleRate>,
-    pub calibration_factor: NodeConfigOutputPort<CalibrationFactor>,
+    // one per channel
+    pub calibration_factors: Vec<NodeConfigOutputPort<CalibrationFactor>>,
+    pub source: NodeConfigOutputPort<Source>,
+    pub packet_format: NodeConfigOutputPort<PacketFormat>,
+    pub sample_layout: NodeConfigOutputPort<SampleLayout>,
+    pub gap_handling: NodeConfigOutputPort<GapHandling>,
     pub time_chart_periods: NodeConfigOutputPort<TimeChartPeriods>,
     pub fft_size: NodeConfigOutputPort<FftSize>,
+    pub fft_window: NodeConfigOutputPort<FftWindow>,
     pub harmonics_refresh_period: NodeConfigOutputPort<RefreshPeriod>,
+    pub rated_voltage: NodeConfigOutputPort<RatedVoltage>,
+    pub frequency_estimator: NodeConfigOutputPort<FrequencyEstimator>,
     pub window: NodeConfigOutputPort<RmsWindow>,
+    pub measurement_mode: NodeConfigOutputPort<MeasurementMode>,
+    pub cycles: NodeConfigOutputPort<Cycles>,
     pub chart_size: NodeConfigOutputPort<ChartSize>,
     pub rms_refresh_period: NodeConfigOutputPort<RefreshPeriod>,
 }
 
 impl Settings {
-    pub fn new(receiver: Receiver<SettingsPacket>) -> Self {
+    pub fn new(
+        receiver: Receiver<SettingsPacket>,
+        channels: usize,
+        measurement: Arc<RwLock<MeasurementConfig>>,
+    ) -> Self {
         Self {
             receiver,
+            measurement,
 
             sample_rate: NodeConfigOutputPort::new(),
-            calibration_factor: NodeConfigOutputPort::new(),
+            calibration_factors: (0..channels).map(|_| NodeConfigOutputPort::new()).collect(),
+            source: NodeConfigOutputPort::new(),
+            packet_format: NodeConfigOutputPort::new(),
+            sample_layout: NodeConfigOutputPort::new(),
+            gap_handling: NodeConfigOutputPort::new(),
             time_chart_periods: NodeConfigOutputPort::new(),
             fft_size: NodeConfigOutputPort::new(),
+            fft_window: NodeConfigOutputPort::new(),
             harmonics_refresh_period: NodeConfigOutputPort::new(),
+            rated_voltage: NodeConfigOutputPort::new(),
+            frequency_estimator: NodeConfigOutputPort::new(),
             window: NodeConfigOutputPort::new(),
+            measurement_mode: NodeConfigOutputPort::new(),
+            cycles: NodeConfigOutputPort::new(),
             chart_size: NodeConfigOutputPort::new(),
             rms_refresh_period: NodeConfigOutputPort::new(),
         }
@@ -111,12 +217,26 @@ impl NodeConfig for Settings {
     fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
         Box::new(SettingsRunner {
             receiver: self.receiver,
+            measurement: self.measurement,
             sample_rate: self.sample_rate.into(),
-            calibration_factor: self.calibration_factor.into(),
+            calibration_factors: self
+                .calibration_factors
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            source: self.source.into(),
+            packet_format: self.packet_format.into(),
+            sample_layout: self.sample_layout.into(),
+            gap_handling: self.gap_handling.into(),
             time_chartperiods: self.time_chart_periods.into(),
             fft_size: self.fft_size.into(),
+            fft_window: self.fft_window.into(),
             harmonics_refresh_period: self.harmonics_refresh_period.into(),
+            rated_voltage: self.rated_voltage.into(),
+            frequency_estimator: self.frequency_estimator.into(),
             window: self.window.into(),
+            measurement_mode: self.measurement_mode.into(),
+            cycles: self.cycles.into(),
             chart_size: self.chart_size.into(),
             rms_refresh_period: self.rms_refresh_period.into(),
         })
@@ -0,0 +1,140 @@
+use crate::settings::Channel;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    sync::{
+        mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// frames queued per subscriber, a subscriber that falls further behind misses frames
+const SUBSCRIBER_CAPACITY: usize = 64;
+
+/// Data published by the charts of the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stream {
+    // every capture of the time chart
+    Waveform,
+    // every spectrum of the harmonics chart, in dBV
+    Spectrum,
+    // every new value of the trend and widget charts
+    Rms,
+    PeakSqrt,
+    Frequency,
+}
+
+impl Stream {
+    pub const ALL: [Stream; 5] = [
+        Stream::Waveform,
+        Stream::Spectrum,
+        Stream::Rms,
+        Stream::PeakSqrt,
+        Stream::Frequency,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stream::Waveform => "waveform",
+            Stream::Spectrum => "spectrum",
+            Stream::Rms => "rms",
+            Stream::PeakSqrt => "peak-sqrt",
+            Stream::Frequency => "frequency",
+        }
+    }
+}
+
+impl FromStr for Stream {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Stream::ALL
+            .into_iter()
+            .find(|stream| stream.name() == value.trim())
+            .ok_or_else(|| format!("unknown stream `{}`", value.trim()))
+    }
+}
+
+impl Display for Stream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A single update of a stream. The values are evenly spaced by `step`, seconds for waveforms
+/// and hertz for spectra, single values have no step.
+#[derive(Debug, Clone, Serialize)]
+pub struct Frame {
+    pub stream: Stream,
+    pub channel: Channel,
+    // seconds since the unix epoch
+    pub time: f64,
+    pub step: f64,
+    pub values: Vec<f64>,
+}
+
+/// Subscriptions to the frames of all streams, shared between the pipeline and its consumers.
+#[derive(Clone, Default)]
+pub struct Streams {
+    subscribers: Arc<Mutex<Vec<SyncSender<Arc<Frame>>>>>,
+}
+
+impl Streams {
+    /// Receives all frames published from now on, until the receiver is dropped.
+    pub fn subscribe(&self) -> Receiver<Arc<Frame>> {
+        let (sender, receiver) = sync_channel(SUBSCRIBER_CAPACITY);
+
+        self.subscribers.lock().unwrap().push(sender);
+
+        receiver
+    }
+
+    pub fn publisher(&self, stream: Stream, channel: Channel) -> Publisher {
+        Publisher {
+            streams: self.clone(),
+            stream,
+            channel,
+        }
+    }
+}
+
+/// Publishes the frames of one stream of one channel.
+#[derive(Clone)]
+pub struct Publisher {
+    streams: Streams,
+    stream: Stream,
+    channel: Channel,
+}
+
+impl Publisher {
+    /// Sends a frame to all subscribers. The values are only collected if there is a subscriber,
+    /// the charts publish on every update.
+    pub fn publish(&self, step: f64, values: impl FnOnce() -> Vec<f64>) {
+        let mut subscribers = self.streams.subscribers.lock().unwrap();
+
+        if subscribers.is_empty() {
+            return;
+        }
+
+        let frame = Arc::new(Frame {
+            stream: self.stream,
+            channel: self.channel,
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0.0, |time| time.as_secs_f64()),
+            step,
+            values: values(),
+        });
+
+        // a full queue drops the frame for a slow subscriber, a closed one ends its subscription
+        subscribers.retain(|subscriber| {
+            !matches!(
+                subscriber.try_send(frame.clone()),
+                Err(TrySendError::Disconnected(_))
+            )
+        });
+    }
+}
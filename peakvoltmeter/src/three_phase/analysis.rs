@@ -0,0 +1,303 @@
+use crate::{
+    frequency_widget::{interpolate, FrequencyEstimator},
+    harmonics::{line_rms, FftWindow},
+};
+use conductor::prelude::*;
+use rustfft::num_complex::Complex;
+use std::{
+    collections::VecDeque,
+    f64::consts::FRAC_PI_3,
+    sync::{Arc, RwLock},
+};
+
+// spectra waiting for the matching spectra of the other phases, older ones are dropped
+const MAX_PENDING_SPECTRA: usize = 16;
+
+#[derive(Clone, Copy)]
+pub struct ThreePhase {
+    // fundamental of every phase as rms phasor, the angle is relative to the first phase
+    pub phases: [Complex<f64>; 3],
+    // rms of the line-to-line voltages ab, bc and ca
+    pub line_to_line: [f64; 3],
+
+    pub positive_sequence: Complex<f64>,
+    pub negative_sequence: Complex<f64>,
+    pub zero_sequence: Complex<f64>,
+
+    // voltage unbalance factor, negative sequence in percent of positive sequence
+    pub unbalance: f64,
+}
+
+impl ThreePhase {
+    /// Analyses the windowed spectra of the three phases at the fundamental of the first
+    /// phase, `None` if the spectra do not belong together.
+    fn from_spectra(spectra: &[Vec<Complex<f32>>; 3], window: FftWindow) -> Option<Self> {
+        let fft_size = spectra[0].len();
+
+        if fft_size < 4 || spectra.iter().any(|spectrum| spectrum.len() != fft_size) {
+            return None;
+        }
+
+        // the fundamental mostly lies between two lines, the line closest to it is read
+        let fundamental = interpolate(&spectra[0], window, FrequencyEstimator::Jacobsen)?;
+        let line = fundamental.round() as usize;
+
+        // rms of a unit line, corrected by the share of the sine the window leaves on the line,
+        // the phase is kept
+        let scale = line_rms(line, 1.0, fft_size) / window.kernel(line as f64 - fundamental);
+
+        let phasors = spectra.each_ref().map(|spectrum| {
+            let value = spectrum[line];
+            Complex::new(value.re as f64, value.im as f64) * scale
+        });
+
+        // rotating all phasors keeps their relation, with the first phase as reference
+        let reference = Complex::from_polar(1.0, -phasors[0].arg());
+        let [a, b, c] = phasors.map(|phasor| phasor * reference);
+
+        let operator = Complex::from_polar(1.0, 2.0 * FRAC_PI_3);
+        let operator_squared = operator * operator;
+
+        let positive_sequence = (a + operator * b + operator_squared * c) / 3.0;
+        let negative_sequence = (a + operator_squared * b + operator * c) / 3.0;
+        let zero_sequence = (a + b + c) / 3.0;
+
+        let unbalance = if positive_sequence.norm() > 0.0 {
+            negative_sequence.norm() / positive_sequence.norm() * 100.0
+        } else {
+            0.0
+        };
+
+        Some(Self {
+            phases: [a, b, c],
+            line_to_line: [(a - b).norm(), (b - c).norm(), (c - a).norm()],
+            positive_sequence,
+            negative_sequence,
+            zero_sequence,
+            unbalance,
+        })
+    }
+}
+
+struct ThreePhaseAnalysisRunner {
+    data: Arc<RwLock<Option<ThreePhase>>>,
+
+    phase_a: NodeRunnerInputPort<Vec<Complex<f32>>>,
+    phase_b: NodeRunnerInputPort<Vec<Complex<f32>>>,
+    phase_c: NodeRunnerInputPort<Vec<Complex<f32>>>,
+
+    window: NodeRunnerInputPort<FftWindow>,
+}
+
+impl NodeRunner for ThreePhaseAnalysisRunner {
+    fn run(self: Box<Self>) {
+        let mut window = self.window.recv();
+
+        type Pending = [VecDeque<Vec<Complex<f32>>>; 3];
+
+        // `skipped` counts the spectra of every phase still to be dropped, because they were
+        // dropped from the other phases before they arrived
+        fn push(
+            pending: &mut Pending,
+            skipped: &mut [usize; 3],
+            phase: usize,
+            spectrum: Vec<Complex<f32>>,
+        ) {
+            if skipped[phase] > 0 {
+                skipped[phase] -= 1;
+                return;
+            }
+
+            pending[phase].push_back(spectrum);
+
+            // the oldest spectra are dropped from all phases together, so the pairs stay aligned
+            if pending[phase].len() > MAX_PENDING_SPECTRA {
+                for (spectra, skipped) in pending.iter_mut().zip(skipped) {
+                    if spectra.pop_front().is_none() {
+                        *skipped += 1;
+                    }
+                }
+            }
+        }
+
+        // the harmonics of every channel emit their spectra for the same sample windows, so the
+        // n-th spectra of all phases belong together
+        let mut pending = Pending::default();
+        let mut skipped = [0; 3];
+
+        loop {
+            receive! {
+                (self.phase_a): spectrum => {
+                    push(&mut pending, &mut skipped, 0, spectrum);
+                },
+                (self.phase_b): spectrum => {
+                    push(&mut pending, &mut skipped, 1, spectrum);
+                },
+                (self.phase_c): spectrum => {
+                    push(&mut pending, &mut skipped, 2, spectrum);
+                },
+                (self.window): new_window => {
+                    window = new_window;
+                },
+            };
+
+            while pending.iter().all(|spectra| !spectra.is_empty()) {
+                let spectra = pending
+                    .each_mut()
+                    .map(|spectra| spectra.pop_front().unwrap());
+
+                if let Some(three_phase) = ThreePhase::from_spectra(&spectra, window) {
+                    *self.data.write().unwrap() = Some(three_phase);
+                }
+            }
+        }
+    }
+}
+
+/// Computes phasors, line-to-line voltages, symmetrical components and the voltage unbalance
+/// factor from the spectra of three phases.
+pub struct ThreePhaseAnalysis {
+    data: Arc<RwLock<Option<ThreePhase>>>,
+
+    pub phase_a: NodeConfigInputPort<Vec<Complex<f32>>>,
+    pub phase_b: NodeConfigInputPort<Vec<Complex<f32>>>,
+    pub phase_c: NodeConfigInputPort<Vec<Complex<f32>>>,
+
+    pub window: NodeConfigInputPort<FftWindow>,
+}
+
+impl ThreePhaseAnalysis {
+    pub fn new(data: Arc<RwLock<Option<ThreePhase>>>) -> Self {
+        Self {
+            data,
+
+            phase_a: NodeConfigInputPort::new(),
+            phase_b: NodeConfigInputPort::new(),
+            phase_c: NodeConfigInputPort::new(),
+
+            window: NodeConfigInputPort::new(),
+        }
+    }
+}
+
+impl NodeConfig for ThreePhaseAnalysis {
+    fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
+        Box::new(ThreePhaseAnalysisRunner {
+            data: self.data,
+
+            phase_a: self.phase_a.into(),
+            phase_b: self.phase_b.into(),
+            phase_c: self.phase_c.into(),
+
+            window: self.window.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustfft::FftPlanner;
+    use std::f64::consts::{SQRT_2, TAU};
+
+    const SAMPLE_RATE: f64 = 3125.0;
+    const FFT_SIZE: usize = 2048;
+
+    /// Windowed spectrum of a sine with an rms voltage and an angle in degrees.
+    fn spectrum(window: FftWindow, frequency: f64, rms: f64, angle: f64) -> Vec<Complex<f32>> {
+        let mut spectrum = window
+            .weights(FFT_SIZE)
+            .into_iter()
+            .enumerate()
+            .map(|(sample, weight)| {
+                let phase = TAU * frequency * sample as f64 / SAMPLE_RATE + angle.to_radians();
+
+                Complex::new((rms * SQRT_2 * phase.cos()) as f32 * weight, 0.0)
+            })
+            .collect::<Vec<_>>();
+
+        FftPlanner::new()
+            .plan_fft_forward(FFT_SIZE)
+            .process(&mut spectrum);
+
+        spectrum
+    }
+
+    fn assert_close(value: f64, expected: f64, tolerance: f64, what: &str) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{}: {} instead of {}",
+            what,
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn analyses_an_unbalanced_system() {
+        let rms = [230.0, 230.0, 200.0];
+        let angles: [f64; 3] = [0.0, -120.0, 120.0];
+
+        let phasors =
+            [0, 1, 2].map(|phase| Complex::from_polar(rms[phase], angles[phase].to_radians()));
+        let operator = Complex::from_polar(1.0, 2.0 * FRAC_PI_3);
+
+        let negative_sequence =
+            (phasors[0] + operator * operator * phasors[1] + operator * phasors[2]).norm() / 3.0;
+
+        for window in [
+            FftWindow::Hann,
+            FftWindow::BlackmanHarris,
+            FftWindow::FlatTop,
+        ] {
+            // 50 Hz lies 0.77 of a line past a line
+            let spectra = [0, 1, 2].map(|phase| spectrum(window, 50.0, rms[phase], angles[phase]));
+
+            let three_phase = ThreePhase::from_spectra(&spectra, window).unwrap();
+
+            for phase in 0..3 {
+                assert_close(
+                    three_phase.phases[phase].norm(),
+                    rms[phase],
+                    0.2,
+                    "magnitude",
+                );
+                assert_close(
+                    three_phase.phases[phase].arg().to_degrees(),
+                    angles[phase],
+                    0.05,
+                    "angle",
+                );
+
+                let line_to_line = (phasors[phase] - phasors[(phase + 1) % 3]).norm();
+                assert_close(
+                    three_phase.line_to_line[phase],
+                    line_to_line,
+                    0.3,
+                    "line to line",
+                );
+            }
+
+            // 230, 230 and 200 V sum to 660 V in the positive sequence
+            assert_close(
+                three_phase.positive_sequence.norm(),
+                220.0,
+                0.2,
+                "positive sequence",
+            );
+            assert_close(
+                three_phase.negative_sequence.norm(),
+                negative_sequence,
+                0.1,
+                "negative sequence",
+            );
+            assert_close(three_phase.zero_sequence.norm(), 10.0, 0.1, "zero sequence");
+            assert_close(
+                three_phase.unbalance,
+                negative_sequence / 220.0 * 100.0,
+                0.05,
+                "unbalance",
+            );
+        }
+    }
+}
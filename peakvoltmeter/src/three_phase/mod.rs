@@ -0,0 +1,156 @@
+mod analysis;
+
+use crate::{
+    application::{Precision, VoltageUnit},
+    channel::{channel_color, channel_name},
+    harmonics::FftWindow,
+    DARK_GRAY,
+};
+pub use analysis::ThreePhase;
+use analysis::ThreePhaseAnalysis;
+use conductor::{core::pipeline::Pipeline, prelude::*};
+use eframe::egui::Frame;
+use egui::{Color32, RichText, Rounding};
+use egui_plot::{Arrows, Line, Plot, PlotPoints};
+use rustfft::num_complex::Complex;
+use std::{
+    f64::consts::TAU,
+    sync::{Arc, RwLock},
+};
+
+pub const PHASES: usize = 3;
+
+pub struct ThreePhaseInputPorts {
+    // spectrum of the first three channels
+    pub spectra: [NodeConfigInputPort<Vec<Complex<f32>>>; PHASES],
+    pub window: NodeConfigInputPort<FftWindow>,
+}
+
+pub fn three_phase(data: Arc<RwLock<Option<ThreePhase>>>) -> Pipeline<ThreePhaseInputPorts, ()> {
+    let analysis = ThreePhaseAnalysis::new(data);
+
+    let input_ports = ThreePhaseInputPorts {
+        spectra: [
+            analysis.phase_a.clone(),
+            analysis.phase_b.clone(),
+            analysis.phase_c.clone(),
+        ],
+        window: analysis.window.clone(),
+    };
+
+    Pipeline::new(vec![Box::new(analysis)], input_ports, ())
+}
+
+pub struct PhasorDiagram {
+    data: Arc<RwLock<Option<ThreePhase>>>,
+}
+
+impl PhasorDiagram {
+    pub fn new(data: Arc<RwLock<Option<ThreePhase>>>) -> Self {
+        Self { data }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, unit: VoltageUnit, precision: Precision) {
+        let frame = Frame::default()
+            .inner_margin(10.0)
+            .fill(DARK_GRAY)
+            .rounding(Rounding::same(10.0));
+
+        frame.show(ui, |ui| {
+            ui.spacing_mut().item_spacing.y = 10.0;
+
+            ui.style_mut().visuals.extreme_bg_color = DARK_GRAY;
+            ui.style_mut().visuals.override_text_color = Some(Color32::WHITE);
+
+            ui.label(RichText::new("Three-Phase").size(16.0));
+
+            let Some(three_phase) = *self.data.read().unwrap() else {
+                ui.label("Waiting for data");
+                return;
+            };
+
+            let voltage = |value: f64| unit.apply_unit_with_precision(value, precision);
+
+            egui::Grid::new("Three-Phase Values")
+                .num_columns(3)
+                .show(ui, |ui| {
+                    for (phase, phasor) in three_phase.phases.iter().enumerate() {
+                        ui.label(RichText::new(channel_name(phase)).color(channel_color(phase)));
+                        ui.label(voltage(phasor.norm()));
+                        ui.label(format!(
+                            "{:.precision$}°",
+                            phasor.arg().to_degrees(),
+                            precision = precision
+                        ));
+                        ui.end_row();
+                    }
+
+                    for (name, value) in ["V12", "V23", "V31"].iter().zip(three_phase.line_to_line)
+                    {
+                        ui.label(*name);
+                        ui.label(voltage(value));
+                        ui.end_row();
+                    }
+
+                    ui.label("V+ / V- / V0");
+                    ui.label(format!(
+                        "{} / {} / {}",
+                        voltage(three_phase.positive_sequence.norm()),
+                        voltage(three_phase.negative_sequence.norm()),
+                        voltage(three_phase.zero_sequence.norm())
+                    ));
+                    ui.end_row();
+
+                    ui.label("VUF");
+                    ui.label(
+                        RichText::new(format!(
+                            "{:.precision$} %",
+                            three_phase.unbalance,
+                            precision = precision
+                        ))
+                        .strong(),
+                    );
+                    ui.end_row();
+                });
+
+            let radius = three_phase
+                .phases
+                .iter()
+                .map(|phasor| phasor.norm())
+                .fold(0.0, f64::max);
+
+            Plot::new("Phasor Diagram")
+                .data_aspect(1.0)
+                .show_axes(false)
+                .show_grid(false)
+                .allow_boxed_zoom(false)
+                .allow_drag(false)
+                .allow_zoom(false)
+                .allow_scroll(false)
+                .label_formatter(|_, _| "".to_owned())
+                .include_x(-radius)
+                .include_x(radius)
+                .include_y(-radius)
+                .include_y(radius)
+                .height(ui.available_width().min(ui.available_height()))
+                .show(ui, |plot_ui| {
+                    plot_ui.line(
+                        Line::new(PlotPoints::from_parametric_callback(
+                            |t| (radius * t.cos(), radius * t.sin()),
+                            0.0..=TAU,
+                            100,
+                        ))
+                        .color(Color32::GRAY),
+                    );
+
+                    for (phase, phasor) in three_phase.phases.iter().enumerate() {
+                        plot_ui.arrows(
+                            Arrows::new(vec![[0.0, 0.0]], vec![[phasor.re, phasor.im]])
+                                .color(channel_color(phase))
+                                .name(channel_name(phase)),
+                        );
+                    }
+                });
+        });
+    }
+}
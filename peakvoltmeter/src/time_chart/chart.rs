@@ -1,43 +1,133 @@
-use crate::settings::SampleRate;
+use crate::{
+    settings::{CalibrationFactor, SampleRate},
+    streams::Publisher,
+};
 
-use super::trigger::TriggerMessage;
+use super::{trigger::TriggerMessage, TimeChartCapture};
+use chrono::Local;
 use conductor::prelude::*;
-use std::sync::{Arc, RwLock};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+/// Samples of a channel cut into captures at the triggers. A trigger tells the index of the
+/// sample it fired at, so the captures of all channels end at the same sample, no matter whether
+/// the trigger arrives before or after the samples.
+#[derive(Default)]
+struct Captures {
+    cache: Vec<f32>,
+    // index of the first cached sample
+    start: u64,
+    // triggers the samples have not reached yet
+    triggers: VecDeque<u64>,
+}
+
+impl Captures {
+    fn trigger(&mut self, index: u64) {
+        self.triggers.push_back(index);
+    }
+
+    fn push(&mut self, sample: f32) {
+        self.cache.push(sample);
+    }
+
+    /// Next complete capture, with the index of the sample it ends before.
+    fn take(&mut self) -> Option<(u64, Vec<f32>)> {
+        let end = *self.triggers.front()?;
+
+        if self.start + (self.cache.len() as u64) < end {
+            return None;
+        }
+
+        self.triggers.pop_front();
+
+        let capture = self
+            .cache
+            .drain(..end.saturating_sub(self.start) as usize)
+            .collect();
+        self.start = self.start.max(end);
+
+        Some((end, capture))
+    }
+}
 
 struct ChartRunner {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    capture: Arc<RwLock<Option<TimeChartCapture>>>,
+    publisher: Publisher,
+    processed_samples: Arc<AtomicU64>,
 
     trigger: NodeRunnerInputPort<TriggerMessage>,
     input: NodeRunnerInputPort<f32>,
 
     sample_rate: NodeRunnerInputPort<SampleRate>,
+    calibration_factor: NodeRunnerInputPort<CalibrationFactor>,
 }
 
-impl NodeRunner for ChartRunner {
-    fn run(self: Box<Self>) {
+impl ChartRunner {
+    fn publish(
+        &self,
+        captures: &mut Captures,
+        sample_rate: SampleRate,
+        calibration_factor: CalibrationFactor,
+    ) {
         fn index_to_time(index: usize, sample_rate: SampleRate) -> f64 {
             index as f64 * (1.0 / sample_rate as f64)
         }
 
-        let mut cache = Vec::new();
+        while let Some((end, samples)) = captures.take() {
+            self.publisher.publish(1.0 / sample_rate as f64, || {
+                samples.iter().map(|&v| v as f64).collect()
+            });
+
+            *self.data.write().unwrap() = samples
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| [index_to_time(i, sample_rate), v as f64])
+                .collect();
+
+            *self.capture.write().unwrap() = Some(TimeChartCapture {
+                end,
+                time: Local::now(),
+                sample_rate,
+                calibration_factor,
+                samples,
+            });
+        }
+    }
+}
+
+impl NodeRunner for ChartRunner {
+    fn run(self: Box<Self>) {
+        let mut captures = Captures::default();
 
         let mut sample_rate = self.sample_rate.recv();
+        let mut calibration_factor = self.calibration_factor.recv();
 
         loop {
             receive! {
-                (self.trigger): _msg => {
-                    *self.data.write().unwrap() = std::mem::take(&mut cache)
-                        .into_iter()
-                        .enumerate()
-                        .map(|(i, v)| [index_to_time(i, sample_rate), v as f64])
-                        .collect();
+                (self.trigger): TriggerMessage::Triggered(index) => {
+                    captures.trigger(index);
+
+                    self.publish(&mut captures, sample_rate, calibration_factor);
                 },
                 (self.input): msg => {
-                    cache.push(msg);
+                    captures.push(msg);
+
+                    self.publish(&mut captures, sample_rate, calibration_factor);
+
+                    self.processed_samples.fetch_add(1, Ordering::Relaxed);
                 },
                 (self.sample_rate): new_sample_rate => {
                     sample_rate = new_sample_rate;
                 },
+                (self.calibration_factor): new_calibration_factor => {
+                    calibration_factor = new_calibration_factor;
+                },
             };
         }
     }
@@ -45,22 +135,37 @@ impl NodeRunner for ChartRunner {
 
 pub struct Chart {
     data: Arc<RwLock<Vec<[f64; 2]>>>,
+    capture: Arc<RwLock<Option<TimeChartCapture>>>,
+    publisher: Publisher,
+    // samples of all channels that made it through the pipeline
+    processed_samples: Arc<AtomicU64>,
 
     pub trigger: NodeConfigInputPort<TriggerMessage>,
     pub input: NodeConfigInputPort<f32>,
 
     pub sample_rate: NodeConfigInputPort<SampleRate>,
+    // the samples are calibrated already, the factor describes the capture
+    pub calibration_factor: NodeConfigInputPort<CalibrationFactor>,
 }
 
 impl Chart {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(
+        data: Arc<RwLock<Vec<[f64; 2]>>>,
+        capture: Arc<RwLock<Option<TimeChartCapture>>>,
+        publisher: Publisher,
+        processed_samples: Arc<AtomicU64>,
+    ) -> Self {
         Self {
             data,
+            capture,
+            publisher,
+            processed_samples,
 
             trigger: NodeConfigInputPort::new(),
             input: NodeConfigInputPort::new(),
 
             sample_rate: NodeConfigInputPort::new(),
+            calibration_factor: NodeConfigInputPort::new(),
         }
     }
 }
@@ -69,11 +174,61 @@ impl NodeConfig for Chart {
     fn into_runner(self: Box<Self>) -> Box<dyn NodeRunner + Send> {
         Box::new(ChartRunner {
             data: self.data,
+            capture: self.capture,
+            publisher: self.publisher,
+            processed_samples: self.processed_samples,
 
             trigger: self.trigger.into(),
             input: self.input.into(),
 
             sample_rate: self.sample_rate.into(),
+            calibration_factor: self.calibration_factor.into(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cuts_all_channels_at_the_same_sample() {
+        let samples = (0..100).map(|sample| sample as f32).collect::<Vec<_>>();
+        let triggers = [10, 35, 60];
+
+        // the trigger arrives after the samples on one channel, before them on the other
+        let mut late = Captures::default();
+        let mut early = Captures::default();
+
+        let mut late_captures = Vec::new();
+        let mut early_captures = Vec::new();
+
+        for &trigger in &triggers {
+            early.trigger(trigger);
+        }
+
+        for (index, &sample) in samples.iter().enumerate() {
+            late.push(sample);
+            early.push(sample);
+
+            if let Some(&trigger) = triggers
+                .iter()
+                .find(|&&trigger| trigger + 5 == index as u64)
+            {
+                late.trigger(trigger);
+            }
+
+            late_captures.extend(late.take());
+            early_captures.extend(early.take());
+        }
+
+        assert_eq!(late_captures, early_captures);
+        assert_eq!(
+            late_captures
+                .iter()
+                .map(|(end, capture)| (*end, capture[0], capture.len()))
+                .collect::<Vec<_>>(),
+            [(10, 0.0, 10), (35, 10.0, 25), (60, 35.0, 25)]
+        );
+    }
+}
@@ -3,54 +3,104 @@ mod trigger;
 
 use crate::{
     application::{calculate_precision, Precision, VoltageUnit, CHART_X_BOUND_MARGIN},
+    channel::{channel_color, channel_name, channel_views, ChannelLayout},
     coordinates_formatter,
-    settings::{SampleRate, TimeChartPeriods},
+    settings::{CalibrationFactor, Channel, SampleRate, TimeChartPeriods},
+    streams::Publisher,
 };
 use chart::Chart;
+use chrono::{DateTime, Local};
 use conductor::{core::pipeline::Pipeline, prelude::*};
-use egui::{Color32, RichText, Vec2b};
-use egui_plot::{Line, Plot, PlotPoints};
-use std::sync::{Arc, RwLock};
+use egui::{RichText, Vec2b};
+use egui_plot::{Legend, Line, Plot, PlotPoints};
+use std::sync::{atomic::AtomicU64, Arc, RwLock};
 use trigger::RisingEdgeTrigger;
 
+pub use trigger::ZeroCrossingDetector;
+
+/// Capture of a channel as the time chart shows it, with what describes it in an export.
+#[derive(Clone)]
+pub struct TimeChartCapture {
+    // index of the sample the capture ends before, the same for the captures of all channels taken
+    // at one trigger
+    pub end: u64,
+    // time the last sample was taken over
+    pub time: DateTime<Local>,
+    pub sample_rate: SampleRate,
+    pub calibration_factor: CalibrationFactor,
+    // calibrated
+    pub samples: Vec<f32>,
+}
+
 pub struct TimeChartInputPorts {
-    pub data: (NodeConfigInputPort<f32>, NodeConfigInputPort<f32>),
+    // signal the captures of all channels are triggered on
+    pub trigger: NodeConfigInputPort<f32>,
+    // one per channel
+    pub data: Vec<NodeConfigInputPort<f32>>,
     pub periods: NodeConfigInputPort<TimeChartPeriods>,
-    pub sample_rate: NodeConfigInputPort<SampleRate>,
+    pub sample_rate: Vec<NodeConfigInputPort<SampleRate>>,
+    pub calibration_factor: Vec<NodeConfigInputPort<CalibrationFactor>>,
 }
 
-pub fn time_chart(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Pipeline<TimeChartInputPorts, ()> {
+pub fn time_chart(
+    data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>,
+    captures: Vec<Arc<RwLock<Option<TimeChartCapture>>>>,
+    publishers: Vec<Publisher>,
+    processed_samples: Arc<AtomicU64>,
+) -> Pipeline<TimeChartInputPorts, ()> {
     let trigger = RisingEdgeTrigger::new(0.0);
 
     let period = Downsample::new();
 
-    let chart = Chart::new(data);
+    let charts = data
+        .into_iter()
+        .zip(captures)
+        .zip(publishers)
+        .map(|((data, capture), publisher)| {
+            Chart::new(data, capture, publisher, processed_samples.clone())
+        })
+        .collect::<Vec<_>>();
 
     trigger.trigger.connect(&period.input);
 
-    period.output.connect(&chart.trigger);
+    // a shared trigger keeps the phase relation between the channels visible
+    for chart in &charts {
+        period.output.connect(&chart.trigger);
+    }
 
     let input_ports = TimeChartInputPorts {
-        data: (trigger.input.clone(), chart.input.clone()),
+        trigger: trigger.input.clone(),
+        data: charts.iter().map(|chart| chart.input.clone()).collect(),
         periods: period.factor.clone(),
-        sample_rate: chart.sample_rate.clone(),
+        sample_rate: charts
+            .iter()
+            .map(|chart| chart.sample_rate.clone())
+            .collect(),
+        calibration_factor: charts
+            .iter()
+            .map(|chart| chart.calibration_factor.clone())
+            .collect(),
     };
 
-    Pipeline::new(
-        vec![Box::new(trigger), Box::new(period), Box::new(chart)],
-        input_ports,
-        (),
-    )
+    let mut nodes: Vec<Box<dyn NodeConfig>> = vec![Box::new(trigger), Box::new(period)];
+    nodes.extend(
+        charts
+            .into_iter()
+            .map(|chart| Box::new(chart) as Box<dyn NodeConfig>),
+    );
+
+    Pipeline::new(nodes, input_ports, ())
 }
 
 pub struct TimeChart {
-    pub data: Arc<RwLock<Vec<[f64; 2]>>>,
+    // one per channel
+    pub data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>,
 
     prev_x_bound: f64,
 }
 
 impl TimeChart {
-    pub fn new(data: Arc<RwLock<Vec<[f64; 2]>>>) -> Self {
+    pub fn new(data: Vec<Arc<RwLock<Vec<[f64; 2]>>>>) -> Self {
         Self {
             data,
             prev_x_bound: f64::NEG_INFINITY,
@@ -60,6 +110,7 @@ impl TimeChart {
     pub fn ui(
         &mut self,
         ui: &mut egui::Ui,
+        layout: ChannelLayout,
         chart_x_bound: usize,
         sample_rate: SampleRate,
         unit: VoltageUnit,
@@ -77,51 +128,64 @@ impl TimeChart {
 
                 let x_bound = (chart_x_bound + CHART_X_BOUND_MARGIN) as f64 / sample_rate as f64;
 
-                let mut plot = Plot::new("Time Chart")
-                    .auto_bounds(Vec2b::new(false, true))
-                    .y_axis_label("Voltage")
-                    .x_axis_label("Time")
-                    .allow_boxed_zoom(false)
-                    .allow_drag(false)
-                    .allow_zoom(false)
-                    .allow_scroll(false)
-                    .label_formatter(|_, _| "".to_owned())
-                    .coordinates_formatter(
-                        egui_plot::Corner::LeftTop,
-                        coordinates_formatter(unit, precision),
-                    )
-                    .x_axis_formatter(|grid_mark, range| {
-                        format!(
-                            "{:.precision$} s",
-                            grid_mark.value,
-                            precision = calculate_precision(range)
+                // We need to check if the x bound has changed to reset the plots, otherwise the
+                // plots will not update the x bound.
+                let reset = (self.prev_x_bound - x_bound).abs() > f64::EPSILON;
+                self.prev_x_bound = x_bound;
+
+                channel_views(ui, layout, self.data.len(), |ui, channels| {
+                    let mut plot = Plot::new(("Time Chart", channels.start))
+                        .auto_bounds(Vec2b::new(false, true))
+                        .y_axis_label("Voltage")
+                        .x_axis_label("Time")
+                        .allow_boxed_zoom(false)
+                        .allow_drag(false)
+                        .allow_zoom(false)
+                        .allow_scroll(false)
+                        .label_formatter(|_, _| "".to_owned())
+                        .coordinates_formatter(
+                            egui_plot::Corner::LeftTop,
+                            coordinates_formatter(unit, precision),
                         )
-                    })
-                    .y_axis_formatter(|grid_mark, range| {
-                        unit.apply_unit_with_precision(grid_mark.value, calculate_precision(range))
-                    })
-                    .include_x(0.0)
-                    .include_x(x_bound);
-
-                // We need to check if the x bound has changed to reset the plot, otherwise the
-                // plot will not update the x bound.
-                if (self.prev_x_bound - x_bound).abs() > f64::EPSILON {
-                    plot = plot.reset();
-                    self.prev_x_bound = x_bound;
-                }
-
-                plot.show(ui, |plot_ui| {
-                    plot_ui.line(self.signal());
+                        .x_axis_formatter(|grid_mark, range| {
+                            format!(
+                                "{:.precision$} s",
+                                grid_mark.value,
+                                precision = calculate_precision(range)
+                            )
+                        })
+                        .y_axis_formatter(|grid_mark, range| {
+                            unit.apply_unit_with_precision(
+                                grid_mark.value,
+                                calculate_precision(range),
+                            )
+                        })
+                        .include_x(0.0)
+                        .include_x(x_bound);
+
+                    if self.data.len() > 1 {
+                        plot = plot.legend(Legend::default());
+                    }
+
+                    if reset {
+                        plot = plot.reset();
+                    }
+
+                    plot.show(ui, |plot_ui| {
+                        for channel in channels {
+                            plot_ui.line(self.signal(channel));
+                        }
+                    });
                 });
             },
         );
     }
 
-    fn signal(&self) -> Line {
-        let plot_points = PlotPoints::from_iter(self.data.read().unwrap().clone());
+    fn signal(&self, channel: Channel) -> Line {
+        let plot_points = PlotPoints::from_iter(self.data[channel].read().unwrap().clone());
 
         Line::new(plot_points)
-            .color(Color32::LIGHT_BLUE)
-            .name("Signal")
+            .color(channel_color(channel))
+            .name(channel_name(channel))
     }
 }
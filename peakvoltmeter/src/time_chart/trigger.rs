@@ -1,8 +1,39 @@
 use conductor::prelude::*;
 
+/// Rising zero crossings of a signal, interpolated between the samples. A crossing only counts
+/// once the signal fell below the hysteresis since the last one, so noise and harmonics around
+/// zero do not add crossings.
+#[derive(Default)]
+pub struct ZeroCrossingDetector {
+    previous: f64,
+    // the signal fell below the hysteresis since the last crossing
+    armed: bool,
+}
+
+impl ZeroCrossingDetector {
+    /// Takes the next sample, returns the share of the sample period from the previous sample to
+    /// the crossing if the signal crossed zero.
+    pub fn push(&mut self, value: f64, hysteresis: f64) -> Option<f64> {
+        if value < -hysteresis {
+            self.armed = true;
+        }
+
+        let crossing = self.armed && self.previous < 0.0 && value >= 0.0;
+        let fraction = self.previous / (self.previous - value);
+
+        self.previous = value;
+
+        crossing.then(|| {
+            self.armed = false;
+            fraction
+        })
+    }
+}
+
 #[derive(Clone)]
 pub enum TriggerMessage {
-    Triggered,
+    // index of the sample the signal crossed the threshold at, counted from the first sample
+    Triggered(u64),
 }
 
 struct RisingEdgeTriggerRunner<T> {
@@ -15,12 +46,14 @@ struct RisingEdgeTriggerRunner<T> {
 impl<T: PartialOrd> NodeRunner for RisingEdgeTriggerRunner<T> {
     fn run(self: Box<Self>) {
         let mut previous_value = self.input.recv();
+        let mut index = 0;
 
         loop {
             let value = self.input.recv();
+            index += 1;
 
             if previous_value < self.threshold && value >= self.threshold {
-                self.trigger.send(&TriggerMessage::Triggered);
+                self.trigger.send(&TriggerMessage::Triggered(index));
             }
 
             previous_value = value;
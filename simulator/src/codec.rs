@@ -0,0 +1,81 @@
+use clap::ValueEnum;
+
+// bit of the sample format code that marks big endian samples
+const BIG_ENDIAN_FLAG: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Encoding {
+    I16,
+    I24,
+    I32,
+    U16,
+    U32,
+    F32,
+}
+
+impl Encoding {
+    pub fn size(&self) -> usize {
+        match self {
+            Encoding::I16 | Encoding::U16 => 2,
+            Encoding::I24 => 3,
+            Encoding::I32 | Encoding::U32 | Encoding::F32 => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Codec {
+    pub encoding: Encoding,
+    pub endianness: Endianness,
+}
+
+impl Codec {
+    /// Sample format code of the packet header, see `peakvoltmeter/src/ingest/codec.rs`.
+    pub fn code(&self) -> u8 {
+        let code = match self.encoding {
+            Encoding::I32 => 1,
+            Encoding::I16 => 2,
+            Encoding::I24 => 3,
+            Encoding::U16 => 4,
+            Encoding::U32 => 5,
+            Encoding::F32 => 6,
+        };
+
+        match self.endianness {
+            Endianness::Little => code,
+            Endianness::Big => code | BIG_ENDIAN_FLAG,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.encoding.size()
+    }
+
+    /// Appends a single sample to `buffer`. Samples that do not fit the encoding are clamped.
+    pub fn encode(&self, sample: i32, buffer: &mut Vec<u8>) {
+        let mut bytes = match self.encoding {
+            Encoding::I16 => (sample.clamp(i16::MIN as i32, i16::MAX as i32) as i16)
+                .to_le_bytes()
+                .to_vec(),
+            Encoding::I24 => sample.clamp(-(1 << 23), (1 << 23) - 1).to_le_bytes()[..3].to_vec(),
+            Encoding::I32 => sample.to_le_bytes().to_vec(),
+            Encoding::U16 => ((sample.clamp(i16::MIN as i32, i16::MAX as i32) + (1 << 15)) as u16)
+                .to_le_bytes()
+                .to_vec(),
+            Encoding::U32 => ((sample as i64 + (1 << 31)) as u32).to_le_bytes().to_vec(),
+            Encoding::F32 => (sample as f32).to_le_bytes().to_vec(),
+        };
+
+        if let Endianness::Big = self.endianness {
+            bytes.reverse();
+        }
+
+        buffer.extend_from_slice(&bytes);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::codec::Codec;
+
+// Framed packet format understood by the peakvoltmeter, see
+// `peakvoltmeter/src/ingest/packet.rs` for the layout.
+const PACKET_MAGIC: [u8; 4] = *b"PVMF";
+const PACKET_VERSION: u8 = 2;
+const HEADER_SIZE: usize = 16;
+
+// largest payload a single UDP datagram can carry over IPv4
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+pub const MAX_SAMPLES_PER_PACKET: usize = (MAX_DATAGRAM_SIZE - HEADER_SIZE) / size_of::<i32>();
+
+/// Encodes interleaved samples of `channels` channels into a framed packet.
+pub fn encode_frame(sequence: u32, channels: usize, samples: &[i32], codec: Codec) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_SIZE + samples.len() * codec.size());
+
+    frame.extend_from_slice(&PACKET_MAGIC);
+    frame.push(PACKET_VERSION);
+    frame.push(codec.code());
+    frame.extend_from_slice(&((samples.len() / channels) as u16).to_le_bytes());
+    frame.extend_from_slice(&sequence.to_le_bytes());
+    frame.push(channels as u8);
+    frame.extend_from_slice(&[0; 3]);
+
+    for &sample in samples {
+        codec.encode(sample, &mut frame);
+    }
+
+    frame
+}
+
+/// Encodes samples into a datagram without header. The first sample starts at `offset`, every
+/// following one `stride` bytes later, the bytes in between are zero.
+pub fn encode_raw(samples: &[i32], codec: Codec, offset: usize, stride: usize) -> Vec<u8> {
+    let stride = stride.max(codec.size());
+    let mut datagram = vec![0; offset];
+
+    for &sample in samples {
+        let start = datagram.len();
+
+        codec.encode(sample, &mut datagram);
+        datagram.resize(start + stride, 0);
+    }
+
+    datagram
+}
@@ -1,12 +1,18 @@
+mod codec;
 mod error;
+mod frame;
+mod transport;
 mod voltmeter;
 
 use clap::{
     builder::{OsStringValueParser, TypedValueParser},
     Parser,
 };
+use codec::{Encoding, Endianness};
 use error::ConductorSimResult;
+use frame::MAX_SAMPLES_PER_PACKET;
 use std::path::PathBuf;
+use transport::{Framing, Transport};
 
 #[derive(Debug, Clone)]
 pub enum Input {
@@ -46,11 +52,41 @@ pub struct Command {
     #[arg(short, long, default_value = ",")]
     pub delimiter: u8,
 
+    /// Address of the peakvoltmeter, or the address to listen on with `--transport tcp-server`
     #[arg(short, long)]
     pub target: String,
 
+    #[arg(long, value_enum, default_value_t = Transport::Udp)]
+    pub transport: Transport,
+
+    /// How packets are delimited in the TCP stream
+    #[arg(long, value_enum, default_value_t = Framing::LengthPrefixed)]
+    pub framing: Framing,
+
     #[arg(short, long)]
     pub sample_rate: u16,
+
+    #[arg(short = 'n', long, default_value_t = 64)]
+    #[clap(value_parser = clap::value_parser!(u16).range(1..=MAX_SAMPLES_PER_PACKET as i64))]
+    pub samples_per_packet: u16,
+
+    #[arg(short, long, value_enum, default_value_t = Encoding::I32)]
+    pub encoding: Encoding,
+
+    #[arg(short = 'b', long, value_enum, default_value_t = Endianness::Little)]
+    pub byte_order: Endianness,
+
+    /// Send datagrams without packet header
+    #[arg(short, long)]
+    pub raw: bool,
+
+    /// Byte offset of the first sample in raw datagrams
+    #[arg(long, default_value_t = 0, requires = "raw")]
+    pub offset: usize,
+
+    /// Distance in bytes between two samples in raw datagrams, defaults to the sample size
+    #[arg(long, default_value_t = 0, requires = "raw")]
+    pub stride: usize,
 }
 
 fn main() -> ConductorSimResult<()> {
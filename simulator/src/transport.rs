@@ -0,0 +1,73 @@
+use crate::error::ConductorSimResult;
+use clap::ValueEnum;
+use std::{
+    io::Write,
+    net::{TcpListener, TcpStream, UdpSocket},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Transport {
+    Udp,
+    // connects to the peakvoltmeter listening as TCP server
+    TcpClient,
+    // waits for the peakvoltmeter to connect as TCP client
+    TcpServer,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Framing {
+    // every packet is preceded by its length as little endian u32
+    LengthPrefixed,
+    // packets are sent back to back, the peakvoltmeter has to know their size
+    FixedWidth,
+}
+
+pub enum Sink {
+    Udp { socket: UdpSocket, target: String },
+    Tcp { stream: TcpStream, framing: Framing },
+}
+
+impl Sink {
+    /// Opens the connection to the peakvoltmeter. For `Transport::TcpServer` the target is the
+    /// address to listen on, this blocks until the peakvoltmeter has connected.
+    pub fn open(transport: Transport, target: &str, framing: Framing) -> ConductorSimResult<Self> {
+        let sink = match transport {
+            Transport::Udp => Sink::Udp {
+                socket: UdpSocket::bind("127.0.0.1:0")?,
+                target: target.to_string(),
+            },
+            Transport::TcpClient => Sink::Tcp {
+                stream: TcpStream::connect(target)?,
+                framing,
+            },
+            Transport::TcpServer => Sink::Tcp {
+                stream: TcpListener::bind(target)?.accept()?.0,
+                framing,
+            },
+        };
+
+        if let Sink::Tcp { stream, .. } = &sink {
+            // packets are small and paced, they should not wait for more data
+            stream.set_nodelay(true)?;
+        }
+
+        Ok(sink)
+    }
+
+    pub fn send(&mut self, packet: &[u8]) -> ConductorSimResult<()> {
+        match self {
+            Sink::Udp { socket, target } => {
+                socket.send_to(packet, target.as_str())?;
+            }
+            Sink::Tcp { stream, framing } => {
+                if *framing == Framing::LengthPrefixed {
+                    stream.write_all(&(packet.len() as u32).to_le_bytes())?;
+                }
+
+                stream.write_all(packet)?;
+            }
+        }
+
+        Ok(())
+    }
+}
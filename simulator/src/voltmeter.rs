@@ -1,14 +1,14 @@
-use crate::{error::ConductorSimResult, Command, Input};
-use serde::Deserialize;
-use std::{
-    net::UdpSocket,
-    time::{Duration, Instant},
+use crate::{
+    codec::Codec,
+    error::ConductorSimResult,
+    frame::{encode_frame, encode_raw, MAX_SAMPLES_PER_PACKET},
+    transport::{Framing, Sink, Transport},
+    Command, Input,
 };
+use std::time::{Duration, Instant};
 
-#[derive(Debug, Deserialize)]
-struct Row {
-    sample: i32,
-}
+// every column of the csv file is a channel
+type Row = Vec<i32>;
 
 fn read_csv(input: Input, delimiter: u8) -> ConductorSimResult<Vec<Row>> {
     let mut builder = csv::ReaderBuilder::new();
@@ -29,21 +29,54 @@ fn read_csv(input: Input, delimiter: u8) -> ConductorSimResult<Vec<Row>> {
 pub fn voltmeter(command: Command) -> ConductorSimResult<()> {
     let records = read_csv(command.file, command.delimiter)?;
 
-    let stream = UdpSocket::bind("127.0.0.1:0")?;
+    let mut sink = Sink::open(command.transport, &command.target, command.framing)?;
+
+    // fixed width packets must all have the same size
+    let fixed_width = command.transport != Transport::Udp && command.framing == Framing::FixedWidth;
+
+    let codec = Codec {
+        encoding: command.encoding,
+        endianness: command.byte_order,
+    };
+
+    let channels = records.first().map_or(1, |row| row.len()).max(1);
+
+    // packets carry the samples of all channels, so fewer samples per channel fit in
+    let samples_per_packet =
+        (command.samples_per_packet as usize).min(MAX_SAMPLES_PER_PACKET / channels);
 
     let seconds_per_sample = Duration::from_secs_f64(1.0 / (command.sample_rate as f64));
     let mut last_time = Instant::now();
+    let mut sequence: u32 = 0;
+
+    for records in records.chunks(samples_per_packet) {
+        if fixed_width && records.len() < samples_per_packet {
+            break;
+        }
+
+        let samples = records
+            .iter()
+            .flat_map(|record| record.iter().copied())
+            .collect::<Vec<_>>();
+
+        let packet = if command.raw {
+            encode_raw(&samples, codec, command.offset, command.stride)
+        } else {
+            encode_frame(sequence, channels, &samples, codec)
+        };
+
+        sink.send(&packet)?;
+        sequence = sequence.wrapping_add(1);
 
-    for record in records {
-        stream.send_to(&record.sample.to_ne_bytes(), &command.target)?;
+        let seconds_per_packet = seconds_per_sample * records.len() as u32;
 
-        while last_time.elapsed() < seconds_per_sample {
+        while last_time.elapsed() < seconds_per_packet {
             // TODO: Maybe use
             // std::hint::spin_loop();
             // or
             // thread::yield_now();
         }
-        last_time += seconds_per_sample;
+        last_time += seconds_per_packet;
     }
 
     Ok(())